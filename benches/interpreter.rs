@@ -0,0 +1,97 @@
+//! Interpreter throughput: `cargo bench --bench interpreter`.
+//!
+//! Runs every ROM in `roms/` for a fixed number of cycles on many machines, once
+//! decoding every instruction each time it runs and once with the decoded instruction
+//! cache, and prints the cycles per second of both. With `--features jit` it also runs
+//! them with the JIT.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chippers::chip8::Chip8;
+
+const MACHINES: usize = 1000;
+const CYCLES: u64 = 10_000;
+
+fn main() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    let mut paths: Vec<_> = std::fs::read_dir(roms)
+        .expect("the roms directory should exist")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+        .collect();
+    paths.sort();
+
+    print!(
+        "{:<24} {:>14} {:>14} {:>8}",
+        "rom", "uncached c/s", "cached c/s", "speedup"
+    );
+    #[cfg(feature = "jit")]
+    print!(" {:>14} {:>8}", "jit c/s", "speedup");
+    println!();
+    for path in paths {
+        let rom = std::fs::read(&path).unwrap();
+        let uncached = bench(&rom, false);
+        let cached = bench(&rom, true);
+        print!(
+            "{:<24} {:>14.0} {:>14.0} {:>7.2}x",
+            path.file_name().unwrap().to_string_lossy(),
+            throughput(uncached),
+            throughput(cached),
+            uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+        #[cfg(feature = "jit")]
+        {
+            let jit = bench_jit(&rom);
+            print!(
+                " {:>14.0} {:>7.2}x",
+                throughput(jit),
+                uncached.as_secs_f64() / jit.as_secs_f64()
+            );
+        }
+        println!();
+    }
+}
+
+fn machines(rom: &[u8]) -> Vec<Chip8> {
+    (0..MACHINES)
+        .map(|seed| {
+            let mut chip8 = Chip8::with_seed(seed as u64);
+            chip8.load_bytes(rom);
+            chip8
+        })
+        .collect()
+}
+
+/// Time taken to run every machine for `CYCLES` cycles.
+fn bench(rom: &[u8], cache: bool) -> Duration {
+    let mut machines = machines(rom);
+    for chip8 in &mut machines {
+        chip8.set_decode_cache(cache);
+    }
+    let start = Instant::now();
+    for chip8 in &mut machines {
+        chip8.run(CYCLES);
+    }
+    let elapsed = start.elapsed();
+    std::hint::black_box(&machines);
+    elapsed
+}
+
+/// Time taken to run every machine for `CYCLES` cycles with one JIT, compiling included.
+#[cfg(feature = "jit")]
+fn bench_jit(rom: &[u8]) -> Duration {
+    let mut machines = machines(rom);
+    let start = Instant::now();
+    let mut jit = chippers::jit::Jit::new().unwrap();
+    for chip8 in &mut machines {
+        jit.run(chip8, CYCLES);
+    }
+    let elapsed = start.elapsed();
+    std::hint::black_box(&machines);
+    elapsed
+}
+
+fn throughput(elapsed: Duration) -> f64 {
+    (MACHINES as u64 * CYCLES) as f64 / elapsed.as_secs_f64()
+}
@@ -0,0 +1,138 @@
+//! Runs many independent machines across threads with rayon, for fuzzing, reinforcement
+//! learning and compatibility sweeps.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use rayon::prelude::*;
+
+use crate::chip8::{Chip8, Cpu};
+use crate::keypad::KeyWait;
+
+// Machines move between the threads of the pool
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<Chip8>();
+};
+
+/// One machine to run: a ROM, the seed of its random numbers, the keys held in each frame
+/// and how many frames to run.
+#[derive(Debug, Clone, Copy)]
+pub struct Job<'a> {
+    pub rom: &'a [u8],
+    pub seed: u64,
+    /// Keys held down in each frame as a bitmask, key 0 in the lowest bit. Frames past the
+    /// end of the input hold no keys.
+    pub input: &'a [u16],
+    pub frames: u64,
+    pub cycles_per_frame: u32,
+    pub key_wait: KeyWait,
+}
+
+impl<'a> Job<'a> {
+    /// Runs `rom` for `frames` frames of 13 cycles with seed 0 and no input.
+    pub fn new(rom: &'a [u8], frames: u64) -> Self {
+        Self {
+            rom,
+            seed: 0,
+            input: &[],
+            frames,
+            cycles_per_frame: 13,
+            key_wait: KeyWait::default(),
+        }
+    }
+
+    /// Runs the job on the current thread.
+    pub fn run(&self) -> Outcome {
+        let mut chip8 = Chip8::with_seed(self.seed);
+        chip8.load_bytes(self.rom);
+        chip8.set_key_wait(self.key_wait);
+        let mut frames = 0;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            while frames < self.frames {
+                chip8.set_keys(self.input.get(frames as usize).copied().unwrap_or(0));
+                chip8.run(self.cycles_per_frame as u64);
+                chip8.tick_timers();
+                frames += 1;
+            }
+        }));
+        let panic = result.err().map(|payload| {
+            payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "unknown panic".to_string())
+        });
+        Outcome {
+            gfx: chip8.gfx,
+            cpu: chip8.cpu(),
+            state_hash: chip8.state_hash(),
+            frames,
+            panic,
+        }
+    }
+}
+
+/// How a job ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// The framebuffer, one byte of 0 or 1 per pixel, row by row.
+    pub gfx: [u8; 64 * 32],
+    pub cpu: Cpu,
+    /// [`Chip8::state_hash`] of the machine, covering memory too.
+    pub state_hash: u64,
+    /// Frames run to the end. Less than asked for if the machine panicked.
+    pub frames: u64,
+    /// The message of the panic that stopped the machine, such as an unknown instruction.
+    pub panic: Option<String>,
+}
+
+/// Runs every job on the rayon thread pool, returning their outcomes in the same order.
+///
+/// Jobs do not share anything, so the outcomes are the same as running them one by one.
+pub fn run_batch(jobs: &[Job]) -> Vec<Outcome> {
+    jobs.par_iter().map(Job::run).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn batches_match_jobs_run_one_by_one() {
+        let roms: Vec<Vec<u8>> = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/roms"))
+            .unwrap()
+            .map(|entry| std::fs::read(entry.unwrap().path()).unwrap())
+            .collect();
+        let mut rng = SmallRng::seed_from_u64(0);
+        let input: Vec<u16> = (0..300).map(|_| rng.gen()).collect();
+        // Runs into an unknown instruction
+        let broken = [0x60, 0x01, 0xFF, 0xFF];
+
+        let mut jobs = Vec::new();
+        for rom in &roms {
+            for seed in 0..3 {
+                jobs.push(Job {
+                    seed,
+                    input: &input[seed as usize * 100..],
+                    cycles_per_frame: 11 + seed as u32,
+                    key_wait: if seed == 1 {
+                        KeyWait::Press
+                    } else {
+                        KeyWait::Release
+                    },
+                    ..Job::new(rom, 300)
+                });
+            }
+        }
+        jobs.push(Job::new(&broken, 10));
+
+        let outcomes = run_batch(&jobs);
+        let expected: Vec<Outcome> = jobs.iter().map(Job::run).collect();
+        assert_eq!(outcomes, expected);
+        assert!(outcomes.last().unwrap().panic.is_some());
+        assert_eq!(outcomes.last().unwrap().frames, 0);
+    }
+}
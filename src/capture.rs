@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::chip8::{HEIGHT, WIDTH};
+use crate::render::{self, Filter, Palette};
+
+/// Largest screenshot, in pixels: a scale of 362, taking 1 GiB before it is compressed.
+const MAX_PNG_PIXELS: usize = 1 << 28;
+
+/// Saves the framebuffer `gfx` as a PNG image, upscaled by `scale` and with `filter` applied.
+///
+/// A `scale` of 1 with [`Filter::None`] writes the framebuffer at its native resolution.
+/// Scales making images larger than 2^28 pixels are rejected.
+pub fn save_png<P>(
+    path: P,
+    gfx: &[u8],
+    scale: u32,
+    palette: &Palette,
+    filter: Filter,
+) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let (width, height) = scaled_size(scale, u32::MAX)?;
+    let len = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|&pixels| pixels <= MAX_PNG_PIXELS)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "scale {} makes a {}x{} screenshot, larger than the {} pixels allowed",
+                    scale, width, height, MAX_PNG_PIXELS
+                ),
+            )
+        })?;
+    let mut frame = vec![0; len];
+    render::rasterize(gfx, scale, palette, filter, &mut frame);
+
+    let file = File::create(path.as_ref())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&frame)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Records emulated frames to an animated GIF or a raw Y4M video stream.
+///
+/// The format is chosen from the file extension. Every call to
+/// [`Recorder::record_frame`] is one emulated frame, so the output plays back at the
+/// emulated frame rate no matter how fast the host managed to draw.
+pub struct Recorder {
+    output: Output,
+    width: u32,
+    height: u32,
+    scale: u32,
+    frames_per_second: u32,
+    frame: u64,
+}
+
+enum Output {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        pending: Option<PendingFrame>,
+    },
+    Y4m {
+        writer: BufWriter<File>,
+        luma: [u8; 2],
+        chroma: [[u8; 2]; 2],
+    },
+}
+
+/// A GIF frame held back until we know how long it stays on screen.
+struct PendingFrame {
+    gfx: Vec<u8>,
+    start: u64,
+}
+
+impl Recorder {
+    pub fn create<P>(
+        path: P,
+        scale: u32,
+        palette: &Palette,
+        frames_per_second: u32,
+    ) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let is_gif = extension.is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+        // GIF sizes are 16-bit
+        let max = if is_gif { u16::MAX as u32 } else { u32::MAX };
+        let (width, height) = scaled_size(scale, max)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let output = match extension {
+            Some(_) if is_gif => {
+                let colors = [&palette.off[..3], &palette.on[..3]].concat();
+                let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &colors)
+                    .map_err(io::Error::other)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(io::Error::other)?;
+                Output::Gif {
+                    encoder,
+                    pending: None,
+                }
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("y4m") => {
+                writeln!(
+                    writer,
+                    "YUV4MPEG2 W{width} H{height} F{frames_per_second}:1 Ip A1:1 C444 XCOLORRANGE=FULL"
+                )?;
+                let (off_y, off_u, off_v) = rgb_to_yuv(palette.off);
+                let (on_y, on_u, on_v) = rgb_to_yuv(palette.on);
+                Output::Y4m {
+                    writer,
+                    luma: [off_y, on_y],
+                    chroma: [[off_u, on_u], [off_v, on_v]],
+                }
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "recordings must end in .gif or .y4m",
+                ))
+            }
+        };
+
+        Ok(Self {
+            output,
+            width,
+            height,
+            scale,
+            frames_per_second,
+            frame: 0,
+        })
+    }
+
+    /// Appends one emulated frame showing `gfx` to the recording.
+    pub fn record_frame(&mut self, gfx: &[u8]) -> io::Result<()> {
+        let now = self.centiseconds(self.frame);
+        self.frame += 1;
+
+        match &mut self.output {
+            Output::Gif { encoder, pending } => match pending {
+                // Unchanged frames just stay on screen longer
+                Some(frame) if frame.gfx == gfx => {}
+                // Browsers stretch delays below 2cs, so very short frames are replaced instead
+                Some(frame) if now - frame.start < 2 => frame.gfx.copy_from_slice(gfx),
+                _ => {
+                    if let Some(frame) = pending.take() {
+                        let delay = now - frame.start;
+                        write_gif_frame(encoder, &frame.gfx, self.scale, delay)?;
+                    }
+                    *pending = Some(PendingFrame {
+                        gfx: gfx.to_vec(),
+                        start: now,
+                    });
+                }
+            },
+            Output::Y4m {
+                writer,
+                luma,
+                chroma,
+            } => {
+                writer.write_all(b"FRAME\n")?;
+                let mut plane = vec![0; (self.width * self.height) as usize];
+                for values in [*luma, chroma[0], chroma[1]] {
+                    upscale(gfx, self.scale, &mut plane, |lit| values[lit as usize]);
+                    writer.write_all(&plane)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes any buffered frames and closes the file.
+    pub fn finish(self) -> io::Result<()> {
+        let end = self.centiseconds(self.frame);
+        match self.output {
+            Output::Gif {
+                mut encoder,
+                pending,
+            } => {
+                if let Some(frame) = pending {
+                    let delay = (end - frame.start).max(2);
+                    write_gif_frame(&mut encoder, &frame.gfx, self.scale, delay)?;
+                }
+                encoder.into_inner()?.flush()
+            }
+            Output::Y4m { mut writer, .. } => writer.flush(),
+        }
+    }
+
+    /// Start time of `frame` in the GIF time base of hundredths of a second.
+    fn centiseconds(&self, frame: u64) -> u64 {
+        (frame * 100 + self.frames_per_second as u64 / 2) / self.frames_per_second as u64
+    }
+}
+
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    gfx: &[u8],
+    scale: u32,
+    delay: u64,
+) -> io::Result<()> {
+    let (width, height) = (scale * WIDTH, scale * HEIGHT);
+    let mut pixels = vec![0; (width * height) as usize];
+    upscale(gfx, scale, &mut pixels, |lit| lit);
+
+    // Recorder::create checked that the size fits
+    let mut frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+    frame.delay = delay.min(u16::MAX as u64) as u16;
+    encoder.write_frame(&frame).map_err(io::Error::other)
+}
+
+/// Size of a frame upscaled by `scale`, which has to be at least 1 and keep both sides
+/// within `max` pixels.
+fn scaled_size(scale: u32, max: u32) -> io::Result<(u32, u32)> {
+    match (scale.checked_mul(WIDTH), scale.checked_mul(HEIGHT)) {
+        (Some(width), Some(height)) if scale > 0 && width <= max => Ok((width, height)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "scale {} is out of range, images can be 1 to {} times the native size",
+                scale,
+                max / WIDTH
+            ),
+        )),
+    }
+}
+
+/// Fills `out` with one byte per upscaled pixel, mapping each framebuffer value through `value`.
+fn upscale(gfx: &[u8], scale: u32, out: &mut [u8], value: impl Fn(u8) -> u8) {
+    let out_width = (scale * WIDTH) as usize;
+    for (i, byte) in out.iter_mut().enumerate() {
+        let x = (i % out_width) / scale as usize;
+        let y = (i / out_width) / scale as usize;
+        *byte = value(gfx[x + y * WIDTH as usize]);
+    }
+}
+
+/// Converts an RGBA color to full-range BT.601 Y'CbCr.
+fn rgb_to_yuv(rgba: [u8; 4]) -> (u8, u8, u8) {
+    let (r, g, b) = (rgba[0] as f32, rgba[1] as f32, rgba[2] as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let u = 128.0 + (b - y) * 0.564;
+    let v = 128.0 + (r - y) * 0.713;
+    (
+        y.round() as u8,
+        u.round().clamp(0.0, 255.0) as u8,
+        v.round().clamp(0.0, 255.0) as u8,
+    )
+}
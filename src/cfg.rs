@@ -0,0 +1,571 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::chip8;
+use crate::disasm::Instruction;
+
+/// Most entries a Bnnn jump table can have, one jump for every even value of V0.
+const MAX_TABLE_LEN: u16 = 128;
+
+/// An instruction of a block: its address, opcode and what the interpreter decodes it to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+}
+
+/// How control gets from one block to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    /// Falls through to the next instruction, or returns there from a call.
+    Next,
+    Jump,
+    /// Skips the next instruction.
+    Skip,
+    /// Calls a subroutine, which returns to the [`EdgeKind::Next`] edge of the same block.
+    Call,
+    /// One of the targets of a Bnnn jump table.
+    Table,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub target: u16,
+}
+
+/// Straight-line code that is only entered at its start and only leaves at its end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: u16,
+    pub lines: Vec<Line>,
+    /// Where control goes after the last instruction. Blocks ending in 00EE, in a Bnnn whose
+    /// targets are unknown or in anything that stops the machine have none.
+    pub edges: Vec<Edge>,
+}
+
+impl Block {
+    /// Address just past the last instruction.
+    pub fn end(&self) -> u16 {
+        self.start + 2 * self.lines.len() as u16
+    }
+
+    pub fn last(&self) -> &Line {
+        self.lines.last().unwrap()
+    }
+}
+
+/// The control-flow graph of a ROM, loaded at 200.
+///
+/// Code is found by following every jump, skip, call and return address from 200. Bnnn has
+/// no fixed target, so two heuristics guess where it goes: after straight-line code that
+/// loads V0 with `LD V0, nn` it goes to nnn plus that constant, and otherwise the run of 1nnn
+/// jumps starting at nnn is taken as its jump table. Bnnn jumps left with no targets are listed by
+/// [`Cfg::unresolved_jumps`]. Code written at run time cannot be found.
+pub struct Cfg {
+    rom_end: u16,
+    blocks: BTreeMap<u16, Block>,
+    /// Blocks of each subroutine by entry address, the main program at 200 first.
+    subroutines: BTreeMap<u16, BTreeSet<u16>>,
+    unresolved: Vec<u16>,
+    /// Bytes of the ROM that are part of a reachable instruction.
+    code: Vec<bool>,
+}
+
+impl Cfg {
+    pub fn new(rom: &[u8]) -> Self {
+        let rom_end = (0x200 + rom.len()).min(0x1000) as u16;
+        let fetch = |address: u16| fetch(rom, address);
+
+        let mut reachable = BTreeMap::new();
+        let mut leaders = BTreeSet::from([0x200]);
+        let mut unresolved = Vec::new();
+        let mut pending = vec![0x200];
+        while let Some(address) = pending.pop() {
+            if reachable.contains_key(&address) {
+                continue;
+            }
+            let Some(line) = fetch(address) else {
+                continue;
+            };
+            let edges = edges(rom, &line);
+            if ends_block(line.instruction) {
+                leaders.extend(edges.iter().map(|edge| edge.target));
+                if edges.is_empty() && matches!(line.instruction, Instruction::JumpV0 { .. }) {
+                    unresolved.push(address);
+                }
+            }
+            pending.extend(edges.iter().map(|edge| edge.target));
+            reachable.insert(address, (line, edges));
+        }
+
+        let mut blocks = BTreeMap::new();
+        let mut code = vec![false; rom.len()];
+        for &start in leaders.iter().filter(|start| reachable.contains_key(start)) {
+            let mut lines = Vec::new();
+            let mut address = start;
+            let edges = loop {
+                let (line, edges) = &reachable[&address];
+                lines.push(*line);
+                address += 2;
+                if ends_block(line.instruction) || leaders.contains(&address) {
+                    break edges.clone();
+                }
+                // Code running into 0000 or the end of the ROM stops there
+                if !reachable.contains_key(&address) {
+                    break Vec::new();
+                }
+            };
+            for line in &lines {
+                let offset = line.address as usize - 0x200;
+                code[offset..offset + 2].fill(true);
+            }
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    lines,
+                    edges,
+                },
+            );
+        }
+
+        let mut cfg = Self {
+            rom_end,
+            blocks,
+            subroutines: BTreeMap::new(),
+            unresolved,
+            code,
+        };
+        cfg.subroutines = cfg.find_subroutines();
+        cfg
+    }
+
+    /// Blocks by start address.
+    pub fn blocks(&self) -> &BTreeMap<u16, Block> {
+        &self.blocks
+    }
+
+    /// Number of instructions in all blocks.
+    pub fn instructions(&self) -> usize {
+        self.blocks.values().map(|block| block.lines.len()).sum()
+    }
+
+    /// Blocks of each subroutine by entry address, with the main program at 200. A block
+    /// reached from several subroutines belongs to all of them.
+    pub fn subroutines(&self) -> &BTreeMap<u16, BTreeSet<u16>> {
+        &self.subroutines
+    }
+
+    /// The call graph: the subroutines each subroutine calls, by entry address.
+    pub fn calls(&self) -> BTreeMap<u16, BTreeSet<u16>> {
+        self.subroutines
+            .iter()
+            .map(|(&entry, blocks)| {
+                let callees = blocks
+                    .iter()
+                    .flat_map(|start| &self.blocks[start].edges)
+                    .filter(|edge| edge.kind == EdgeKind::Call)
+                    .map(|edge| edge.target)
+                    .collect();
+                (entry, callees)
+            })
+            .collect()
+    }
+
+    /// Addresses of the Bnnn jumps whose targets could not be worked out.
+    pub fn unresolved_jumps(&self) -> &[u16] {
+        &self.unresolved
+    }
+
+    /// Ranges of the ROM that no reachable instruction covers, which are usually sprites
+    /// and other data.
+    pub fn data(&self) -> Vec<Range<u16>> {
+        let mut ranges: Vec<Range<u16>> = Vec::new();
+        for address in 0x200..self.rom_end {
+            if self.code[address as usize - 0x200] {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == address => range.end += 1,
+                _ => ranges.push(address..address + 1),
+            }
+        }
+        ranges
+    }
+
+    /// Follows every edge but calls from the entry of each subroutine.
+    fn find_subroutines(&self) -> BTreeMap<u16, BTreeSet<u16>> {
+        let mut entries = BTreeSet::from([0x200]);
+        entries.extend(
+            self.blocks
+                .values()
+                .flat_map(|block| &block.edges)
+                .filter(|edge| edge.kind == EdgeKind::Call)
+                .map(|edge| edge.target)
+                .filter(|target| self.blocks.contains_key(target)),
+        );
+        entries
+            .into_iter()
+            .filter(|entry| self.blocks.contains_key(entry))
+            .map(|entry| {
+                let mut blocks = BTreeSet::new();
+                let mut pending = vec![entry];
+                while let Some(start) = pending.pop() {
+                    if !blocks.insert(start) {
+                        continue;
+                    }
+                    pending.extend(
+                        self.blocks[&start]
+                            .edges
+                            .iter()
+                            .filter(|edge| edge.kind != EdgeKind::Call)
+                            .map(|edge| edge.target)
+                            .filter(|target| self.blocks.contains_key(target)),
+                    );
+                }
+                (entry, blocks)
+            })
+            .collect()
+    }
+
+    /// Writes the graph in Graphviz DOT, titled `name`: one node per block listing its
+    /// instructions, grouped by subroutine, and a node for each range of data.
+    ///
+    /// Jumps are bold, skips dashed, calls dotted and jump table entries labeled with their
+    /// index. A block shared by several subroutines is drawn with the first of them.
+    pub fn write_dot(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        writeln!(out, "digraph \"{}\" {{", escape(name))?;
+        writeln!(out, "    label=\"{}\";", escape(name))?;
+        writeln!(out, "    node [shape=box, fontname=monospace];")?;
+
+        let mut drawn = BTreeSet::new();
+        for (&entry, blocks) in &self.subroutines {
+            writeln!(out, "    subgraph cluster_{:03X} {{", entry)?;
+            writeln!(out, "        label=\"{}\";", subroutine_name(entry))?;
+            for &start in blocks {
+                if drawn.insert(start) {
+                    self.write_node(out, &self.blocks[&start])?;
+                }
+            }
+            writeln!(out, "    }}")?;
+        }
+        for range in self.data() {
+            writeln!(
+                out,
+                "    data_{:03X} [shape=note, label=\"data {:03X}-{:03X}\\n{} bytes\"];",
+                range.start,
+                range.start,
+                range.end - 1,
+                range.len()
+            )?;
+        }
+
+        for block in self.blocks.values() {
+            let mut table_index = 0;
+            for edge in &block.edges {
+                if !self.blocks.contains_key(&edge.target) {
+                    continue;
+                }
+                let style = match edge.kind {
+                    EdgeKind::Next => String::new(),
+                    EdgeKind::Jump => " [style=bold]".to_string(),
+                    EdgeKind::Skip => " [style=dashed, label=\"skip\"]".to_string(),
+                    EdgeKind::Call => " [style=dotted, color=blue, label=\"call\"]".to_string(),
+                    EdgeKind::Table => {
+                        table_index += 1;
+                        format!(" [color=darkorange, label=\"{}\"]", table_index - 1)
+                    }
+                };
+                writeln!(
+                    out,
+                    "    block_{:03X} -> block_{:03X}{};",
+                    block.start, edge.target, style
+                )?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Writes the call graph in Graphviz DOT, titled `name`.
+    pub fn write_call_graph_dot(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        writeln!(out, "digraph \"{}\" {{", escape(name))?;
+        writeln!(out, "    label=\"{}\";", escape(name))?;
+        writeln!(out, "    node [shape=box, fontname=monospace];")?;
+        for (&entry, blocks) in &self.subroutines {
+            let instructions: usize = blocks
+                .iter()
+                .map(|start| self.blocks[start].lines.len())
+                .sum();
+            writeln!(
+                out,
+                "    sub_{:03X} [label=\"{}\\n{} instructions\"];",
+                entry,
+                subroutine_name(entry),
+                instructions
+            )?;
+        }
+        for (entry, callees) in self.calls() {
+            for callee in callees {
+                writeln!(out, "    sub_{:03X} -> sub_{:03X};", entry, callee)?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    fn write_node(&self, out: &mut impl Write, block: &Block) -> io::Result<()> {
+        let mut label = String::new();
+        for line in &block.lines {
+            label += &escape(&format!(
+                "{:03X}  {:04X}  {}",
+                line.address, line.opcode, line.instruction
+            ));
+            // Left-justified line break
+            label += "\\l";
+        }
+        writeln!(
+            out,
+            "        block_{:03X} [label=\"{}\"];",
+            block.start, label
+        )
+    }
+}
+
+/// The instruction at `address` of `rom`, if there is one to run: 0000 stops the machine.
+fn fetch(rom: &[u8], address: u16) -> Option<Line> {
+    let offset = (address as usize).checked_sub(0x200)?;
+    if offset + 2 > rom.len() || address as usize + 2 > 0x1000 {
+        return None;
+    }
+    let opcode = (rom[offset] as u16) << 8 | rom[offset + 1] as u16;
+    (opcode != 0).then(|| Line {
+        address,
+        opcode,
+        instruction: chip8::decode(opcode),
+    })
+}
+
+/// Whether `instruction` changes the flow of control, which ends a block.
+pub(crate) fn ends_block(instruction: Instruction) -> bool {
+    use Instruction::*;
+    matches!(
+        instruction,
+        Jump { .. }
+            | Call { .. }
+            | Return
+            | JumpV0 { .. }
+            | Sys { .. }
+            | Unknown(_)
+            | SkipEqImm { .. }
+            | SkipNeImm { .. }
+            | SkipEq { .. }
+            | SkipNe { .. }
+            | SkipKey { .. }
+            | SkipNotKey { .. }
+    )
+}
+
+/// Where control can go after `line` of `rom`.
+fn edges(rom: &[u8], line: &Line) -> Vec<Edge> {
+    use Instruction::*;
+    let edge = |kind, target| Edge { kind, target };
+    let next = line.address + 2;
+    match line.instruction {
+        Jump { nnn } => vec![edge(EdgeKind::Jump, nnn)],
+        Call { nnn } => vec![edge(EdgeKind::Call, nnn), edge(EdgeKind::Next, next)],
+        Return | Sys { .. } | Unknown(_) => Vec::new(),
+        JumpV0 { nnn } => jump_table(rom, line.address, nnn)
+            .into_iter()
+            .map(|target| edge(EdgeKind::Table, target))
+            .collect(),
+        SkipEqImm { .. }
+        | SkipNeImm { .. }
+        | SkipEq { .. }
+        | SkipNe { .. }
+        | SkipKey { .. }
+        | SkipNotKey { .. } => vec![edge(EdgeKind::Next, next), edge(EdgeKind::Skip, next + 2)],
+        _ => vec![edge(EdgeKind::Next, next)],
+    }
+}
+
+/// Guesses the targets of the Bnnn at `address`: nnn plus V0 if the straight-line code
+/// before it last loads V0 with a constant, or else every entry of the run of 1nnn jumps at
+/// nnn.
+fn jump_table(rom: &[u8], address: u16, nnn: u16) -> Vec<u16> {
+    let mut before = address;
+    while let Some(line) = before
+        .checked_sub(2)
+        .and_then(|address| fetch(rom, address))
+    {
+        if ends_block(line.instruction) {
+            break;
+        }
+        match line.instruction {
+            Instruction::LoadImm { x: 0, nn } => return vec![nnn + nn as u16],
+            instruction if writes(instruction, 0) => break,
+            _ => before = line.address,
+        }
+    }
+    (0..MAX_TABLE_LEN)
+        .map(|index| nnn + 2 * index)
+        .take_while(|&entry| {
+            matches!(
+                fetch(rom, entry),
+                Some(Line {
+                    instruction: Instruction::Jump { .. },
+                    ..
+                })
+            )
+        })
+        .collect()
+}
+
+/// Whether `instruction` can change `register`, including VF as a flag.
+pub(crate) fn writes(instruction: Instruction, register: u8) -> bool {
+    use Instruction::*;
+    match instruction {
+        LoadImm { x, .. }
+        | AddImm { x, .. }
+        | Move { x, .. }
+        | Random { x, .. }
+        | GetDelay { x }
+        | WaitKey { x } => x == register,
+        Or { x, .. }
+        | And { x, .. }
+        | Xor { x, .. }
+        | Add { x, .. }
+        | Sub { x, .. }
+        | ShiftRight { x, .. }
+        | SubReverse { x, .. }
+        | ShiftLeft { x, .. } => x == register || register == 0xF,
+        Draw { .. } => register == 0xF,
+        // Loads V0 to Vx
+        Load { x } => register <= x,
+        _ => false,
+    }
+}
+
+fn subroutine_name(entry: u16) -> String {
+    if entry == 0x200 {
+        "main".to_string()
+    } else {
+        format!("sub_{:03X}", entry)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assemble;
+
+    fn starts(cfg: &Cfg) -> Vec<u16> {
+        cfg.blocks().keys().copied().collect()
+    }
+
+    fn targets(cfg: &Cfg, block: u16) -> Vec<(EdgeKind, u16)> {
+        cfg.blocks()[&block]
+            .edges
+            .iter()
+            .map(|edge| (edge.kind, edge.target))
+            .collect()
+    }
+
+    #[test]
+    fn jump_after_constant_v0_has_one_target() {
+        let cfg = Cfg::new(&assemble(&[
+            0x6004, // 200: LD V0, 4
+            0x6105, // 202: LD V1, 5
+            0xB210, // 204: JP V0, 210
+            0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, // 206: data
+            0x1214, // 214: JP 214
+        ]));
+        assert_eq!(starts(&cfg), [0x200, 0x214]);
+        assert_eq!(targets(&cfg, 0x200), [(EdgeKind::Table, 0x214)]);
+        assert_eq!(cfg.unresolved_jumps(), []);
+        assert_eq!(cfg.data(), vec![0x206..0x214]);
+    }
+
+    #[test]
+    fn jump_after_changing_v0_is_unresolved() {
+        let cfg = Cfg::new(&assemble(&[
+            0x6004, // 200: LD V0, 4
+            0x7001, // 202: ADD V0, 1
+            0xB300, // 204: JP V0, 300
+        ]));
+        assert_eq!(starts(&cfg), [0x200]);
+        assert_eq!(targets(&cfg, 0x200), []);
+        assert_eq!(cfg.unresolved_jumps(), [0x204]);
+    }
+
+    #[test]
+    fn jump_table_runs_until_the_first_other_instruction() {
+        let cfg = Cfg::new(&assemble(&[
+            0xC006, // 200: RND V0, 06
+            0xB206, // 202: JP V0, 206
+            0x0000, // 204: data
+            0x120E, // 206: JP 20E
+            0x1210, // 208: JP 210
+            0x1200, // 20A: JP 200
+            0x00E0, // 20C: not part of the table
+            0x120E, // 20E: JP 20E
+            0x1210, // 210: JP 210
+        ]));
+        assert_eq!(
+            targets(&cfg, 0x200),
+            [
+                (EdgeKind::Table, 0x206),
+                (EdgeKind::Table, 0x208),
+                (EdgeKind::Table, 0x20A)
+            ]
+        );
+        assert_eq!(starts(&cfg), [0x200, 0x206, 0x208, 0x20A, 0x20E, 0x210]);
+        assert_eq!(cfg.data(), [0x204..0x206, 0x20C..0x20E]);
+    }
+
+    #[test]
+    fn finds_subroutines_and_their_calls() {
+        let cfg = Cfg::new(&assemble(&[
+            0x2208, // 200: CALL 208
+            0x220C, // 202: CALL 20C
+            0x1204, // 204: JP 204
+            0x0000, // 206: data
+            0x220C, // 208: CALL 20C
+            0x00EE, // 20A: RET
+            0x3000, // 20C: SE V0, 0
+            0x7001, // 20E: ADD V0, 1
+            0x00EE, // 210: RET
+        ]));
+        let subroutines: Vec<(u16, Vec<u16>)> = cfg
+            .subroutines()
+            .iter()
+            .map(|(&entry, blocks)| (entry, blocks.iter().copied().collect()))
+            .collect();
+        assert_eq!(
+            subroutines,
+            [
+                (0x200, vec![0x200, 0x202, 0x204]),
+                (0x208, vec![0x208, 0x20A]),
+                (0x20C, vec![0x20C, 0x20E, 0x210]),
+            ]
+        );
+        assert_eq!(
+            cfg.calls(),
+            BTreeMap::from([
+                (0x200, BTreeSet::from([0x208, 0x20C])),
+                (0x208, BTreeSet::from([0x20C])),
+                (0x20C, BTreeSet::new()),
+            ])
+        );
+        assert_eq!(
+            targets(&cfg, 0x20C),
+            [(EdgeKind::Next, 0x20E), (EdgeKind::Skip, 0x210)]
+        );
+        assert_eq!(cfg.instructions(), 8);
+    }
+}
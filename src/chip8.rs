@@ -1,8 +1,25 @@
-use std::{fs, path::Path, time::Instant};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
 
-use rand::Rng;
+use core::hash::Hasher;
 
-pub struct Chip8 {
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use alloc::sync::Arc;
+
+use crate::disasm::Instruction;
+use crate::keypad::{KeyWait, Keypad};
+
+pub const WIDTH: u32 = 64;
+pub const HEIGHT: u32 = 32;
+
+/// The CHIP-8 machine.
+///
+/// Timers are not tied to any clock: the host calls [`Chip8::tick_timers`] 60 times per
+/// emulated second. Cxnn draws from the random number generator `R`.
+#[derive(Clone)]
+pub struct Chip8<R = SmallRng> {
     pub gfx: [u8; 64 * 32],
     memory: Memory,
     stack: [u16; 16],
@@ -12,16 +29,30 @@ pub struct Chip8 {
     pc: u16,
     delay_timer: u8,
     sound_timer: u8,
-    pub key: [u8; 16],
-    pub draw_flag: bool,
-    pub await_key_flag: bool,
-    pub await_key_notify: bool,
-    pub await_key_pressed: u8,
-    last_timer: Instant,
+    keypad: Keypad,
+    /// Bitmask of framebuffer rows changed since the frontend last cleared it.
+    pub dirty_rows: u32,
+    rng: R,
 }
 
 impl Chip8 {
+    /// Creates a machine with a randomly seeded RNG, or a fixed seed without `std`.
     pub fn new() -> Self {
+        #[cfg(feature = "std")]
+        let rng = SmallRng::from_entropy();
+        #[cfg(not(feature = "std"))]
+        let rng = SmallRng::seed_from_u64(0);
+        Self::with_rng(rng)
+    }
+
+    /// Creates a machine whose random numbers are fully determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(SmallRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: RngCore> Chip8<R> {
+    pub fn with_rng(rng: R) -> Self {
         Self {
             gfx: [0; 64 * 32],
             memory: Memory::new(),
@@ -32,214 +63,166 @@ impl Chip8 {
             pc: 0x200,
             delay_timer: 0,
             sound_timer: 0,
-            key: [0; 16],
-            draw_flag: false,
-            await_key_flag: false,
-            await_key_notify: false,
-            await_key_pressed: 0x0,
-            last_timer: Instant::now(),
+            keypad: Keypad::default(),
+            dirty_rows: 0,
+            rng,
+        }
+    }
+
+    /// Counts the delay and sound timers down by one step of their 60 Hz clock.
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
         }
     }
 
     pub fn cycle(&mut self) {
+        self.cycle_with(&mut ());
+    }
+
+    /// Runs one instruction like [`Chip8::cycle`], reporting it to `observer`.
+    pub fn cycle_with<O: Observer + ?Sized>(&mut self, observer: &mut O) {
         if self.pc >= 4096 {
             return;
         }
 
-        let opcode = (self.memory.read(self.pc) as u16) << 8 | self.memory.read(self.pc + 1) as u16;
+        let (opcode, instruction) = self.memory.fetch(self.pc);
 
         if opcode == 0x0000 {
             return;
         }
 
-        if self.last_timer.elapsed().as_secs_f64() > 1.0 / 60.0 {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
-            }
-            self.last_timer = Instant::now();
+        observer.instruction(opcode, &self.registers());
+        self.execute(opcode, instruction, observer);
+        observer.executed(&self.registers());
+    }
+
+    /// Runs `cycles` instructions, as fast as the interpreter goes.
+    pub fn run(&mut self, cycles: u64) {
+        self.run_with(cycles, &mut ());
+    }
+
+    /// Runs `cycles` instructions like [`Chip8::run`], reporting them to `observer`.
+    pub fn run_with<O: Observer + ?Sized>(&mut self, cycles: u64, observer: &mut O) {
+        for _ in 0..cycles {
+            self.cycle_with(observer);
         }
+    }
 
-        match opcode & 0xF000 {
-            0x0000 => {
-                match opcode & 0x00FF {
-                    0x00E0 => {
-                        // Clear screen
-                        self.gfx = [0; 64 * 32];
-                        self.draw_flag = true;
-                        self.pc += 2;
-                    }
-                    0x00EE => {
-                        // Return from subroutine
-                        self.sp -= 1;
-                        self.pc = self.stack[self.sp as usize];
-                    }
-                    _ => panic!("Not implemented: {:#06x}", opcode),
-                }
+    /// Turns the cache of decoded instructions on or off. It is on by default; turning it
+    /// off decodes every instruction again each time it runs, which is only useful to
+    /// measure what the cache brings.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.memory.set_decode_cache(enabled);
+    }
+
+    fn execute<O: Observer + ?Sized>(
+        &mut self,
+        opcode: u16,
+        instruction: Instruction,
+        observer: &mut O,
+    ) {
+        match instruction {
+            Instruction::Clear => {
+                self.gfx = [0; 64 * 32];
+                self.dirty_rows = u32::MAX;
+                self.pc += 2;
             }
-            0x1000 => {
-                // Jump to address
-                self.pc = opcode & 0x0FFF;
+            Instruction::Return => {
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
             }
-            0x2000 => {
+            Instruction::Jump { nnn } => self.pc = nnn,
+            Instruction::Call { nnn } => {
                 self.stack[self.sp as usize] = self.pc + 2;
                 self.sp += 1;
-                self.pc = opcode & 0x0FFF;
+                self.pc = nnn;
             }
-            0x3000 => {
-                let x = (opcode & 0x0F00) >> 8;
-                let val = (opcode & 0x00FF) as u8;
-                if self.v[x as usize] == val {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
+            Instruction::SkipEqImm { x, nn } => self.skip_if(self.v[x as usize] == nn),
+            Instruction::SkipNeImm { x, nn } => self.skip_if(self.v[x as usize] != nn),
+            Instruction::SkipEq { x, y } => self.skip_if(self.v[x as usize] == self.v[y as usize]),
+            Instruction::SkipNe { x, y } => self.skip_if(self.v[x as usize] != self.v[y as usize]),
+            Instruction::LoadImm { x, nn } => {
+                self.v[x as usize] = nn;
+                self.pc += 2;
             }
-            0x4000 => {
-                // Skip instruction
-                let x = (opcode & 0x0F00) >> 8;
-                let val = (opcode & 0x00FF) as u8;
-                if self.v[x as usize] != val {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
+            Instruction::AddImm { x, nn } => {
+                // No carry flag
+                self.v[x as usize] = self.v[x as usize].wrapping_add(nn);
+                self.pc += 2;
             }
-            0x5000 => {
-                // Skip instruction
-                let x = (opcode & 0x0F00) >> 8;
-                let y = (opcode & 0x00F0) >> 4;
-                if self.v[x as usize] == self.v[y as usize] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
+            Instruction::Move { x, y } => {
+                self.v[x as usize] = self.v[y as usize];
+                self.pc += 2;
             }
-            0x6000 => {
-                // Sets VX to NN
-                let x = (opcode & 0x0F00) >> 8;
-                let val = (opcode & 0x00FF) as u8;
-                self.v[x as usize] = val;
+            Instruction::Or { x, y } => {
+                self.v[x as usize] |= self.v[y as usize];
+                self.v[0xF] = 0;
                 self.pc += 2;
             }
-            0x7000 => {
-                // Adds NN to VX without carry
-                let x = (opcode & 0x0F00) >> 8;
-                let val = (opcode & 0x00FF) as u8;
-                self.v[x as usize] = self.v[x as usize].wrapping_add(val);
+            Instruction::And { x, y } => {
+                self.v[x as usize] &= self.v[y as usize];
+                self.v[0xF] = 0;
                 self.pc += 2;
             }
-            0x8000 => {
-                let x = (opcode & 0x0F00) >> 8;
-                let y = (opcode & 0x00F0) >> 4;
-
-                match opcode & 0x000F {
-                    0x0000 => {
-                        self.v[x as usize] = self.v[y as usize];
-                        self.pc += 2;
-                    }
-                    0x0001 => {
-                        self.v[x as usize] |= self.v[y as usize];
-                        self.v[0xF] = 0;
-                        self.pc += 2;
-                    }
-                    0x0002 => {
-                        self.v[x as usize] &= self.v[y as usize];
-                        self.v[0xF] = 0;
-                        self.pc += 2;
-                    }
-                    0x0003 => {
-                        self.v[x as usize] ^= self.v[y as usize];
-                        self.v[0xF] = 0;
-                        self.pc += 2;
-                    }
-                    0x0004 => {
-                        let prev = self.v[x as usize];
-                        self.v[x as usize] = self.v[x as usize].wrapping_add(self.v[y as usize]);
-                        if self.v[x as usize] < prev {
-                            self.v[0xF] = 1;
-                        } else {
-                            self.v[0xF] = 0;
-                        }
-                        self.pc += 2;
-                    }
-                    0x0005 => {
-                        let carry = if self.v[x as usize] >= self.v[y as usize] {
-                            1
-                        } else {
-                            0
-                        };
-                        self.v[x as usize] = self.v[x as usize].wrapping_sub(self.v[y as usize]);
-                        self.v[0xF] = carry;
-                        self.pc += 2;
-                    }
-                    0x0006 => {
-                        let lsb = self.v[y as usize] & 0x01;
-                        self.v[x as usize] = self.v[y as usize] >> 1;
-                        self.v[0xF] = lsb;
-                        self.pc += 2;
-                    }
-                    0x0007 => {
-                        let carry = if self.v[y as usize] >= self.v[x as usize] {
-                            1
-                        } else {
-                            0
-                        };
-                        self.v[x as usize] = self.v[y as usize].wrapping_sub(self.v[x as usize]);
-                        self.v[0xF] = carry;
-                        self.pc += 2;
-                    }
-                    0x000E => {
-                        let msb = self.v[y as usize] >> 7;
-                        self.v[x as usize] = self.v[y as usize] << 1;
-                        self.v[0xF] = msb;
-                        self.pc += 2;
-                    }
-                    _ => panic!("Not implemented"),
-                }
+            Instruction::Xor { x, y } => {
+                self.v[x as usize] ^= self.v[y as usize];
+                self.v[0xF] = 0;
+                self.pc += 2;
             }
-            0x9000 => {
-                // Skips next instruction based on test
-                let x = (opcode & 0x0F00) >> 8;
-                let y = (opcode & 0x00F0) >> 4;
-                if self.v[x as usize] != self.v[y as usize] {
-                    self.pc += 4;
-                } else {
-                    self.pc += 2;
-                }
+            Instruction::Add { x, y } => {
+                let (sum, carry) = self.v[x as usize].overflowing_add(self.v[y as usize]);
+                self.v[x as usize] = sum;
+                self.v[0xF] = carry as u8;
+                self.pc += 2;
             }
-            0xA000 => {
-                // Sets I to address of NNN
-                self.i = opcode & 0x0FFF;
+            Instruction::Sub { x, y } => {
+                let no_borrow = self.v[x as usize] >= self.v[y as usize];
+                self.v[x as usize] = self.v[x as usize].wrapping_sub(self.v[y as usize]);
+                self.v[0xF] = no_borrow as u8;
                 self.pc += 2;
             }
-            0xB000 => {
-                // Jumps to address of NNN plus V0
-                self.pc = (opcode & 0x0FFF).wrapping_add(self.v[0x0] as u16);
+            Instruction::ShiftRight { x, y } => {
+                let lsb = self.v[y as usize] & 0x01;
+                self.v[x as usize] = self.v[y as usize] >> 1;
+                self.v[0xF] = lsb;
+                self.pc += 2;
+            }
+            Instruction::SubReverse { x, y } => {
+                let no_borrow = self.v[y as usize] >= self.v[x as usize];
+                self.v[x as usize] = self.v[y as usize].wrapping_sub(self.v[x as usize]);
+                self.v[0xF] = no_borrow as u8;
+                self.pc += 2;
+            }
+            Instruction::ShiftLeft { x, y } => {
+                let msb = self.v[y as usize] >> 7;
+                self.v[x as usize] = self.v[y as usize] << 1;
+                self.v[0xF] = msb;
+                self.pc += 2;
+            }
+            Instruction::LoadI { nnn } => {
+                self.i = nnn;
+                self.pc += 2;
             }
-            0xC000 => {
-                // Random number operation
-                let x = (opcode & 0x0F00) >> 8;
-                let val = (opcode & 0x00FF) as u8;
-                let mut rng = rand::thread_rng();
-                let r = rng.gen_range(0..=255);
-
-                self.v[x as usize] = val & r;
+            Instruction::JumpV0 { nnn } => self.pc = nnn.wrapping_add(self.v[0x0] as u16),
+            Instruction::Random { x, nn } => {
+                self.v[x as usize] = nn & self.rng.gen::<u8>();
                 self.pc += 2;
             }
-            0xD000 => {
-                // Draws a sprite
-                let x = (opcode & 0x0F00) >> 8;
-                let y = (opcode & 0x00F0) >> 4;
-                let n = (opcode & 0x000F) as u16;
+            Instruction::Draw { x, y, n } => {
                 let x = self.v[x as usize] as u16;
                 let y = self.v[y as usize] as u16;
                 self.v[0xF] = 0;
 
-                for y_line in 0..n {
-                    let pixel = self.memory.read(self.i + y_line as u16);
+                for y_line in 0..n as u16 {
+                    observer.memory_read(self.i + y_line);
+                    let pixel = self.memory.read(self.i + y_line);
+                    if pixel != 0 {
+                        self.dirty_rows |= 1 << ((y + y_line) % 32);
+                    }
                     for x_line in 0..8 {
                         let x_coord = (x + x_line) as usize % 64;
                         let y_coord = (y + y_line) as usize % 32;
@@ -251,97 +234,199 @@ impl Chip8 {
                         }
                     }
                 }
-                self.draw_flag = true;
                 self.pc += 2;
             }
-            0xE000 => {
-                let x = (opcode & 0x0F00) >> 8;
-                match opcode & 0x00FF {
-                    0x009E => {
-                        if self.key[self.v[x as usize] as usize] != 0 {
-                            self.pc += 4;
-                        } else {
-                            self.pc += 2;
-                        }
-                    }
-                    0x00A1 => {
-                        if self.key[self.v[x as usize] as usize] == 0 {
-                            self.pc += 4;
-                        } else {
-                            self.pc += 2;
-                        }
-                    }
-                    _ => panic!("Not implemented"),
+            Instruction::SkipKey { x } => self.skip_if(self.keypad.is_down(self.v[x as usize])),
+            Instruction::SkipNotKey { x } => self.skip_if(!self.keypad.is_down(self.v[x as usize])),
+            Instruction::GetDelay { x } => {
+                self.v[x as usize] = self.delay_timer;
+                self.pc += 2;
+            }
+            Instruction::WaitKey { x } => {
+                // Re-executed every cycle until the keypad reports a key
+                if let Some(key) = self.keypad.poll_wait() {
+                    self.v[x as usize] = key;
+                    self.pc += 2;
                 }
             }
-            0xF000 => {
-                let x = (opcode & 0x0F00) >> 8;
-                match opcode & 0x00FF {
-                    0x0007 => {
-                        self.v[x as usize] = self.delay_timer;
-                        self.pc += 2;
-                    }
-                    0x000A => {
-                        if !self.await_key_notify {
-                            self.await_key_flag = true;
-                            return;
-                        }else{
-                            self.v[x as usize] = self.await_key_pressed;
-                            self.pc += 2;
-                            self.await_key_notify = false;
-                            self.await_key_flag = false;
-                        }
-                    }
-                    0x0015 => {
-                        self.delay_timer = self.v[x as usize];
-                        self.pc += 2;
-                    }
-                    0x0018 => {
-                        self.sound_timer = self.v[x as usize];
-                        self.pc += 2;
-                    }
-                    0x001E => {
-                        self.i = self.i.wrapping_add(self.v[x as usize] as u16);
-                        self.pc += 2;
-                    } // Add to I
-                    0x0029 => {
-                        self.i = 5 * (self.v[x as usize] as u16);
-                        self.pc += 2;
-                    }
-                    0x0033 => {
-                        self.memory.write(self.i, self.v[x as usize] / 100);
-                        self.memory
-                            .write(self.i + 1, (self.v[x as usize] / 10) % 10);
-                        self.memory
-                            .write(self.i + 2, (self.v[x as usize] % 100) % 10);
-                        self.pc += 2;
-                    }
-                    0x0055 => {
-                        for p in 0..=x {
-                            self.memory.write(self.i + p, self.v[p as usize]);
-                        }
-                        self.i = x + 1;
-                        self.pc += 2;
-                    }
-                    0x0065 => {
-                        for p in 0..=x {
-                            self.v[p as usize] = self.memory.read(self.i + p);
-                        }
-                        self.i = x+1;
-                        self.pc += 2;
-                    }
-                    _ => panic!("Not implemented: {:#06x}", opcode),
+            Instruction::SetDelay { x } => {
+                self.delay_timer = self.v[x as usize];
+                self.pc += 2;
+            }
+            Instruction::SetSound { x } => {
+                self.sound_timer = self.v[x as usize];
+                self.pc += 2;
+            }
+            Instruction::AddI { x } => {
+                self.i = self.i.wrapping_add(self.v[x as usize] as u16);
+                self.pc += 2;
+            }
+            Instruction::Font { x } => {
+                self.i = 5 * (self.v[x as usize] as u16);
+                self.pc += 2;
+            }
+            Instruction::Bcd { x } => {
+                let value = self.v[x as usize];
+                let digits = [value / 100, (value / 10) % 10, (value % 100) % 10];
+                for (p, digit) in (0..).zip(digits) {
+                    observer.memory_write(self.i + p, digit);
+                    self.memory.write(self.i + p, digit);
+                }
+                self.pc += 2;
+            }
+            Instruction::Store { x } => {
+                let x = x as u16;
+                for p in 0..=x {
+                    observer.memory_write(self.i + p, self.v[p as usize]);
+                    self.memory.write(self.i + p, self.v[p as usize]);
+                }
+                self.i = x + 1;
+                self.pc += 2;
+            }
+            Instruction::Load { x } => {
+                let x = x as u16;
+                for p in 0..=x {
+                    observer.memory_read(self.i + p);
+                    self.v[p as usize] = self.memory.read(self.i + p);
+                }
+                self.i = x + 1;
+                self.pc += 2;
+            }
+            Instruction::Sys { .. } | Instruction::Unknown(_) => {
+                panic!("Not implemented: {:#06x}", opcode)
+            }
+        }
+    }
+
+    /// Skips the next instruction if `condition` holds.
+    fn skip_if(&mut self, condition: bool) {
+        self.pc += if condition { 4 } else { 2 };
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    /// Return addresses pushed by 2nnn, of which the first [`Chip8::sp`] are in use.
+    pub fn stack(&self) -> &[u16; 16] {
+        &self.stack
+    }
+
+    /// The whole 4 KiB of memory, font included.
+    pub fn memory(&self) -> &[u8; 0x1000] {
+        &self.memory.data
+    }
+
+    /// The CPU state, for running the CPU outside the interpreter.
+    pub fn cpu(&self) -> Cpu {
+        Cpu {
+            v: self.v,
+            stack: self.stack,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            keys: self.keypad.held(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    /// Replaces the CPU state with `cpu`, except for the keys, which stay as they are.
+    pub fn set_cpu(&mut self, cpu: &Cpu) {
+        self.v = cpu.v;
+        self.stack = cpu.stack;
+        self.i = cpu.i;
+        self.pc = cpu.pc;
+        self.sp = cpu.sp;
+        self.delay_timer = cpu.delay_timer;
+        self.sound_timer = cpu.sound_timer;
+    }
+
+    pub fn press_key(&mut self, key: u8) {
+        self.keypad.press(key);
+    }
+
+    pub fn release_key(&mut self, key: u8) {
+        self.keypad.release(key);
+    }
+
+    /// Holds down exactly the keys set in `keys`, key 0 in the lowest bit, pressing and
+    /// releasing only the keys that change.
+    pub fn set_keys(&mut self, keys: u16) {
+        for key in 0..16 {
+            let down = keys & (1 << key) != 0;
+            if down != self.keypad.is_down(key) {
+                if down {
+                    self.keypad.press(key);
+                } else {
+                    self.keypad.release(key);
                 }
             }
-            _ => (),
         }
+    }
+
+    pub fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    /// Chooses whether Fx0A waits for a key release (VIP) or only a press (SCHIP).
+    pub fn set_key_wait(&mut self, mode: KeyWait) {
+        self.keypad.set_mode(mode);
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
 
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
     }
 
-    pub fn load_raw(&mut self, rom: &Vec<u16>) {
+    /// Hash of the whole machine state except the RNG, for telling whether two runs agree.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write(&self.memory.data);
+        hasher.write(&self.gfx);
+        hasher.write(&self.v);
+        for word in self.stack {
+            hasher.write(&word.to_le_bytes());
+        }
+        hasher.write(&self.sp.to_le_bytes());
+        hasher.write(&self.i.to_le_bytes());
+        hasher.write(&self.pc.to_le_bytes());
+        hasher.write_u8(self.delay_timer);
+        hasher.write_u8(self.sound_timer);
+        hasher.write(&self.keypad.held().to_le_bytes());
+        hasher.write_u8(self.keypad.is_waiting() as u8);
+        hasher.finish()
+    }
+
+    pub fn load_raw(&mut self, rom: &[u16]) {
         let mut p = 0;
-        for i in 0..rom.len() {
-            let opcode = rom[i];
+        for &opcode in rom {
             let left = ((opcode & 0xFF00) >> 8) as u8;
             let right = (opcode & 0x00FF) as u8;
             self.memory.write(0x200 + p, left);
@@ -350,24 +435,159 @@ impl Chip8 {
         }
     }
 
+    /// Copies `rom` into memory at the program start address 0x200.
+    pub fn load_bytes(&mut self, rom: &[u8]) {
+        for (i, &byte) in rom.iter().enumerate() {
+            self.memory.write(0x200 + i as u16, byte);
+        }
+    }
+
+    #[cfg(feature = "std")]
     pub fn load_rom<P>(&mut self, path: P)
     where
         P: AsRef<Path>,
     {
         let rom = fs::read(path.as_ref()).expect("ROM should be readable");
-        for i in 0..rom.len() {
-            self.memory.write(0x200 + i as u16, rom[i]);
+        self.load_bytes(&rom);
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The CPU registers, as handed to an [`Observer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// The registers, stack and keys of a [`Chip8`] as plain data, for code that runs the CPU
+/// itself and only hands the rest to the interpreter: the JIT and recompiled ROMs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cpu {
+    pub v: [u8; 16],
+    pub stack: [u16; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u16,
+    /// Keys held down, key 0 in the lowest bit.
+    pub keys: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// Watches instructions execute, for tracers and profilers.
+///
+/// Every method does nothing by default. `()` is the observer that watches nothing, which
+/// is what [`Chip8::cycle`] uses.
+pub trait Observer {
+    /// Called before `opcode` executes, with the registers it starts from.
+    fn instruction(&mut self, _opcode: u16, _before: &Registers) {}
+
+    /// Called when the instruction reads data from memory: sprites and Fx65.
+    fn memory_read(&mut self, _address: u16) {}
+
+    /// Called when the instruction writes memory: Fx33 and Fx55.
+    fn memory_write(&mut self, _address: u16, _value: u8) {}
+
+    /// Called after the instruction executed, with the registers it left behind.
+    fn executed(&mut self, _after: &Registers) {}
+
+    /// Called by the emulator before it runs the cycles of frame number `frame`.
+    fn frame(&mut self, _frame: u64) {}
+}
+
+impl Observer for () {}
+
+impl<T: Observer> Observer for Option<T> {
+    fn instruction(&mut self, opcode: u16, before: &Registers) {
+        if let Some(observer) = self {
+            observer.instruction(opcode, before);
         }
     }
+
+    fn memory_read(&mut self, address: u16) {
+        if let Some(observer) = self {
+            observer.memory_read(address);
+        }
+    }
+
+    fn memory_write(&mut self, address: u16, value: u8) {
+        if let Some(observer) = self {
+            observer.memory_write(address, value);
+        }
+    }
+
+    fn executed(&mut self, after: &Registers) {
+        if let Some(observer) = self {
+            observer.executed(after);
+        }
+    }
+
+    fn frame(&mut self, frame: u64) {
+        if let Some(observer) = self {
+            observer.frame(frame);
+        }
+    }
+}
+
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    fn instruction(&mut self, opcode: u16, before: &Registers) {
+        self.0.instruction(opcode, before);
+        self.1.instruction(opcode, before);
+    }
+
+    fn memory_read(&mut self, address: u16) {
+        self.0.memory_read(address);
+        self.1.memory_read(address);
+    }
+
+    fn memory_write(&mut self, address: u16, value: u8) {
+        self.0.memory_write(address, value);
+        self.1.memory_write(address, value);
+    }
+
+    fn executed(&mut self, after: &Registers) {
+        self.0.executed(after);
+        self.1.executed(after);
+    }
+
+    fn frame(&mut self, frame: u64) {
+        self.0.frame(frame);
+        self.1.frame(frame);
+    }
 }
 
+/// The 4 KiB of memory, with a cache of the instructions decoded from it.
+///
+/// Writes drop the cached instructions overlapping the written byte, so self-modifying
+/// code runs what it wrote. Clones share the cache until one of them changes it, which
+/// keeps cloning a machine cheap.
+#[derive(Clone)]
 pub struct Memory {
     data: [u8; 0x1000],
+    /// Opcode and instruction starting at each address. An opcode of 0 means nothing is
+    /// cached, which costs nothing as the interpreter stops at 0000 anyway.
+    decoded: Arc<[(u16, Instruction); 0x1000]>,
+    cache_enabled: bool,
 }
 
 impl Memory {
     pub fn new() -> Self {
-        let mut memory = Self { data: [0; 0x1000] };
+        let mut memory = Self {
+            data: [0; 0x1000],
+            decoded: Arc::new([UNCACHED; 0x1000]),
+            cache_enabled: true,
+        };
 
         let fontset: [u8; 80] = [
             0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -388,8 +608,8 @@ impl Memory {
             0xF0, 0x80, 0xF0, 0x80, 0x80, // F
         ];
 
-        for i in 0..80 {
-            memory.write(i, fontset[i as usize]);
+        for (i, &byte) in fontset.iter().enumerate() {
+            memory.write(i as u16, byte);
         }
 
         memory
@@ -401,5 +621,213 @@ impl Memory {
 
     pub fn write(&mut self, loc: u16, val: u8) {
         self.data[loc as usize] = val;
+        self.uncache(loc as usize);
+        if loc > 0 {
+            self.uncache(loc as usize - 1);
+        }
+    }
+
+    /// The opcode at `loc` and the instruction it decodes to.
+    pub fn fetch(&mut self, loc: u16) -> (u16, Instruction) {
+        let cached = self.decoded[loc as usize];
+        if cached.0 != 0 {
+            return cached;
+        }
+        let opcode = (self.read(loc) as u16) << 8 | self.read(loc + 1) as u16;
+        let decoded = (opcode, decode(opcode));
+        if self.cache_enabled {
+            Arc::make_mut(&mut self.decoded)[loc as usize] = decoded;
+        }
+        decoded
+    }
+
+    /// Drops the instruction cached at `loc`, if any.
+    fn uncache(&mut self, loc: usize) {
+        // Checked first so writing data does not copy a shared cache
+        if self.decoded[loc].0 != 0 {
+            Arc::make_mut(&mut self.decoded)[loc] = UNCACHED;
+        }
+    }
+
+    fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache_enabled = enabled;
+        Arc::make_mut(&mut self.decoded).fill(UNCACHED);
+    }
+}
+
+const UNCACHED: (u16, Instruction) = (0, Instruction::Unknown(0));
+
+/// Decodes `opcode` the way the interpreter runs it, which ignores a few bits the
+/// disassembler is strict about: 5xyN and 9xyN ignore N, and 0nE0 and 0nEE ignore n.
+pub(crate) fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let y = ((opcode >> 4) & 0xF) as u8;
+    match (opcode >> 12, opcode & 0xFF) {
+        (0x0, 0xE0) => Instruction::Clear,
+        (0x0, 0xEE) => Instruction::Return,
+        (0x5, _) => Instruction::SkipEq { x, y },
+        (0x9, _) => Instruction::SkipNe { x, y },
+        _ => Instruction::decode(opcode),
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The 64-bit FNV-1a hash, stable across platforms and releases unlike `DefaultHasher`.
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_run_like_the_original() {
+        let mut original = Chip8::with_seed(3);
+        original.load_bytes(include_bytes!("../roms/breakout.ch8"));
+        original.run(1000);
+        let mut clone = original.clone();
+        for frame in 0..200 {
+            original.set_keys(frame);
+            clone.set_keys(frame);
+            original.run(12);
+            clone.run(12);
+            original.tick_timers();
+            clone.tick_timers();
+        }
+        assert_eq!(clone.state_hash(), original.state_hash());
+    }
+
+    #[test]
+    fn clones_run_code_written_after_cloning() {
+        let mut original = Chip8::with_seed(0);
+        // v1 += 1, jump back
+        original.load_raw(&[0x7101, 0x1200]);
+        original.run(10);
+        let mut clone = original.clone();
+        // v2 += 1, jump back
+        clone.load_raw(&[0x7201, 0x1200]);
+        clone.run(10);
+        original.run(10);
+        assert_eq!(&clone.v()[1..3], &[5, 5]);
+        assert_eq!(&original.v()[1..3], &[10, 0]);
+    }
+
+    /// Runs `program` for `cycles` instructions with or without the decode cache.
+    fn run_program(program: &[u16], cycles: u64, cache: bool) -> Chip8 {
+        let mut chip8 = Chip8::with_seed(0);
+        chip8.set_decode_cache(cache);
+        chip8.load_raw(program);
+        chip8.run(cycles);
+        chip8
+    }
+
+    #[test]
+    fn code_overwritten_by_fx55_is_decoded_again() {
+        let program = [
+            0x6072, // 200: LD V0, 72
+            0xA210, // 202: LD I, 210
+            0x2210, // 204: CALL 210
+            0xF055, // 206: LD [I], V0, turning 210 into ADD V2, 1
+            0x2210, // 208: CALL 210
+            0x120A, // 20A: JP 20A
+            0x0000, // 20C
+            0x0000, // 20E
+            0x7301, // 210: ADD V3, 1
+            0x00EE, // 212: RET
+        ];
+        let chip8 = run_program(&program, 20, true);
+        assert_eq!(&chip8.v()[2..4], &[1, 1]);
+        assert_eq!(chip8.memory()[0x210..0x212], [0x72, 0x01]);
+        assert_eq!(
+            chip8.state_hash(),
+            run_program(&program, 20, false).state_hash()
+        );
+    }
+
+    #[test]
+    fn writes_to_the_second_byte_of_an_opcode_decode_it_again() {
+        let program = [
+            0x6005, // 200: LD V0, 5
+            0xA211, // 202: LD I, 211
+            0x2210, // 204: CALL 210
+            0xF055, // 206: LD [I], V0, turning 210 into ADD V3, 5
+            0x2210, // 208: CALL 210
+            0x120A, // 20A: JP 20A
+            0x0000, // 20C
+            0x0000, // 20E
+            0x7301, // 210: ADD V3, 1
+            0x00EE, // 212: RET
+        ];
+        let chip8 = run_program(&program, 20, true);
+        assert_eq!(chip8.v()[3], 6);
+
+        // Fx33 can only change second bytes, since its digits make no first byte that runs
+        let program = [
+            0x7401, // 200: ADD V4, 1
+            0x6532, // 202: LD V5, 50
+            0xA20D, // 204: LD I, 20D
+            0x7301, // 206: ADD V3, 1
+            0x4302, // 208: SNE V3, 2
+            0xF533, // 20A: LD B, V5 the second time, turning 20C into JP 200
+            0x1206, // 20C: JP 206
+        ];
+        let chip8 = run_program(&program, 30, true);
+        assert_eq!(chip8.memory()[0x20C..0x20E], [0x12, 0x00]);
+        assert!(chip8.v()[4] > 1, "the jump at 20C was not decoded again");
+        assert_eq!(
+            chip8.state_hash(),
+            run_program(&program, 30, false).state_hash()
+        );
+    }
+
+    #[test]
+    fn runs_without_the_decode_cache_are_identical() {
+        let mut cached = Chip8::with_seed(5);
+        let mut uncached = Chip8::with_seed(5);
+        uncached.set_decode_cache(false);
+        for chip8 in [&mut cached, &mut uncached] {
+            chip8.load_bytes(include_bytes!("../roms/breakout.ch8"));
+        }
+        for frame in 0..300 {
+            for chip8 in [&mut cached, &mut uncached] {
+                chip8.set_keys(if frame % 100 < 50 { 1 << 4 } else { 1 << 6 });
+                chip8.run(12);
+                chip8.tick_timers();
+            }
+            assert_eq!(
+                cached.state_hash(),
+                uncached.state_hash(),
+                "frame {}",
+                frame
+            );
+        }
+
+        // Turning the cache off halfway drops what it held
+        cached.set_decode_cache(false);
+        cached.run(1000);
+        uncached.run(1000);
+        assert_eq!(cached.state_hash(), uncached.state_hash());
     }
 }
@@ -0,0 +1,431 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::chip8::{Observer, Registers};
+use crate::disasm::Instruction;
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+const WRITTEN: u8 = 4;
+
+/// Output formats of a coverage report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageFormat {
+    /// An annotated disassembly of the ROM.
+    Listing,
+    Html,
+    /// An lcov tracefile, with addresses for line numbers.
+    Lcov,
+}
+
+impl CoverageFormat {
+    /// Picks the format from the extension of `path`: `.html` or `.htm`, `.info` or `.lcov`,
+    /// and a listing for anything else.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension
+            .map(|extension| extension.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("html" | "htm") => CoverageFormat::Html,
+            Some("info" | "lcov") => CoverageFormat::Lcov,
+            _ => CoverageFormat::Listing,
+        }
+    }
+}
+
+/// How many bytes of a ROM were used in each way. A byte can be both read and written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoverageSummary {
+    pub rom_bytes: usize,
+    pub executed: usize,
+    pub read: usize,
+    pub written: usize,
+    pub untouched: usize,
+}
+
+/// Tracks which bytes of the loaded ROM were executed, read as data through I, or written.
+///
+/// Both bytes of an executed instruction count as executed. Data reads are the sprite
+/// rows of Dxyn and the registers loaded by Fx65; writes are Fx33 and Fx55.
+pub struct Coverage {
+    rom: Vec<u8>,
+    executions: Vec<u64>,
+    /// The opcode last executed at each address, which self-modifying code can change.
+    opcodes: Vec<u16>,
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    /// Tracks `rom`, loaded at 200.
+    pub fn new(rom: &[u8]) -> Self {
+        Self {
+            rom: rom.to_vec(),
+            executions: vec![0; 0x1000],
+            opcodes: vec![0; 0x1000],
+            flags: vec![0; 0x1000],
+        }
+    }
+
+    /// Times an instruction started at `address`.
+    pub fn executions(&self, address: u16) -> u64 {
+        self.executions[address as usize & 0xFFF]
+    }
+
+    pub fn is_executed(&self, address: u16) -> bool {
+        self.flag(address, EXECUTED)
+    }
+
+    pub fn is_read(&self, address: u16) -> bool {
+        self.flag(address, READ)
+    }
+
+    pub fn is_written(&self, address: u16) -> bool {
+        self.flag(address, WRITTEN)
+    }
+
+    fn flag(&self, address: u16, flag: u8) -> bool {
+        self.flags[address as usize & 0xFFF] & flag != 0
+    }
+
+    /// Addresses of the ROM, which may be cut short by the end of memory.
+    fn rom_addresses(&self) -> std::ops::Range<u16> {
+        0x200..(0x200 + self.rom.len()).min(0x1000) as u16
+    }
+
+    pub fn summary(&self) -> CoverageSummary {
+        let mut summary = CoverageSummary::default();
+        for address in self.rom_addresses() {
+            summary.rom_bytes += 1;
+            let flags = self.flags[address as usize];
+            summary.executed += (flags & EXECUTED != 0) as usize;
+            summary.read += (flags & READ != 0) as usize;
+            summary.written += (flags & WRITTEN != 0) as usize;
+            summary.untouched += (flags == 0) as usize;
+        }
+        summary
+    }
+
+    /// Splits the ROM into executed instructions and single bytes of anything else.
+    fn lines(&self) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut addresses = self.rom_addresses();
+        while let Some(address) = addresses.next() {
+            if self.executions(address) > 0 {
+                lines.push(Line::Instruction(address));
+                addresses.next();
+            } else {
+                lines.push(Line::Byte(address));
+            }
+        }
+        lines
+    }
+
+    /// The markers of a line: `X` executed, `R` read, `W` written, or `-` for none.
+    fn markers(&self, line: Line) -> String {
+        let (address, len) = match line {
+            Line::Instruction(address) => (address, 2),
+            Line::Byte(address) => (address, 1),
+        };
+        let any = |flag| (address..address + len).any(|address| self.flag(address, flag));
+        [(EXECUTED, 'X'), (READ, 'R'), (WRITTEN, 'W')]
+            .into_iter()
+            .map(|(flag, marker)| if any(flag) { marker } else { '-' })
+            .collect()
+    }
+
+    /// The text of a line without its markers: address, bytes and disassembly or pixels.
+    fn describe(&self, line: Line) -> String {
+        match line {
+            Line::Instruction(address) => {
+                let opcode = self.opcodes[address as usize];
+                format!(
+                    "{:03X}  {:04X}  {:>9}x  {}",
+                    address,
+                    opcode,
+                    self.executions(address),
+                    Instruction::decode(opcode)
+                )
+            }
+            Line::Byte(address) => {
+                let byte = self.rom[address as usize - 0x200];
+                let pixels: String = (0..8)
+                    .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                    .collect();
+                format!("{:03X}  {:02X}                {}", address, byte, pixels)
+            }
+        }
+    }
+
+    pub fn write(
+        &self,
+        out: &mut impl Write,
+        format: CoverageFormat,
+        name: &str,
+    ) -> io::Result<()> {
+        match format {
+            CoverageFormat::Listing => self.write_listing(out),
+            CoverageFormat::Html => self.write_html(out, name),
+            CoverageFormat::Lcov => self.write_lcov(out, name),
+        }
+    }
+
+    /// Writes the annotated disassembly: executed instructions with their execution
+    /// counts, and every other byte with its bits drawn as pixels, since unexecuted bytes
+    /// are often sprites.
+    pub fn write_listing(&self, out: &mut impl Write) -> io::Result<()> {
+        let summary = self.summary();
+        writeln!(out, "; {}", summary_text(&summary))?;
+        writeln!(out, "; X executed, R read as data, W written")?;
+        for line in self.lines() {
+            writeln!(out, "{}  {}", self.markers(line), self.describe(line))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the annotated disassembly as a standalone HTML page titled `name`.
+    pub fn write_html(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        let summary = self.summary();
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+        writeln!(out, "<title>Coverage of {}</title>", escape(name))?;
+        writeln!(
+            out,
+            "<style>\
+             body {{ font-family: sans-serif; }} \
+             pre {{ font-family: monospace; line-height: 1.3; }} \
+             .executed {{ background: #c8f0c8; }} \
+             .read {{ background: #c8dcf8; }} \
+             .written {{ background: #f8e0b0; }} \
+             .untouched {{ background: #f4c8c8; }}\
+             </style>"
+        )?;
+        writeln!(out, "</head><body>")?;
+        writeln!(out, "<h1>Coverage of {}</h1>", escape(name))?;
+        writeln!(out, "<p>{}</p>", escape(&summary_text(&summary)))?;
+        writeln!(
+            out,
+            "<p><span class=\"executed\">executed</span> \
+             <span class=\"read\">read as data</span> \
+             <span class=\"written\">written</span> \
+             <span class=\"untouched\">untouched</span></p>"
+        )?;
+        writeln!(out, "<pre>")?;
+        for line in self.lines() {
+            let markers = self.markers(line);
+            let class = if markers.contains('X') {
+                "executed"
+            } else if markers.contains('W') {
+                "written"
+            } else if markers.contains('R') {
+                "read"
+            } else {
+                "untouched"
+            };
+            writeln!(
+                out,
+                "<span class=\"{}\">{}  {}</span>",
+                class,
+                markers,
+                escape(&self.describe(line))
+            )?;
+        }
+        writeln!(out, "</pre>")?;
+        writeln!(out, "</body></html>")
+    }
+
+    /// Writes an lcov tracefile for `source`, with one line per instruction address.
+    ///
+    /// Executed instructions have their execution counts, and bytes never touched count as
+    /// unexecuted lines. Bytes only used as data are left out, since they are not code.
+    pub fn write_lcov(&self, out: &mut impl Write, source: &str) -> io::Result<()> {
+        writeln!(out, "TN:")?;
+        writeln!(out, "SF:{}", source)?;
+        let mut found = 0;
+        let mut hit = 0;
+        for line in self.lines() {
+            let (address, count) = match line {
+                Line::Instruction(address) => (address, self.executions(address)),
+                Line::Byte(address) if self.flags[address as usize] == 0 => (address, 0),
+                Line::Byte(_) => continue,
+            };
+            writeln!(out, "DA:{},{}", address, count)?;
+            found += 1;
+            hit += (count > 0) as usize;
+        }
+        writeln!(out, "LH:{}", hit)?;
+        writeln!(out, "LF:{}", found)?;
+        writeln!(out, "end_of_record")
+    }
+}
+
+impl Observer for Coverage {
+    fn instruction(&mut self, opcode: u16, before: &Registers) {
+        let pc = before.pc as usize & 0xFFF;
+        self.executions[pc] += 1;
+        self.opcodes[pc] = opcode;
+        self.flags[pc] |= EXECUTED;
+        self.flags[(pc + 1) & 0xFFF] |= EXECUTED;
+    }
+
+    fn memory_read(&mut self, address: u16) {
+        self.flags[address as usize & 0xFFF] |= READ;
+    }
+
+    fn memory_write(&mut self, address: u16, _value: u8) {
+        self.flags[address as usize & 0xFFF] |= WRITTEN;
+    }
+}
+
+/// A line of a report: an executed instruction, or any other byte.
+#[derive(Debug, Clone, Copy)]
+enum Line {
+    Instruction(u16),
+    Byte(u16),
+}
+
+fn summary_text(summary: &CoverageSummary) -> String {
+    let percent = |count: usize| 100.0 * count as f64 / summary.rom_bytes.max(1) as f64;
+    format!(
+        "{} ROM bytes: {} executed ({:.1}%), {} read as data ({:.1}%), {} written ({:.1}%), \
+         {} untouched ({:.1}%)",
+        summary.rom_bytes,
+        summary.executed,
+        percent(summary.executed),
+        summary.read,
+        percent(summary.read),
+        summary.written,
+        percent(summary.written),
+        summary.untouched,
+        percent(summary.untouched)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::test_util::assemble;
+
+    /// Runs a ROM that draws a sprite, stores and loads through I, then loops at 210.
+    fn coverage() -> Coverage {
+        let opcodes: [u16; 9] = [
+            0xA214, // 200: LD I, 214
+            0xD012, // 202: DRW V0, V1, 2
+            0xA216, // 204: LD I, 216
+            0xF033, // 206: LD B, V0
+            0xA219, // 208: LD I, 219
+            0xF155, // 20A: LD [I], V1
+            0xA21B, // 20C: LD I, 21B
+            0xF065, // 20E: LD V0, [I]
+            0x1210, // 210: JP 210
+        ];
+        let mut rom = assemble(&opcodes);
+        // 212: padding, 214: sprite, 216: digits, 219: registers, 21B: loaded, 21C: unused
+        rom.extend([0x00, 0x00, 0xFF, 0x81, 0, 0, 0, 0, 0, 0x42, 0x99]);
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&rom);
+        let mut coverage = Coverage::new(&rom);
+        chip8.run_with(20, &mut coverage);
+        coverage
+    }
+
+    #[test]
+    fn bytes_are_flagged_by_how_they_are_used() {
+        let coverage = coverage();
+        let flagged = |flag: fn(&Coverage, u16) -> bool| -> Vec<u16> {
+            (0x200..0x21D)
+                .filter(|&address| flag(&coverage, address))
+                .collect()
+        };
+        assert_eq!(
+            flagged(Coverage::is_executed),
+            (0x200..0x212).collect::<Vec<_>>()
+        );
+        assert_eq!(flagged(Coverage::is_read), [0x214, 0x215, 0x21B]);
+        assert_eq!(
+            flagged(Coverage::is_written),
+            [0x216, 0x217, 0x218, 0x219, 0x21A]
+        );
+        assert_eq!(coverage.executions(0x200), 1);
+        assert_eq!(coverage.executions(0x201), 0);
+        assert_eq!(coverage.executions(0x210), 12);
+        assert_eq!(
+            coverage.summary(),
+            CoverageSummary {
+                rom_bytes: 0x1D,
+                executed: 0x12,
+                read: 3,
+                written: 5,
+                untouched: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn listings_mark_each_line() {
+        let mut listing = Vec::new();
+        coverage().write_listing(&mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines.len(), 2 + 9 + 11);
+        assert_eq!(lines[2], "X--  200  A214          1x  LD I, 0x214");
+        assert_eq!(lines[10], "X--  210  1210         12x  JP 0x210");
+        assert_eq!(lines[13], "-R-  214  FF                ########");
+        assert_eq!(lines[14], "-R-  215  81                #......#");
+        assert_eq!(lines[15], "--W  216  00                ........");
+        assert_eq!(lines[21], "---  21C  99                #..##..#");
+    }
+
+    #[test]
+    fn lcov_lists_code_and_untouched_bytes() {
+        let mut lcov = Vec::new();
+        coverage().write_lcov(&mut lcov, "game.ch8").unwrap();
+        let lcov = String::from_utf8(lcov).unwrap();
+        let mut expected = vec!["TN:".to_string(), "SF:game.ch8".to_string()];
+        expected.extend(
+            (0x200..0x210)
+                .step_by(2)
+                .map(|address| format!("DA:{},1", address)),
+        );
+        expected.push(format!("DA:{},12", 0x210));
+        expected.extend([0x212, 0x213, 0x21C].map(|address| format!("DA:{},0", address)));
+        expected.extend(["LH:9", "LF:12", "end_of_record"].map(String::from));
+        assert_eq!(lcov.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn html_reports_escape_names() {
+        let mut html = Vec::new();
+        coverage()
+            .write_html(&mut html, "<Tom & Jerry's \"game\">.ch8")
+            .unwrap();
+        let html = String::from_utf8(html).unwrap();
+        let name = "&lt;Tom &amp; Jerry's &quot;game&quot;&gt;.ch8";
+        assert!(html.contains(&format!("<title>Coverage of {}</title>", name)));
+        assert!(html.contains(&format!("<h1>Coverage of {}</h1>", name)));
+        assert!(!html.contains("<Tom"));
+        assert!(html.contains("<span class=\"executed\">X--  200  A214"));
+        assert!(html.contains("<span class=\"read\">-R-  214"));
+        assert!(html.contains("<span class=\"written\">--W  216"));
+        assert!(html.contains("<span class=\"untouched\">---  21C"));
+    }
+
+    #[test]
+    fn formats_follow_the_extension() {
+        let format = |path: &str| CoverageFormat::from_path(Path::new(path));
+        assert_eq!(format("coverage.HTML"), CoverageFormat::Html);
+        assert_eq!(format("coverage.htm"), CoverageFormat::Html);
+        assert_eq!(format("coverage.info"), CoverageFormat::Lcov);
+        assert_eq!(format("coverage.lcov"), CoverageFormat::Lcov);
+        assert_eq!(format("coverage.txt"), CoverageFormat::Listing);
+        assert_eq!(format("coverage"), CoverageFormat::Listing);
+    }
+}
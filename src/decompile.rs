@@ -0,0 +1,805 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use crate::cfg::{Cfg, EdgeKind, Line};
+use crate::disasm::Instruction;
+
+/// Data bytes written on one line.
+const BYTES_PER_LINE: usize = 8;
+
+/// A piece of the ROM in address order: an instruction, or a byte of anything else.
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    Code(Line),
+    Byte(u16, u8),
+}
+
+impl Item {
+    fn address(&self) -> u16 {
+        match *self {
+            Item::Code(line) => line.address,
+            Item::Byte(address, _) => address,
+        }
+    }
+
+    fn line(&self) -> Option<&Line> {
+        match self {
+            Item::Code(line) => Some(line),
+            Item::Byte(..) => None,
+        }
+    }
+}
+
+/// Structured code covering `start..end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    /// `loop` at `start`, closed by the backward jump at `end - 2` as `again`.
+    Loop { start: u16, end: u16 },
+    /// A skip and a forward jump at `start` as `if begin`. With an `else`, the body ends in
+    /// a jump over the else branch, which starts at `otherwise`.
+    If {
+        start: u16,
+        end: u16,
+        otherwise: Option<u16>,
+    },
+}
+
+impl Region {
+    fn start(&self) -> u16 {
+        match *self {
+            Region::Loop { start, .. } | Region::If { start, .. } => start,
+        }
+    }
+
+    fn end(&self) -> u16 {
+        match *self {
+            Region::Loop { end, .. } | Region::If { end, .. } => end,
+        }
+    }
+
+    /// Ranges of the code the region wraps, which nested regions have to stay within.
+    fn inner(&self) -> Vec<(u16, u16)> {
+        match *self {
+            Region::Loop { start, end } => vec![(start, end - 2)],
+            Region::If {
+                start,
+                end,
+                otherwise: None,
+            } => vec![(start + 4, end)],
+            Region::If {
+                start,
+                end,
+                otherwise: Some(otherwise),
+            } => vec![(start + 4, otherwise - 2), (otherwise, end)],
+        }
+    }
+
+    fn contains(&self, address: u16) -> bool {
+        self.inner()
+            .iter()
+            .any(|&(start, end)| (start..end).contains(&address))
+    }
+
+    fn nests_in(&self, outer: &Region) -> bool {
+        outer
+            .inner()
+            .iter()
+            .any(|&(start, end)| start <= self.start() && self.end() <= end)
+    }
+}
+
+/// A ROM turned into structured code in the syntax of the Octo assembler.
+///
+/// Code is found through the ROM's [`Cfg`] and written in address order, so the output
+/// assembles back into the same bytes. Backward jumps become `loop` and `again`, skips
+/// over a forward jump `if begin`, `else` and `end`, skips out of a loop `while` and other
+/// skips `if then`, whenever nothing jumps into the middle of them. Subroutines are named
+/// after their address, and the bytes no instruction covers are written as data. Known
+/// idioms, such as printing a number in decimal, a loop drawing sprites or waiting for a key,
+/// are pointed out in comments.
+pub struct Decompiler {
+    cfg: Cfg,
+    items: Vec<Item>,
+    /// Index in `items` of the item starting at each address.
+    index: HashMap<u16, usize>,
+    /// Addresses of the last instructions of blocks leading to each address.
+    inbound: HashMap<u16, Vec<u16>>,
+    /// Regions by start address, the outermost first.
+    regions: BTreeMap<u16, Vec<Region>>,
+    whiles: BTreeSet<u16>,
+    thens: BTreeSet<u16>,
+    labels: BTreeMap<u16, String>,
+}
+
+impl Decompiler {
+    /// Decompiles `rom`, loaded at 200.
+    pub fn new(rom: &[u8]) -> Self {
+        let cfg = Cfg::new(rom);
+        let lines: BTreeMap<u16, Line> = cfg
+            .blocks()
+            .values()
+            .flat_map(|block| &block.lines)
+            .map(|&line| (line.address, line))
+            .collect();
+
+        let rom_end = (0x200 + rom.len()).min(0x1000) as u16;
+        let mut items = Vec::new();
+        let mut address = 0x200;
+        while address < rom_end {
+            match lines.get(&address) {
+                Some(&line) => {
+                    items.push(Item::Code(line));
+                    address += 2;
+                }
+                None => {
+                    items.push(Item::Byte(address, rom[address as usize - 0x200]));
+                    address += 1;
+                }
+            }
+        }
+        let index = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (item.address(), index))
+            .collect();
+
+        let mut inbound: HashMap<u16, Vec<u16>> = HashMap::new();
+        for block in cfg.blocks().values() {
+            for edge in &block.edges {
+                inbound
+                    .entry(edge.target)
+                    .or_default()
+                    .push(block.last().address);
+            }
+        }
+
+        let mut decompiler = Self {
+            cfg,
+            items,
+            index,
+            inbound,
+            regions: BTreeMap::new(),
+            whiles: BTreeSet::new(),
+            thens: BTreeSet::new(),
+            labels: BTreeMap::new(),
+        };
+        decompiler.find_regions();
+        decompiler.find_whiles();
+        decompiler.find_thens();
+        decompiler.find_labels();
+        decompiler
+    }
+
+    /// Number of `loop`s, and of `if`s with `begin` or `then`, found.
+    pub fn structures(&self) -> (usize, usize) {
+        let regions = self.regions.values().flatten();
+        let loops = regions
+            .clone()
+            .filter(|region| matches!(region, Region::Loop { .. }))
+            .count();
+        let ifs = regions.count() - loops + self.thens.len();
+        (loops, ifs)
+    }
+
+    /// Number of instructions found.
+    pub fn instructions(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.line().is_some())
+            .count()
+    }
+
+    /// The instruction starting at `address`, if the listing has one there.
+    fn line(&self, address: u16) -> Option<&Line> {
+        self.items[*self.index.get(&address)?].line()
+    }
+
+    /// Whether every item from `start` up to `end` is an instruction.
+    fn is_code(&self, start: u16, end: u16) -> bool {
+        let (Some(&first), Some(&last)) = (self.index.get(&start), self.index.get(&(end - 2)))
+        else {
+            return false;
+        };
+        first <= last
+            && self.items[first..=last]
+                .iter()
+                .all(|item| item.line().is_some())
+    }
+
+    /// Whether control only enters `start..end` at `start`.
+    fn is_single_entry(&self, start: u16, end: u16) -> bool {
+        self.inbound.iter().all(|(&target, origins)| {
+            target <= start
+                || target >= end
+                || origins.iter().all(|origin| (start..end).contains(origin))
+        })
+    }
+
+    /// Whether only the instruction before `address` leads to it.
+    fn only_reached_from(&self, address: u16, before: u16) -> bool {
+        self.inbound
+            .get(&address)
+            .is_none_or(|origins| origins.iter().all(|&origin| origin == before))
+    }
+
+    fn find_regions(&mut self) {
+        let referenced = self.references();
+        let mut candidates = Vec::new();
+        for item in &self.items {
+            let Some(&line) = item.line() else {
+                continue;
+            };
+            let address = line.address;
+            match line.instruction {
+                Instruction::Jump { nnn } if nnn <= address => candidates.push(Region::Loop {
+                    start: nnn,
+                    end: address + 2,
+                }),
+                instruction if skip_condition(instruction).is_some() => {
+                    let Some(&Line {
+                        instruction: Instruction::Jump { nnn: target },
+                        ..
+                    }) = self.line(address + 2)
+                    else {
+                        continue;
+                    };
+                    // `begin` writes the jump itself, so nothing else may lead to it
+                    if target <= address + 4
+                        || !self.only_reached_from(address + 2, address)
+                        || referenced.contains(&(address + 2))
+                    {
+                        continue;
+                    }
+                    let otherwise = match self.line(target - 2).map(|line| line.instruction) {
+                        Some(Instruction::Jump { nnn })
+                            if nnn > target && target - 2 > address + 2 =>
+                        {
+                            Some((target, nnn))
+                        }
+                        _ => None,
+                    };
+                    candidates.push(match otherwise {
+                        Some((otherwise, end)) if self.is_code(otherwise, end) => Region::If {
+                            start: address,
+                            end,
+                            otherwise: Some(otherwise),
+                        },
+                        _ => Region::If {
+                            start: address,
+                            end: target,
+                            otherwise: None,
+                        },
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        candidates.retain(|region| {
+            self.is_code(region.start(), region.end())
+                && self.is_single_entry(region.start(), region.end())
+        });
+        candidates.sort_by_key(|region| (region.start(), std::cmp::Reverse(region.end())));
+        let mut accepted: Vec<Region> = Vec::new();
+        for candidate in candidates {
+            let fits = accepted.iter().all(|region| {
+                region.end() <= candidate.start()
+                    || candidate.end() <= region.start()
+                    || candidate.nests_in(region)
+                    || region.nests_in(&candidate)
+            });
+            if fits {
+                accepted.push(candidate);
+            }
+        }
+        for region in accepted {
+            self.regions.entry(region.start()).or_default().push(region);
+        }
+    }
+
+    /// The innermost region whose code contains `address`.
+    fn innermost(&self, address: u16) -> Option<&Region> {
+        self.regions
+            .values()
+            .flatten()
+            .filter(|region| region.contains(address))
+            .min_by_key(|region| region.end() - region.start())
+    }
+
+    fn starts_region(&self, address: u16) -> bool {
+        self.regions.contains_key(&address)
+    }
+
+    /// Skips over a jump out of their innermost loop.
+    fn find_whiles(&mut self) {
+        let referenced = self.references();
+        let mut whiles = BTreeSet::new();
+        for line in self.items.iter().filter_map(Item::line) {
+            let address = line.address;
+            if skip_condition(line.instruction).is_none() || self.starts_region(address) {
+                continue;
+            }
+            let Some(&Region::Loop { end, .. }) = self.innermost(address) else {
+                continue;
+            };
+            let exits = matches!(
+                self.line(address + 2),
+                Some(Line { instruction: Instruction::Jump { nnn }, .. }) if *nnn == end
+            );
+            if exits
+                && self.innermost(address + 2) == self.innermost(address)
+                && self.only_reached_from(address + 2, address)
+                && !referenced.contains(&(address + 2))
+            {
+                whiles.insert(address);
+            }
+        }
+        self.whiles = whiles;
+    }
+
+    /// Skips over a single statement in the same region, or over the `again` of their loop.
+    fn find_thens(&mut self) {
+        let referenced = self.references();
+        let mut thens = BTreeSet::new();
+        for line in self.items.iter().filter_map(Item::line) {
+            let address = line.address;
+            if skip_condition(line.instruction).is_none()
+                || self.whiles.contains(&address)
+                || self.starts_region(address)
+                || !is_exact(line)
+            {
+                continue;
+            }
+            let Some(next) = self.line(address + 2) else {
+                continue;
+            };
+            if skip_condition(next.instruction).is_some()
+                || self.starts_region(next.address)
+                || referenced.contains(&next.address)
+                || !self.only_reached_from(next.address, address)
+            {
+                continue;
+            }
+            let region = self.innermost(address);
+            let closes_loop =
+                matches!(region, Some(&Region::Loop { end, .. }) if end == next.address + 2);
+            if closes_loop || region == self.innermost(next.address) {
+                thens.insert(address);
+            }
+        }
+        self.thens = thens;
+    }
+
+    /// Addresses loaded into I or jumped to through V0, which get labels without being
+    /// the target of an edge.
+    fn references(&self) -> BTreeSet<u16> {
+        self.items
+            .iter()
+            .filter_map(Item::line)
+            .filter_map(|line| match line.instruction {
+                Instruction::LoadI { nnn } | Instruction::JumpV0 { nnn } => Some(nnn),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the jump at `address` is written as part of a region or a `while`.
+    fn is_structural_jump(&self, address: u16) -> bool {
+        if self.whiles.contains(&(address - 2)) {
+            return true;
+        }
+        self.regions.values().flatten().any(|region| match *region {
+            Region::Loop { end, .. } => address == end - 2,
+            Region::If {
+                start, otherwise, ..
+            } => address == start + 2 || otherwise == Some(address + 2),
+        })
+    }
+
+    fn find_labels(&mut self) {
+        let mut targets = BTreeSet::new();
+        for line in self.items.iter().filter_map(Item::line) {
+            match line.instruction {
+                Instruction::Jump { nnn } if !self.is_structural_jump(line.address) => {
+                    targets.insert(nnn);
+                }
+                Instruction::Call { nnn }
+                | Instruction::JumpV0 { nnn }
+                | Instruction::LoadI { nnn } => {
+                    targets.insert(nnn);
+                }
+                _ => (),
+            }
+        }
+        targets.extend(self.cfg.subroutines().keys());
+
+        for target in targets {
+            let Some(&index) = self.index.get(&target) else {
+                continue;
+            };
+            let name = if target == 0x200 {
+                "main".to_string()
+            } else if self.cfg.subroutines().contains_key(&target) {
+                format!("sub_{:03X}", target)
+            } else if self.items[index].line().is_some() {
+                format!("label_{:03X}", target)
+            } else {
+                format!("data_{:03X}", target)
+            };
+            self.labels.insert(target, name);
+        }
+    }
+
+    /// Writes the Octo source, with `name` for the ROM in the header.
+    pub fn write_octo(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        let (loops, ifs) = self.structures();
+        writeln!(out, "# {} decompiled by chippers decompile", name)?;
+        writeln!(
+            out,
+            "# {} instructions, {} loops, {} ifs",
+            self.instructions(),
+            loops,
+            ifs
+        )?;
+
+        let mut callers: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        for block in self.cfg.blocks().values() {
+            for edge in block
+                .edges
+                .iter()
+                .filter(|edge| edge.kind == EdgeKind::Call)
+            {
+                callers
+                    .entry(edge.target)
+                    .or_default()
+                    .push(block.last().address);
+            }
+        }
+
+        let mut open: Vec<Region> = Vec::new();
+        let mut data = Vec::new();
+        let mut index = 0;
+        while index < self.items.len() {
+            let item = self.items[index];
+            let address = item.address();
+
+            while let Some(&Region::If { end, .. }) = open.last() {
+                if end > address {
+                    break;
+                }
+                open.pop();
+                write_data(out, &mut data, depth(&open))?;
+                writeln!(out, "{}end", indent(depth(&open)))?;
+            }
+            if let Some(label) = self.labels.get(&address) {
+                write_data(out, &mut data, depth(&open))?;
+                if let Some(callers) = callers.get(&address) {
+                    let callers: Vec<_> = callers.iter().map(|a| format!("{:03X}", a)).collect();
+                    writeln!(out)?;
+                    writeln!(out, "# called from {}", callers.join(", "))?;
+                } else if address == 0x200 || label.starts_with("data") {
+                    writeln!(out)?;
+                }
+                writeln!(out, ": {}", label)?;
+            }
+
+            let Item::Code(line) = item else {
+                if let Item::Byte(_, byte) = item {
+                    data.push(byte);
+                }
+                index += 1;
+                continue;
+            };
+            write_data(out, &mut data, depth(&open))?;
+
+            let mut skip_items = 1;
+            for &region in self.regions.get(&address).into_iter().flatten() {
+                let pad = indent(depth(&open));
+                match region {
+                    Region::Loop { start, end } => {
+                        writeln!(out, "{}loop{}", pad, self.loop_comment(start, end))?
+                    }
+                    Region::If { .. } => {
+                        let (condition, _) = skip_condition(line.instruction).unwrap();
+                        writeln!(out, "{}if {} begin", pad, condition)?;
+                        skip_items = 2;
+                    }
+                }
+                open.push(region);
+            }
+            if skip_items == 2 {
+                index += 2;
+                continue;
+            }
+
+            let pad = indent(depth(&open));
+            match open.last() {
+                Some(&Region::Loop { end, .. }) if address == end - 2 => {
+                    open.pop();
+                    writeln!(out, "{}again", indent(depth(&open)))?;
+                }
+                Some(&Region::If {
+                    otherwise: Some(otherwise),
+                    ..
+                }) if address == otherwise - 2 => {
+                    writeln!(out, "{}else", indent(depth(&open) - 1))?;
+                }
+                _ if self.whiles.contains(&address) => {
+                    let (condition, _) = skip_condition(line.instruction).unwrap();
+                    writeln!(out, "{}while {}", pad, condition)?;
+                    skip_items = 2;
+                }
+                _ if self.thens.contains(&address) => {
+                    let (_, condition) = skip_condition(line.instruction).unwrap();
+                    let next = self.line(address + 2).unwrap();
+                    match open.last() {
+                        Some(&Region::Loop { end, .. }) if next.address == end - 2 => {
+                            writeln!(out, "{}if {} then", pad, condition)?;
+                            open.pop();
+                            writeln!(out, "{}again", indent(depth(&open)))?;
+                        }
+                        _ => match self.statement(next) {
+                            Some(statement) => {
+                                writeln!(out, "{}if {} then {}", pad, condition, statement)?
+                            }
+                            None => {
+                                writeln!(out, "{}if {} then", pad, condition)?;
+                                writeln!(out, "{}{}", pad, raw(next))?;
+                            }
+                        },
+                    }
+                    skip_items = 2;
+                }
+                _ => {
+                    if let Some(comment) = self.idiom(index) {
+                        writeln!(out, "{}# {}", pad, comment)?;
+                    }
+                    match self.statement(&line) {
+                        Some(statement) => writeln!(out, "{}{}", pad, statement)?,
+                        None => writeln!(out, "{}{}", pad, raw(&line))?,
+                    }
+                }
+            }
+            index += skip_items;
+        }
+        write_data(out, &mut data, 1)?;
+        while let Some(region) = open.pop() {
+            if let Region::If { .. } = region {
+                writeln!(out, "{}end", indent(open.len() + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The Octo statement of `line`, if it has one that assembles to the same opcode.
+    fn statement(&self, line: &Line) -> Option<String> {
+        use Instruction::*;
+        if !is_exact(line) {
+            return None;
+        }
+        let address = |nnn: u16| match self.labels.get(&nnn) {
+            Some(label) => label.clone(),
+            None => format!("0x{:03X}", nnn),
+        };
+        Some(match line.instruction {
+            Clear => "clear".to_string(),
+            Return => "return".to_string(),
+            Sys { nnn } => format!("native 0x{:03X}", nnn),
+            Jump { nnn } => format!("jump {}", address(nnn)),
+            // A call is written as the name of the subroutine
+            Call { nnn } => self.labels.get(&nnn)?.clone(),
+            LoadImm { x, nn } => format!("v{:x} := {}", x, nn),
+            AddImm { x, nn } => format!("v{:x} += {}", x, nn),
+            Move { x, y } => format!("v{:x} := v{:x}", x, y),
+            Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+            And { x, y } => format!("v{:x} &= v{:x}", x, y),
+            Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+            Add { x, y } => format!("v{:x} += v{:x}", x, y),
+            Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+            ShiftRight { x, y } => format!("v{:x} >>= v{:x}", x, y),
+            SubReverse { x, y } => format!("v{:x} =- v{:x}", x, y),
+            ShiftLeft { x, y } => format!("v{:x} <<= v{:x}", x, y),
+            LoadI { nnn } => format!("i := {}", address(nnn)),
+            JumpV0 { nnn } => format!("jump0 {}", address(nnn)),
+            Random { x, nn } => format!("v{:x} := random {}", x, nn),
+            Draw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+            GetDelay { x } => format!("v{:x} := delay", x),
+            WaitKey { x } => format!("v{:x} := key", x),
+            SetDelay { x } => format!("delay := v{:x}", x),
+            SetSound { x } => format!("buzzer := v{:x}", x),
+            AddI { x } => format!("i += v{:x}", x),
+            Font { x } => format!("i := hex v{:x}", x),
+            Bcd { x } => format!("bcd v{:x}", x),
+            Store { x } => format!("save v{:x}", x),
+            Load { x } => format!("load v{:x}", x),
+            SkipEqImm { .. }
+            | SkipNeImm { .. }
+            | SkipEq { .. }
+            | SkipNe { .. }
+            | SkipKey { .. }
+            | SkipNotKey { .. }
+            | Unknown(_) => return None,
+        })
+    }
+
+    /// A comment on what the loop from `start` to `end` does, if it is a known idiom.
+    fn loop_comment(&self, start: u16, end: u16) -> String {
+        if start + 4 == end {
+            if let Some(line) = self.line(start).filter(|_| self.thens.contains(&start)) {
+                match line.instruction {
+                    Instruction::SkipKey { x } => {
+                        return format!(" # waits until key v{:x} is pressed", x)
+                    }
+                    Instruction::SkipNotKey { x } => {
+                        return format!(" # waits until key v{:x} is released", x)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        // Only the innermost loop around a draw gets the comment
+        let nested = |address: u16| {
+            self.regions.values().flatten().any(|region| match *region {
+                Region::Loop { start: s, end: e } => {
+                    (s, e) != (start, end) && s >= start && e <= end && (s..e).contains(&address)
+                }
+                Region::If { .. } => false,
+            })
+        };
+        let draws = (start..end)
+            .step_by(2)
+            .filter_map(|address| self.line(address))
+            .any(|line| {
+                matches!(line.instruction, Instruction::Draw { .. }) && !nested(line.address)
+            });
+        if draws {
+            " # draws sprites in a loop".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    /// A comment on the idiom starting with the item at `index`, if any.
+    fn idiom(&self, index: usize) -> Option<String> {
+        let following = |count: usize| {
+            self.items[index + 1..]
+                .iter()
+                .take(count)
+                .map_while(Item::line)
+                .map(|line| line.instruction)
+        };
+        match self.items[index].line()?.instruction {
+            Instruction::Bcd { x } => {
+                if !following(2).any(|instruction| instruction == Instruction::Load { x: 2 }) {
+                    return Some(format!("splits v{:x} into decimal digits", x));
+                }
+                let draws = following(12)
+                    .any(|instruction| matches!(instruction, Instruction::Font { .. }));
+                Some(if draws {
+                    format!("draws v{:x} in decimal", x)
+                } else {
+                    format!("loads the decimal digits of v{:x} into v0 to v2", x)
+                })
+            }
+            Instruction::WaitKey { x } => Some(format!("waits for a key and puts it in v{:x}", x)),
+            _ => None,
+        }
+    }
+}
+
+/// The conditions of a skip instruction: when it skips, and when it runs the instruction
+/// after it.
+fn skip_condition(instruction: Instruction) -> Option<(String, String)> {
+    use Instruction::*;
+    let (skips, runs) = match instruction {
+        SkipEqImm { x, nn } => (format!("v{:x} == {}", x, nn), format!("v{:x} != {}", x, nn)),
+        SkipNeImm { x, nn } => (format!("v{:x} != {}", x, nn), format!("v{:x} == {}", x, nn)),
+        SkipEq { x, y } => (
+            format!("v{:x} == v{:x}", x, y),
+            format!("v{:x} != v{:x}", x, y),
+        ),
+        SkipNe { x, y } => (
+            format!("v{:x} != v{:x}", x, y),
+            format!("v{:x} == v{:x}", x, y),
+        ),
+        SkipKey { x } => (format!("v{:x} key", x), format!("v{:x} -key", x)),
+        SkipNotKey { x } => (format!("v{:x} -key", x), format!("v{:x} key", x)),
+        _ => return None,
+    };
+    Some((skips, runs))
+}
+
+/// Whether Octo writes `line` back as the same opcode: the interpreter ignores a few bits
+/// that the assembler sets to zero.
+fn is_exact(line: &Line) -> bool {
+    Instruction::decode(line.opcode) == line.instruction
+}
+
+/// `line` as the bytes of its opcode, for instructions Octo has no statement for.
+fn raw(line: &Line) -> String {
+    format!(
+        "0x{:02X} 0x{:02X} # {}",
+        line.opcode >> 8,
+        line.opcode & 0xFF,
+        line.instruction
+    )
+}
+
+/// Depth of the statements inside the `open` regions, one more than the labels.
+fn depth(open: &[Region]) -> usize {
+    open.len() + 1
+}
+
+fn indent(depth: usize) -> String {
+    "\t".repeat(depth)
+}
+
+/// Writes the data bytes collected so far and clears them.
+fn write_data(out: &mut impl Write, data: &mut Vec<u8>, depth: usize) -> io::Result<()> {
+    for row in data.chunks(BYTES_PER_LINE) {
+        let bytes: Vec<_> = row.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+        writeln!(out, "{}{}", indent(depth), bytes.join(" "))?;
+    }
+    data.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assemble;
+
+    /// The Octo source of `opcodes` without the header.
+    fn decompile(opcodes: &[u16]) -> String {
+        let mut out = Vec::new();
+        Decompiler::new(&assemble(opcodes))
+            .write_octo(&mut out, "test.ch8")
+            .unwrap();
+        let source = String::from_utf8(out).unwrap();
+        source.lines().skip(3).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn skip_over_forward_jump_is_if_begin() {
+        assert_eq!(
+            decompile(&[0x3001, 0x1208, 0x7101, 0x7201, 0x1208]),
+            ": main\n\tif v0 == 1 begin\n\t\tv1 += 1\n\t\tv2 += 1\n\tend\n\tloop\n\tagain"
+        );
+    }
+
+    #[test]
+    fn jump_over_else_branch_is_else() {
+        assert_eq!(
+            decompile(&[0x3001, 0x1208, 0x7101, 0x120A, 0x7201, 0x120A]),
+            ": main\n\tif v0 == 1 begin\n\t\tv1 += 1\n\telse\n\t\tv2 += 1\n\tend\n\tloop\n\tagain"
+        );
+    }
+
+    #[test]
+    fn skip_over_loop_exit_is_while() {
+        assert_eq!(
+            decompile(&[0x7101, 0x3105, 0x1208, 0x1200, 0x1208]),
+            ": main\n\tloop\n\t\tv1 += 1\n\t\twhile v1 == 5\n\tagain\n\tloop\n\tagain"
+        );
+    }
+
+    #[test]
+    fn skip_over_statement_is_if_then() {
+        assert_eq!(
+            decompile(&[0x7301, 0xE19E, 0x7201, 0x1200]),
+            ": main\n\tloop\n\t\tv3 += 1\n\t\tif v1 -key then v2 += 1\n\tagain"
+        );
+    }
+
+    #[test]
+    fn jump_reached_from_elsewhere_is_not_swallowed() {
+        // The jump at 202 is also the target of the jump at 206, so it needs a label
+        let source = decompile(&[0x3001, 0x1208, 0x7101, 0x1202, 0x1208]);
+        assert!(!source.contains("begin"), "{}", source);
+        assert!(
+            source.contains(": label_202\n\tjump label_208"),
+            "{}",
+            source
+        );
+    }
+}
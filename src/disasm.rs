@@ -0,0 +1,290 @@
+use core::fmt;
+use core::str::FromStr;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A decoded CHIP-8 instruction.
+///
+/// `x` and `y` are register numbers, `nn` an immediate byte and `nnn` an address, as in
+/// the usual `8xy4`/`6xnn`/`1nnn` notation. Mnemonics follow Cowgod's reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// `00E0`
+    Clear,
+    /// `00EE`
+    Return,
+    /// `0nnn`, a call into COSMAC VIP machine code.
+    Sys { nnn: u16 },
+    /// `1nnn`
+    Jump { nnn: u16 },
+    /// `2nnn`
+    Call { nnn: u16 },
+    /// `3xnn`
+    SkipEqImm { x: u8, nn: u8 },
+    /// `4xnn`
+    SkipNeImm { x: u8, nn: u8 },
+    /// `5xy0`
+    SkipEq { x: u8, y: u8 },
+    /// `6xnn`
+    LoadImm { x: u8, nn: u8 },
+    /// `7xnn`
+    AddImm { x: u8, nn: u8 },
+    /// `8xy0`
+    Move { x: u8, y: u8 },
+    /// `8xy1`
+    Or { x: u8, y: u8 },
+    /// `8xy2`
+    And { x: u8, y: u8 },
+    /// `8xy3`
+    Xor { x: u8, y: u8 },
+    /// `8xy4`
+    Add { x: u8, y: u8 },
+    /// `8xy5`
+    Sub { x: u8, y: u8 },
+    /// `8xy6`
+    ShiftRight { x: u8, y: u8 },
+    /// `8xy7`
+    SubReverse { x: u8, y: u8 },
+    /// `8xyE`
+    ShiftLeft { x: u8, y: u8 },
+    /// `9xy0`
+    SkipNe { x: u8, y: u8 },
+    /// `Annn`
+    LoadI { nnn: u16 },
+    /// `Bnnn`
+    JumpV0 { nnn: u16 },
+    /// `Cxnn`
+    Random { x: u8, nn: u8 },
+    /// `Dxyn`
+    Draw { x: u8, y: u8, n: u8 },
+    /// `Ex9E`
+    SkipKey { x: u8 },
+    /// `ExA1`
+    SkipNotKey { x: u8 },
+    /// `Fx07`
+    GetDelay { x: u8 },
+    /// `Fx0A`
+    WaitKey { x: u8 },
+    /// `Fx15`
+    SetDelay { x: u8 },
+    /// `Fx18`
+    SetSound { x: u8 },
+    /// `Fx1E`
+    AddI { x: u8 },
+    /// `Fx29`
+    Font { x: u8 },
+    /// `Fx33`
+    Bcd { x: u8 },
+    /// `Fx55`
+    Store { x: u8 },
+    /// `Fx65`
+    Load { x: u8 },
+    /// Anything else, shown as a data word.
+    Unknown(u16),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Self {
+        let x = ((opcode >> 8) & 0xF) as u8;
+        let y = ((opcode >> 4) & 0xF) as u8;
+        let n = (opcode & 0xF) as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        match (opcode >> 12, n) {
+            (0x0, _) if opcode == 0x00E0 => Instruction::Clear,
+            (0x0, _) if opcode == 0x00EE => Instruction::Return,
+            (0x0, _) => Instruction::Sys { nnn },
+            (0x1, _) => Instruction::Jump { nnn },
+            (0x2, _) => Instruction::Call { nnn },
+            (0x3, _) => Instruction::SkipEqImm { x, nn },
+            (0x4, _) => Instruction::SkipNeImm { x, nn },
+            (0x5, 0x0) => Instruction::SkipEq { x, y },
+            (0x6, _) => Instruction::LoadImm { x, nn },
+            (0x7, _) => Instruction::AddImm { x, nn },
+            (0x8, 0x0) => Instruction::Move { x, y },
+            (0x8, 0x1) => Instruction::Or { x, y },
+            (0x8, 0x2) => Instruction::And { x, y },
+            (0x8, 0x3) => Instruction::Xor { x, y },
+            (0x8, 0x4) => Instruction::Add { x, y },
+            (0x8, 0x5) => Instruction::Sub { x, y },
+            (0x8, 0x6) => Instruction::ShiftRight { x, y },
+            (0x8, 0x7) => Instruction::SubReverse { x, y },
+            (0x8, 0xE) => Instruction::ShiftLeft { x, y },
+            (0x9, 0x0) => Instruction::SkipNe { x, y },
+            (0xA, _) => Instruction::LoadI { nnn },
+            (0xB, _) => Instruction::JumpV0 { nnn },
+            (0xC, _) => Instruction::Random { x, nn },
+            (0xD, _) => Instruction::Draw { x, y, n },
+            (0xE, _) if nn == 0x9E => Instruction::SkipKey { x },
+            (0xE, _) if nn == 0xA1 => Instruction::SkipNotKey { x },
+            (0xF, _) => match nn {
+                0x07 => Instruction::GetDelay { x },
+                0x0A => Instruction::WaitKey { x },
+                0x15 => Instruction::SetDelay { x },
+                0x18 => Instruction::SetSound { x },
+                0x1E => Instruction::AddI { x },
+                0x29 => Instruction::Font { x },
+                0x33 => Instruction::Bcd { x },
+                0x55 => Instruction::Store { x },
+                0x65 => Instruction::Load { x },
+                _ => Instruction::Unknown(opcode),
+            },
+            _ => Instruction::Unknown(opcode),
+        }
+    }
+
+    pub fn class(self) -> OpClass {
+        match self {
+            Instruction::Clear | Instruction::Draw { .. } | Instruction::Font { .. } => {
+                OpClass::Display
+            }
+            Instruction::Return
+            | Instruction::Sys { .. }
+            | Instruction::Jump { .. }
+            | Instruction::Call { .. }
+            | Instruction::JumpV0 { .. } => OpClass::Flow,
+            Instruction::SkipEqImm { .. }
+            | Instruction::SkipNeImm { .. }
+            | Instruction::SkipEq { .. }
+            | Instruction::SkipNe { .. } => OpClass::Skip,
+            Instruction::LoadImm { .. }
+            | Instruction::Move { .. }
+            | Instruction::LoadI { .. }
+            | Instruction::Random { .. } => OpClass::Load,
+            Instruction::AddImm { .. }
+            | Instruction::Or { .. }
+            | Instruction::And { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Add { .. }
+            | Instruction::Sub { .. }
+            | Instruction::ShiftRight { .. }
+            | Instruction::SubReverse { .. }
+            | Instruction::ShiftLeft { .. }
+            | Instruction::AddI { .. } => OpClass::Arithmetic,
+            Instruction::SkipKey { .. }
+            | Instruction::SkipNotKey { .. }
+            | Instruction::WaitKey { .. } => OpClass::Keypad,
+            Instruction::GetDelay { .. }
+            | Instruction::SetDelay { .. }
+            | Instruction::SetSound { .. } => OpClass::Timer,
+            Instruction::Bcd { .. } | Instruction::Store { .. } | Instruction::Load { .. } => {
+                OpClass::Memory
+            }
+            Instruction::Unknown(_) => OpClass::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::Sys { nnn } => write!(f, "SYS {:#05X}", nnn),
+            Instruction::Jump { nnn } => write!(f, "JP {:#05X}", nnn),
+            Instruction::Call { nnn } => write!(f, "CALL {:#05X}", nnn),
+            Instruction::SkipEqImm { x, nn } => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Instruction::SkipNeImm { x, nn } => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Instruction::SkipEq { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LoadImm { x, nn } => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Instruction::AddImm { x, nn } => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNe { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI { nnn } => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::JumpV0 { nnn } => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Random { x, nn } => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::GetDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::Unknown(opcode) => write!(f, "DW {:#06X}", opcode),
+        }
+    }
+}
+
+/// Broad groups of instructions, for filtering traces and summing up profiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum OpClass {
+    /// CLS, DRW and LD F.
+    Display,
+    /// Jumps, calls and returns.
+    Flow,
+    /// Conditional skips on registers.
+    Skip,
+    /// Loads of immediates, registers, I and random numbers.
+    Load,
+    /// Register and I arithmetic and logic.
+    Arithmetic,
+    /// SKP, SKNP and the Fx0A key wait.
+    Keypad,
+    Timer,
+    /// BCD, register stores and loads through I.
+    Memory,
+    Unknown,
+}
+
+impl OpClass {
+    pub const ALL: [OpClass; 9] = [
+        OpClass::Display,
+        OpClass::Flow,
+        OpClass::Skip,
+        OpClass::Load,
+        OpClass::Arithmetic,
+        OpClass::Keypad,
+        OpClass::Timer,
+        OpClass::Memory,
+        OpClass::Unknown,
+    ];
+
+    /// The name used for this class on the command line and in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            OpClass::Display => "display",
+            OpClass::Flow => "flow",
+            OpClass::Skip => "skip",
+            OpClass::Load => "load",
+            OpClass::Arithmetic => "arithmetic",
+            OpClass::Keypad => "keypad",
+            OpClass::Timer => "timer",
+            OpClass::Memory => "memory",
+            OpClass::Unknown => "unknown",
+        }
+    }
+}
+
+impl FromStr for OpClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        OpClass::ALL
+            .into_iter()
+            .find(|class| class.name() == lower)
+            .ok_or_else(|| {
+                let names: Vec<_> = OpClass::ALL.iter().map(|class| class.name()).collect();
+                format!(
+                    "unknown opcode class '{}', expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
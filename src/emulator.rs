@@ -0,0 +1,574 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::chip8::{Chip8, Observer};
+use crate::gamepad::Gamepad;
+use crate::movie::{Movie, MovieEvent, MovieRecorder};
+
+/// Emulated frames to run in a single update before giving up on catching up.
+const MAX_FRAMES_PER_UPDATE: u32 = 10;
+
+/// How many times faster than real time frames run while fast-forwarding.
+const FAST_FORWARD_SPEED: u32 = 4;
+
+/// Input delivered by a frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    /// Pauses or resumes emulation.
+    Pause,
+    /// Restarts the program from the state the emulator was created with.
+    Reset,
+    /// Remembers the current machine state, replacing the previously saved one.
+    SaveState,
+    /// Goes back to the saved machine state.
+    LoadState,
+    /// Starts or stops running faster than real time.
+    FastForward(bool),
+    Quit,
+}
+
+/// The host side of the emulator: a window, a terminal, or nothing at all.
+pub trait Frontend {
+    /// Shows the framebuffer. `dirty_rows` flags the rows changed since the last call.
+    fn present(&mut self, chip8: &Chip8, dirty_rows: u32) -> io::Result<()>;
+
+    /// Waits until `deadline` at the latest for the next input event.
+    ///
+    /// Returns `None` once the deadline has passed without input. Only used by
+    /// [`Emulator::run`]; event-driven frontends feed input to the emulator themselves.
+    fn poll_input(&mut self, deadline: Instant) -> io::Result<Option<InputEvent>>;
+
+    /// Turns the buzzer on or off.
+    fn set_buzzer(&mut self, _on: bool) {}
+
+    /// Called when emulation is paused or resumed.
+    fn set_paused(&mut self, _paused: bool) {}
+
+    /// The clock emulated frames are scheduled against.
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Called after every emulated frame, before anything is presented.
+    fn frame_finished(&mut self, _chip8: &Chip8, _frame: u64) {}
+}
+
+/// Runs cycles of a [`Chip8`] in place of its interpreter, such as the JIT or a recompiled
+/// ROM. It has to leave the machine in exactly the state [`Chip8::run`] would.
+pub trait Backend {
+    fn run(&mut self, chip8: &mut Chip8, cycles: u64);
+}
+
+/// A movie being recorded or played back.
+enum MovieMode {
+    Recording(MovieRecorder),
+    Playing(Movie),
+}
+
+/// Drives a [`Chip8`] at a fixed number of emulated frames per second.
+///
+/// Every emulated frame runs `cycles_per_second / frames_per_second` cycles and ticks the
+/// timers at 60 Hz of emulated time. When the host falls behind, missed frames are caught
+/// up so the emulated time stays consistent.
+///
+/// Every cycle is reported to the [`Observer`] `O`, which watches nothing by default.
+pub struct Emulator<O = ()> {
+    chip8: Chip8,
+    observer: O,
+    initial_state: Chip8,
+    saved_state: Option<Chip8>,
+    gamepad: Option<Gamepad>,
+    backend: Option<Box<dyn Backend>>,
+    movie: Option<MovieMode>,
+    frame: u64,
+    next_frame: Option<Instant>,
+    frames_per_second: u32,
+    frame_duration: Duration,
+    cycles_per_frame: f64,
+    cycle_budget: f64,
+    timer_ticks_per_frame: f64,
+    timer_budget: f64,
+    buzzer: bool,
+    redraw: bool,
+    paused: bool,
+    paused_reported: bool,
+    fast_forward: bool,
+}
+
+impl Emulator {
+    pub fn new(chip8: Chip8, frames_per_second: u32, cycles_per_second: u32) -> Self {
+        Self::with_observer(chip8, frames_per_second, cycles_per_second, ())
+    }
+}
+
+impl<O: Observer> Emulator<O> {
+    pub fn with_observer(
+        chip8: Chip8,
+        frames_per_second: u32,
+        cycles_per_second: u32,
+        observer: O,
+    ) -> Self {
+        Self {
+            observer,
+            initial_state: chip8.clone(),
+            saved_state: None,
+            gamepad: None,
+            backend: None,
+            movie: None,
+            chip8,
+            frame: 0,
+            next_frame: None,
+            frames_per_second,
+            frame_duration: Duration::from_secs_f64(1.0 / frames_per_second as f64),
+            cycles_per_frame: cycles_per_second as f64 / frames_per_second as f64,
+            cycle_budget: 0.0,
+            timer_ticks_per_frame: 60.0 / frames_per_second as f64,
+            timer_budget: 0.0,
+            buzzer: false,
+            redraw: true,
+            paused: false,
+            paused_reported: false,
+            fast_forward: false,
+        }
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+
+    /// Number of emulated frames run so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn frames_per_second(&self) -> u32 {
+        self.frames_per_second
+    }
+
+    /// Presses a key, unless a movie is playing and owns the keypad.
+    pub fn press_key(&mut self, key: u8) {
+        self.apply_user_event(MovieEvent::KeyDown(key));
+    }
+
+    /// Releases a key, unless a movie is playing and owns the keypad.
+    pub fn release_key(&mut self, key: u8) {
+        self.apply_user_event(MovieEvent::KeyUp(key));
+    }
+
+    /// Records every keypad edge and reset from now on. Start recording before the first
+    /// frame, as movies always begin at power-on.
+    pub fn record_movie(&mut self, recorder: MovieRecorder) {
+        self.movie = Some(MovieMode::Recording(recorder));
+    }
+
+    /// Replays the input of `movie` instead of the user's until the movie ends.
+    ///
+    /// The emulator must have been created from [`MovieHeader::chip8`] with the movie's
+    /// frame and clock rate. A state hash that does not match makes [`Emulator::update`]
+    /// fail.
+    ///
+    /// [`MovieHeader::chip8`]: crate::movie::MovieHeader::chip8
+    pub fn play_movie(&mut self, movie: Movie) {
+        self.movie = Some(MovieMode::Playing(movie));
+    }
+
+    pub fn is_playing_movie(&self) -> bool {
+        matches!(self.movie, Some(MovieMode::Playing(_)))
+    }
+
+    /// Stops recording or playing a movie, writing the end of a recorded movie.
+    pub fn finish_movie(&mut self) -> io::Result<()> {
+        match self.movie.take() {
+            Some(MovieMode::Recording(recorder)) => recorder.finish(self.frame),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply_user_event(&mut self, event: MovieEvent) {
+        match &mut self.movie {
+            Some(MovieMode::Playing(_)) => return,
+            Some(MovieMode::Recording(recorder)) => recorder.record(self.frame, event),
+            None => (),
+        }
+        self.apply(event);
+    }
+
+    fn apply(&mut self, event: MovieEvent) {
+        match event {
+            MovieEvent::KeyDown(key) => self.chip8.press_key(key),
+            MovieEvent::KeyUp(key) => self.chip8.release_key(key),
+            MovieEvent::Reset => {
+                let initial_state = self.initial_state.clone();
+                self.restore(initial_state);
+            }
+            MovieEvent::Hash(_) => (),
+        }
+    }
+
+    /// Reads input from `gamepad` at the start of every update, in addition to the
+    /// frontend's own input.
+    pub fn attach_gamepad(&mut self, gamepad: Gamepad) {
+        self.gamepad = Some(gamepad);
+    }
+
+    /// Runs the cycles of every following frame with `backend` instead of the interpreter.
+    /// The observer does not see the instructions a backend runs.
+    pub fn set_backend(&mut self, backend: Box<dyn Backend>) {
+        self.backend = Some(backend);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        self.fast_forward = fast_forward;
+    }
+
+    /// Restarts from the state the emulator was created with.
+    pub fn reset(&mut self) {
+        self.apply_user_event(MovieEvent::Reset);
+    }
+
+    /// Remembers the current machine state for [`Emulator::load_state`].
+    pub fn save_state(&mut self) {
+        self.saved_state = Some(self.chip8.clone());
+    }
+
+    /// Goes back to the state remembered by [`Emulator::save_state`], returning false if
+    /// nothing was saved yet or a movie is being recorded or played, which a jump to
+    /// another state would break.
+    pub fn load_state(&mut self) -> bool {
+        if self.movie.is_some() {
+            return false;
+        }
+        match self.saved_state.clone() {
+            Some(state) => {
+                self.restore(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the machine, keeping the keys the user is holding right now.
+    fn restore(&mut self, mut state: Chip8) {
+        for key in 0..16 {
+            if self.chip8.keypad().is_down(key) {
+                state.press_key(key);
+            } else {
+                state.release_key(key);
+            }
+        }
+        self.chip8 = state;
+        self.request_redraw();
+    }
+
+    /// Applies `event`, returning false if the frontend asked to quit.
+    pub fn handle_input(&mut self, event: InputEvent) -> bool {
+        match event {
+            InputEvent::KeyDown(key) => self.press_key(key),
+            InputEvent::KeyUp(key) => self.release_key(key),
+            InputEvent::Pause => self.paused = !self.paused,
+            InputEvent::Reset => self.reset(),
+            InputEvent::SaveState => self.save_state(),
+            InputEvent::LoadState => {
+                self.load_state();
+            }
+            InputEvent::FastForward(on) => self.fast_forward = on,
+            InputEvent::Quit => return false,
+        }
+        true
+    }
+
+    /// Makes the next update present the whole screen, e.g. after the window was resized.
+    pub fn request_redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Runs the cycles of a single emulated frame.
+    ///
+    /// Fails if a movie being played back has desynced.
+    pub fn run_frame(&mut self, frontend: &mut impl Frontend) -> io::Result<()> {
+        if let Some(MovieMode::Playing(movie)) = &mut self.movie {
+            let mut events = Vec::new();
+            while let Some(event) = movie.next_event(self.frame) {
+                events.push(event);
+            }
+            for event in events {
+                self.apply(event);
+            }
+        }
+
+        self.observer.frame(self.frame);
+        self.cycle_budget += self.cycles_per_frame;
+        let cycles = self.cycle_budget.floor();
+        self.cycle_budget -= cycles;
+        match &mut self.backend {
+            Some(backend) => backend.run(&mut self.chip8, cycles as u64),
+            None => self.chip8.run_with(cycles as u64, &mut self.observer),
+        }
+        self.timer_budget += self.timer_ticks_per_frame;
+        while self.timer_budget >= 1.0 {
+            self.chip8.tick_timers();
+            self.timer_budget -= 1.0;
+        }
+        self.frame += 1;
+
+        match &mut self.movie {
+            Some(MovieMode::Recording(recorder)) => {
+                let interval = recorder.hash_interval();
+                if interval > 0 && self.frame.is_multiple_of(interval) {
+                    recorder.record(self.frame, MovieEvent::Hash(self.chip8.state_hash()));
+                }
+            }
+            Some(MovieMode::Playing(movie)) => {
+                if let Some(expected) = movie.next_hash(self.frame) {
+                    let actual = self.chip8.state_hash();
+                    if actual != expected {
+                        self.movie = None;
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "movie desynced at frame {}: expected state {:016x}, got {:016x}",
+                                self.frame, expected, actual
+                            ),
+                        ));
+                    }
+                }
+                if movie.is_finished(self.frame) {
+                    self.movie = None;
+                }
+            }
+            None => (),
+        }
+
+        frontend.frame_finished(&self.chip8, self.frame);
+        Ok(())
+    }
+
+    /// Runs every frame that is due by the frontend's clock and presents the result.
+    pub fn update(&mut self, frontend: &mut impl Frontend) -> io::Result<()> {
+        while let Some(event) = self.gamepad.as_mut().and_then(Gamepad::poll) {
+            self.handle_input(event);
+        }
+
+        let now = frontend.now();
+        let mut next_frame = *self.next_frame.get_or_insert(now);
+        let frame_duration = if self.fast_forward {
+            self.frame_duration / FAST_FORWARD_SPEED
+        } else {
+            self.frame_duration
+        };
+
+        let mut frames_run = 0;
+        if self.paused {
+            // Keep the schedule moving so resuming does not catch up on the pause
+            next_frame = now + frame_duration;
+        }
+        while next_frame <= now {
+            if frames_run == MAX_FRAMES_PER_UPDATE {
+                // Too far behind, drop the backlog instead of spiralling
+                next_frame = now + frame_duration;
+                break;
+            }
+            next_frame += frame_duration;
+            frames_run += 1;
+            self.run_frame(frontend)?;
+        }
+        self.next_frame = Some(next_frame);
+
+        if self.paused != self.paused_reported {
+            self.paused_reported = self.paused;
+            frontend.set_paused(self.paused);
+        }
+
+        let buzzer = self.chip8.sound_timer() > 0 && !self.paused;
+        if buzzer != self.buzzer {
+            self.buzzer = buzzer;
+            frontend.set_buzzer(buzzer);
+        }
+
+        if frames_run > 0 || self.redraw {
+            let dirty_rows = if std::mem::take(&mut self.redraw) {
+                u32::MAX
+            } else {
+                self.chip8.dirty_rows
+            };
+            self.chip8.dirty_rows = 0;
+            frontend.present(&self.chip8, dirty_rows)?;
+        }
+        Ok(())
+    }
+
+    /// Runs until the frontend sends [`InputEvent::Quit`], for frontends that are polled
+    /// for input rather than delivering it through callbacks.
+    pub fn run(&mut self, frontend: &mut impl Frontend) -> io::Result<()> {
+        loop {
+            let deadline = self.next_frame.unwrap_or_else(|| frontend.now());
+            while let Some(event) = frontend.poll_input(deadline)? {
+                if !self.handle_input(event) {
+                    return Ok(());
+                }
+            }
+            self.update(frontend)?;
+        }
+    }
+}
+
+/// A frontend without any display or input that runs a fixed number of frames as fast as
+/// possible.
+pub struct Headless {
+    clock: Instant,
+    frames: u64,
+    frame: u64,
+}
+
+impl Headless {
+    pub fn new(frames: u64) -> Self {
+        Self {
+            clock: Instant::now(),
+            frames,
+            frame: 0,
+        }
+    }
+}
+
+impl Frontend for Headless {
+    fn present(&mut self, _chip8: &Chip8, _dirty_rows: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn poll_input(&mut self, deadline: Instant) -> io::Result<Option<InputEvent>> {
+        if self.frame >= self.frames {
+            return Ok(Some(InputEvent::Quit));
+        }
+        // Time only passes when the emulator asks for it
+        self.clock = self.clock.max(deadline);
+        Ok(None)
+    }
+
+    fn now(&self) -> Instant {
+        self.clock
+    }
+
+    fn frame_finished(&mut self, _chip8: &Chip8, frame: u64) {
+        self.frame = frame;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::movie::MovieHeader;
+
+    const ROM: &[u8] = include_bytes!("../roms/breakout.ch8");
+
+    fn movie_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chippers-{}-{}.txt", std::process::id(), name))
+    }
+
+    /// Plays breakout for 300 frames with the paddle moving both ways, recording a movie
+    /// to `path`. Returns the final state hash.
+    fn record(path: &PathBuf) -> u64 {
+        let mut chip8 = Chip8::with_seed(42);
+        chip8.load_bytes(ROM);
+        let header = MovieHeader::new(ROM, 42, &chip8, 60, 700, 10);
+        let mut emulator = Emulator::new(chip8, 60, 700);
+        emulator.record_movie(MovieRecorder::create(path, &header).unwrap());
+        let mut frontend = Headless::new(300);
+        for frame in 0..300 {
+            match frame {
+                30 => emulator.press_key(0x4),
+                90 => emulator.release_key(0x4),
+                100 => emulator.press_key(0x6),
+                250 => emulator.release_key(0x6),
+                _ => (),
+            }
+            emulator.run_frame(&mut frontend).unwrap();
+        }
+        emulator.finish_movie().unwrap();
+        emulator.chip8().state_hash()
+    }
+
+    /// Plays `movie` back to its end.
+    fn replay(movie: Movie) -> io::Result<Emulator> {
+        let chip8 = movie.header.chip8(ROM)?;
+        let mut emulator = Emulator::new(chip8, 60, 700);
+        emulator.play_movie(movie);
+        let mut frontend = Headless::new(300);
+        while emulator.is_playing_movie() {
+            emulator.run_frame(&mut frontend)?;
+        }
+        Ok(emulator)
+    }
+
+    #[test]
+    fn movies_replay_to_the_recorded_state() {
+        let path = movie_path("replay");
+        let recorded = record(&path);
+        let movie = Movie::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let movie = movie.unwrap();
+        let emulator = replay(movie.clone()).unwrap();
+        assert_eq!(emulator.frame(), 300);
+        assert_eq!(emulator.chip8().state_hash(), recorded);
+
+        // Without its input the same movie ends somewhere else
+        let mut emulator = Emulator::new(movie.header.chip8(ROM).unwrap(), 60, 700);
+        let mut frontend = Headless::new(300);
+        for _ in 0..300 {
+            emulator.run_frame(&mut frontend).unwrap();
+        }
+        assert_ne!(emulator.chip8().state_hash(), recorded);
+    }
+
+    #[test]
+    fn tampered_movies_desync() {
+        let path = movie_path("tampered");
+        record(&path);
+        let text = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let text = text.unwrap();
+        let line = text
+            .lines()
+            .find(|line| line.starts_with("150 hash "))
+            .unwrap();
+        let tampered = text.replace(line, "150 hash 0123456789abcdef");
+        let err = replay(Movie::read(tampered.as_bytes()).unwrap())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string().starts_with("movie desynced at frame 150:"),
+            "{}",
+            err
+        );
+    }
+}
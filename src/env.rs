@@ -0,0 +1,574 @@
+//! A reinforcement-learning environment around [`Chip8`], in the style of Gym.
+//!
+//! An episode starts from power-on with a seed, and each step holds down a combination of
+//! keys for a few frames. Rewards and the end of an episode come from numbers the ROM keeps
+//! in its registers or memory, such as a score stored as BCD digits, described by an
+//! [`EnvSpec`] for each ROM.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::chip8::{Chip8, HEIGHT, WIDTH};
+use crate::keypad::KeyWait;
+
+/// Pixels of an observation: the framebuffer, one byte of 0 or 1 per pixel, row by row.
+pub const OBSERVATION_LEN: usize = (WIDTH * HEIGHT) as usize;
+
+/// A number a ROM keeps in a register or in memory.
+///
+/// Parsed from `v5` for a register, `2F0` for a byte, `2F0:word` for a big-endian 16-bit
+/// number and `2F0:bcd3` for three decimal digits stored one per byte, most significant
+/// first, as Fx33 writes them. Addresses are hexadecimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Register(u8),
+    Byte(u16),
+    Word(u16),
+    Bcd { address: u16, digits: u8 },
+}
+
+impl Value {
+    pub fn read(&self, chip8: &Chip8) -> u32 {
+        let memory = chip8.memory();
+        let byte = |address: u16| memory[address as usize & 0xFFF] as u32;
+        match *self {
+            Value::Register(x) => chip8.v()[x as usize & 0xF] as u32,
+            Value::Byte(address) => byte(address),
+            Value::Word(address) => byte(address) << 8 | byte(address.wrapping_add(1)),
+            Value::Bcd { address, digits } => (0..digits as u16).fold(0, |number, digit| {
+                number * 10 + byte(address.wrapping_add(digit)) % 10
+            }),
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(register) = s.strip_prefix(['v', 'V']) {
+            return match u8::from_str_radix(register, 16) {
+                Ok(x) if x < 16 => Ok(Value::Register(x)),
+                _ => Err(format!("unknown register '{}', expected v0 to vF", s)),
+            };
+        }
+        let (address, kind) = s.split_once(':').unwrap_or((s, "byte"));
+        let address = parse_address(address)?;
+        match kind.to_ascii_lowercase().as_str() {
+            "byte" => Ok(Value::Byte(address)),
+            "word" => Ok(Value::Word(address)),
+            bcd if bcd.starts_with("bcd") => match bcd[3..].parse() {
+                Ok(digits @ 1..=9) => Ok(Value::Bcd { address, digits }),
+                _ => Err(format!(
+                    "bad digit count in '{}', expected bcd1 to bcd9",
+                    kind
+                )),
+            },
+            _ => Err(format!(
+                "unknown kind of value '{}', expected byte, word or bcdN",
+                kind
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Value::Register(x) => write!(f, "v{:X}", x),
+            Value::Byte(address) => write!(f, "{:03X}", address),
+            Value::Word(address) => write!(f, "{:03X}:word", address),
+            Value::Bcd { address, digits } => write!(f, "{:03X}:bcd{}", address, digits),
+        }
+    }
+}
+
+fn parse_address(s: &str) -> Result<u16, String> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    match u16::from_str_radix(digits, 16) {
+        Ok(address) if address < 0x1000 => Ok(address),
+        _ => Err(format!("bad address '{}', expected 000 to FFF", s)),
+    }
+}
+
+/// How a [`Condition`] compares a value with its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// Operators in the order they are looked for, longest first.
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Eq),
+        ("!=", Comparison::Ne),
+        ("<=", Comparison::Le),
+        (">=", Comparison::Ge),
+        ("<", Comparison::Lt),
+        (">", Comparison::Gt),
+    ];
+
+    fn operator(self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|&&(_, comparison)| comparison == self)
+            .unwrap()
+            .0
+    }
+}
+
+/// A test of a value against a number, such as `v7==0` for running out of lives. Parsed
+/// from a [`Value`], one of `==`, `!=`, `<`, `<=`, `>`, `>=` and a decimal number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub value: Value,
+    pub comparison: Comparison,
+    pub target: u32,
+}
+
+impl Condition {
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let value = self.value.read(chip8);
+        match self.comparison {
+            Comparison::Eq => value == self.target,
+            Comparison::Ne => value != self.target,
+            Comparison::Lt => value < self.target,
+            Comparison::Le => value <= self.target,
+            Comparison::Gt => value > self.target,
+            Comparison::Ge => value >= self.target,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (at, operator, comparison) = Comparison::OPERATORS
+            .iter()
+            .find_map(|&(operator, comparison)| {
+                s.find(operator).map(|at| (at, operator, comparison))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "no comparison in '{}', expected one of ==, !=, <, <=, >, >=",
+                    s
+                )
+            })?;
+        let target = &s[at + operator.len()..];
+        Ok(Self {
+            value: s[..at].parse()?,
+            comparison,
+            target: target
+                .trim()
+                .parse()
+                .map_err(|_| format!("bad number '{}' in '{}'", target.trim(), s))?,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.value,
+            self.comparison.operator(),
+            self.target
+        )
+    }
+}
+
+/// What an environment runs and how it scores it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvSpec {
+    /// The value whose increase in a step is the reward of the step, if any.
+    pub score: Option<Value>,
+    /// When an episode is over, if ever.
+    pub done: Option<Condition>,
+    /// Steps after which an episode is over anyway.
+    pub max_steps: Option<u64>,
+    /// Frames a step holds its keys for.
+    pub frame_skip: u32,
+    pub cycles_per_frame: u32,
+    pub key_wait: KeyWait,
+    /// Key combinations worth trying, as bitmasks with key 0 in the lowest bit. Agents with
+    /// a discrete action space pick one of them.
+    pub actions: Vec<u16>,
+}
+
+impl Default for EnvSpec {
+    /// No reward, episodes that never end, 4 frames per step at 13 cycles a frame, and the
+    /// actions of holding no key or any single key.
+    fn default() -> Self {
+        Self {
+            score: None,
+            done: None,
+            max_steps: None,
+            frame_skip: 4,
+            cycles_per_frame: 13,
+            key_wait: KeyWait::default(),
+            actions: core::iter::once(0)
+                .chain((0..16).map(|key| 1 << key))
+                .collect(),
+        }
+    }
+}
+
+/// A ROM as a reinforcement-learning environment.
+///
+/// Everything is determined by the seed of [`Chip8Env::reset`] and the actions taken, so
+/// episodes can be replayed exactly. ROMs that run into unknown instructions panic, like
+/// they do in the interpreter.
+#[derive(Clone)]
+pub struct Chip8Env {
+    rom: Vec<u8>,
+    spec: EnvSpec,
+    chip8: Chip8,
+    score: u32,
+    steps: u64,
+}
+
+impl Chip8Env {
+    /// Creates the environment for `rom`, reset with seed 0.
+    pub fn new(rom: &[u8], spec: EnvSpec) -> Self {
+        let mut env = Self {
+            rom: rom.to_vec(),
+            spec,
+            chip8: Chip8::with_seed(0),
+            score: 0,
+            steps: 0,
+        };
+        env.reset(0);
+        env
+    }
+
+    pub fn spec(&self) -> &EnvSpec {
+        &self.spec
+    }
+
+    /// The machine, for reading anything the observation leaves out.
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    /// Steps taken in the current episode.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn observation(&self) -> &[u8; OBSERVATION_LEN] {
+        &self.chip8.gfx
+    }
+
+    /// Starts a new episode from power-on, with random numbers drawn from `seed`.
+    pub fn reset(&mut self, seed: u64) -> &[u8; OBSERVATION_LEN] {
+        self.chip8 = Chip8::with_seed(seed);
+        self.chip8.load_bytes(&self.rom);
+        self.chip8.set_key_wait(self.spec.key_wait);
+        self.score = self.read_score();
+        self.steps = 0;
+        self.observation()
+    }
+
+    /// Holds down the keys set in `keys`, key 0 in the lowest bit, and releases all others
+    /// for `frame_skip` frames. Returns the framebuffer after them, the increase of the
+    /// score and whether the episode is over.
+    pub fn step(&mut self, keys: u16) -> (&[u8; OBSERVATION_LEN], f32, bool) {
+        self.chip8.set_keys(keys);
+        for _ in 0..self.spec.frame_skip {
+            self.chip8.run(self.spec.cycles_per_frame as u64);
+            self.chip8.tick_timers();
+        }
+        self.steps += 1;
+
+        let score = self.read_score();
+        let reward = score as f32 - self.score as f32;
+        self.score = score;
+        let done = self.spec.done.is_some_and(|done| done.holds(&self.chip8))
+            || self.spec.max_steps.is_some_and(|max| self.steps >= max);
+        (self.observation(), reward, done)
+    }
+
+    fn read_score(&self) -> u32 {
+        self.spec.score.map_or(0, |score| score.read(&self.chip8))
+    }
+}
+
+/// A batch of environments for the same ROM, stepped together.
+///
+/// Observations, rewards and done flags are kept in flat buffers in the order of the
+/// environments, ready to copy into tensors. An environment whose episode ends is reset
+/// right away with the next unused seed, so the observation it returns with `done` set is
+/// the first of its next episode.
+pub struct VecEnv {
+    envs: Vec<Chip8Env>,
+    next_seed: u64,
+    observations: Vec<u8>,
+    rewards: Vec<f32>,
+    dones: Vec<bool>,
+}
+
+impl VecEnv {
+    /// Creates `count` environments for `rom`, reset with seeds 0 and up.
+    pub fn new(rom: &[u8], spec: EnvSpec, count: usize) -> Self {
+        let env = Chip8Env::new(rom, spec);
+        let mut envs = Self {
+            envs: alloc::vec![env; count],
+            next_seed: 0,
+            observations: alloc::vec![0; count * OBSERVATION_LEN],
+            rewards: alloc::vec![0.0; count],
+            dones: alloc::vec![false; count],
+        };
+        envs.reset(0);
+        envs
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn envs(&self) -> &[Chip8Env] {
+        &self.envs
+    }
+
+    /// Resets every environment, the first with `seed` and the others with the seeds
+    /// following it. Returns the observations.
+    pub fn reset(&mut self, seed: u64) -> &[u8] {
+        self.next_seed = seed;
+        for (env, observation) in self
+            .envs
+            .iter_mut()
+            .zip(self.observations.chunks_exact_mut(OBSERVATION_LEN))
+        {
+            observation.copy_from_slice(env.reset(self.next_seed));
+            self.next_seed += 1;
+        }
+        &self.observations
+    }
+
+    /// Steps every environment with the keys of its entry in `actions`. Returns the
+    /// observations, rewards and done flags.
+    ///
+    /// # Panics
+    ///
+    /// If `actions` does not have an entry for every environment.
+    pub fn step(&mut self, actions: &[u16]) -> (&[u8], &[f32], &[bool]) {
+        assert_eq!(
+            actions.len(),
+            self.envs.len(),
+            "one action is needed per environment"
+        );
+        for (index, (env, &keys)) in self.envs.iter_mut().zip(actions).enumerate() {
+            let (observation, reward, done) = env.step(keys);
+            let observation = if done {
+                self.next_seed += 1;
+                env.reset(self.next_seed - 1)
+            } else {
+                observation
+            };
+            self.observations[index * OBSERVATION_LEN..][..OBSERVATION_LEN]
+                .copy_from_slice(observation);
+            self.rewards[index] = reward;
+            self.dones[index] = done;
+        }
+        (&self.observations, &self.rewards, &self.dones)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::test_util::assemble;
+
+    // 200: ADD V1, 1
+    // 202: LD I, 2F0
+    // 204: LD B, V1
+    // 206: RND V0, 3F
+    // 208: RND V2, 1F
+    // 20A: LD I, 200
+    // 20C: DRW V0, V2, 5
+    // 20E: JP 200
+    const ROM: [u16; 8] = [
+        0x7101, 0xA2F0, 0xF133, 0xC03F, 0xC21F, 0xA200, 0xD025, 0x1200,
+    ];
+
+    /// An environment for [`ROM`] that runs its loop once a frame, with the number of
+    /// frames run as the BCD score at 2F0.
+    fn new_env(spec: EnvSpec) -> Chip8Env {
+        Chip8Env::new(&assemble(&ROM), spec)
+    }
+
+    fn spec() -> EnvSpec {
+        EnvSpec {
+            score: Some("2F0:bcd3".parse().unwrap()),
+            frame_skip: 1,
+            cycles_per_frame: ROM.len() as u32,
+            ..EnvSpec::default()
+        }
+    }
+
+    fn observations(env: &mut Chip8Env, seed: u64) -> Vec<[u8; OBSERVATION_LEN]> {
+        env.reset(seed);
+        (0..20).map(|step| *env.step(1 << (step % 16)).0).collect()
+    }
+
+    #[test]
+    fn episodes_replay_from_their_seed() {
+        let mut env = new_env(spec());
+        let first = observations(&mut env, 7);
+        assert_eq!(observations(&mut env, 7), first);
+        assert_eq!(observations(&mut env.clone(), 7), first);
+        assert_ne!(observations(&mut env, 8), first);
+    }
+
+    #[test]
+    fn rewards_are_increases_of_the_score() {
+        let mut env = new_env(EnvSpec {
+            frame_skip: 3,
+            ..spec()
+        });
+        for step in 1..=10 {
+            let (_, reward, done) = env.step(0);
+            assert_eq!(reward, 3.0);
+            assert!(!done);
+            assert_eq!(env.steps(), step);
+        }
+        // The BCD digits of 30
+        assert_eq!(env.chip8().memory()[0x2F0..0x2F3], [0, 3, 0]);
+        assert_eq!(Value::from_str("2F0:bcd3").unwrap().read(env.chip8()), 30);
+        assert_eq!(Value::from_str("2F1:bcd2").unwrap().read(env.chip8()), 30);
+        assert_eq!(Value::from_str("v1").unwrap().read(env.chip8()), 30);
+        env.reset(0);
+        assert_eq!(env.step(0).1, 3.0);
+    }
+
+    #[test]
+    fn episodes_end_on_their_condition_or_after_max_steps() {
+        let mut env = new_env(EnvSpec {
+            done: Some("v1>=3".parse().unwrap()),
+            ..spec()
+        });
+        let dones: Vec<bool> = (0..4).map(|_| env.step(0).2).collect();
+        assert_eq!(dones, [false, false, true, true]);
+
+        let mut env = new_env(EnvSpec {
+            max_steps: Some(2),
+            ..spec()
+        });
+        let dones: Vec<bool> = (0..3).map(|_| env.step(0).2).collect();
+        assert_eq!(dones, [false, true, true]);
+        env.reset(1);
+        assert!(!env.step(0).2);
+    }
+
+    #[test]
+    fn steps_without_frames_change_nothing() {
+        let mut env = new_env(EnvSpec {
+            frame_skip: 0,
+            ..spec()
+        });
+        let hash = env.chip8().state_hash();
+        let (observation, reward, done) = env.step(0);
+        assert_eq!(*observation, [0; OBSERVATION_LEN]);
+        assert_eq!((reward, done), (0.0, false));
+        assert_eq!(env.steps(), 1);
+        assert_eq!(env.chip8().state_hash(), hash);
+    }
+
+    #[test]
+    fn vec_envs_reset_finished_episodes_with_the_next_seed() {
+        let spec = EnvSpec {
+            done: Some("v1==2".parse().unwrap()),
+            ..spec()
+        };
+        let mut envs = VecEnv::new(&assemble(&ROM), spec.clone(), 2);
+        assert_eq!(envs.len(), 2);
+
+        let (_, rewards, dones) = envs.step(&[0, 0]);
+        assert_eq!((rewards, dones), (&[1.0, 1.0][..], &[false, false][..]));
+        let (observations, rewards, dones) = envs.step(&[0, 0]);
+        assert_eq!((rewards, dones), (&[1.0, 1.0][..], &[true, true][..]));
+        // The observations are the first of the next episodes
+        assert!(observations.iter().all(|&pixel| pixel == 0));
+        assert_eq!(envs.envs()[0].steps(), 0);
+
+        // Seeds 0 and 1 went to the first episodes, so 2 and 3 come next
+        let (observations, _, _) = envs.step(&[0, 0]);
+        for (seed, observation) in (2..).zip(observations.chunks_exact(OBSERVATION_LEN)) {
+            let mut env = new_env(spec.clone());
+            env.reset(seed);
+            assert_eq!(observation, env.step(0).0);
+        }
+    }
+
+    #[test]
+    fn values_and_conditions_parse() {
+        assert_eq!("vA".parse(), Ok(Value::Register(0xA)));
+        assert_eq!("0x2f0".parse(), Ok(Value::Byte(0x2F0)));
+        assert_eq!("2F0:WORD".parse(), Ok(Value::Word(0x2F0)));
+        assert_eq!(
+            "2F0:bcd3".parse(),
+            Ok(Value::Bcd {
+                address: 0x2F0,
+                digits: 3
+            })
+        );
+        for value in ["vF", "2F0", "2F0:word", "2F0:bcd3"] {
+            assert_eq!(value.parse::<Value>().unwrap().to_string(), value);
+        }
+        assert_eq!(
+            "v7 <= 300".parse(),
+            Ok(Condition {
+                value: Value::Register(7),
+                comparison: Comparison::Le,
+                target: 300
+            })
+        );
+        assert_eq!("v7!=0".parse::<Condition>().unwrap().to_string(), "v7!=0");
+    }
+
+    #[test]
+    fn malformed_values_and_conditions_are_rejected() {
+        let value = |s: &str| s.parse::<Value>().unwrap_err();
+        assert_eq!(value("v16"), "unknown register 'v16', expected v0 to vF");
+        assert_eq!(value("1000"), "bad address '1000', expected 000 to FFF");
+        assert_eq!(value("2G0"), "bad address '2G0', expected 000 to FFF");
+        assert_eq!(
+            value("2F0:bcd0"),
+            "bad digit count in 'bcd0', expected bcd1 to bcd9"
+        );
+        assert_eq!(
+            value("2F0:bcd"),
+            "bad digit count in 'bcd', expected bcd1 to bcd9"
+        );
+        assert_eq!(
+            value("2F0:long"),
+            "unknown kind of value 'long', expected byte, word or bcdN"
+        );
+
+        let condition = |s: &str| s.parse::<Condition>().unwrap_err();
+        assert_eq!(
+            condition("v7 0"),
+            "no comparison in 'v7 0', expected one of ==, !=, <, <=, >, >="
+        );
+        assert_eq!(condition("v16==0"), value("v16"));
+        assert_eq!(condition("v7==x"), "bad number 'x' in 'v7==x'");
+        assert_eq!(condition("v7==-1"), "bad number '-1' in 'v7==-1'");
+    }
+}
@@ -1,3 +1,7 @@
+mod renderer;
+
+use std::io;
+use std::path::PathBuf;
 use std::time::Instant;
 
 use pixels::{Pixels, SurfaceTexture};
@@ -5,174 +9,416 @@ use winit::application::ApplicationHandler;
 use winit::dpi::LogicalSize;
 use winit::event::{ElementState, KeyEvent, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
-use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::window::{Window, WindowId};
-
-use crate::chip8::Chip8;
-
-const WIDTH: u32 = 64;
-const HEIGHT: u32 = 32;
-
-pub struct App<'a> {
-    pub window: Option<Window>,
-    pub pixels: Option<Pixels>,
-    pub chip8: &'a mut Chip8,
-    scale: u32,
-    last_frame_instant: Instant,
-    last_draw_instant: Instant,
-    frame_rate: f64,
-    cycle_rate: f64,
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+use winit::window::{Fullscreen, Window, WindowId};
+
+use crate::capture::{self, Recorder};
+use crate::chip8::{Chip8, Observer, HEIGHT, WIDTH};
+use crate::emulator::{Emulator, Frontend, InputEvent};
+use crate::keymap::{Action, Hotkey, Keymap};
+use crate::render::{self, Filter, Palette};
+
+use renderer::UpscaleRenderer;
+
+pub struct Options {
+    pub scale: u32,
+    pub filter: Filter,
+    pub integer_scaling: bool,
+    /// Frame number and path of a screenshot to take automatically.
+    pub screenshot_at: Option<(u64, PathBuf)>,
+    /// Start recording to this file right away.
+    pub record: Option<PathBuf>,
+    pub keymap: Keymap,
+}
+
+pub struct App<O = ()> {
+    emulator: Emulator<O>,
+    host: WindowHost,
+    modifiers: ModifiersState,
+    error: Option<io::Error>,
+}
+
+/// The window and everything drawn or captured from it.
+struct WindowHost {
+    window: Option<Window>,
+    pixels: Option<Pixels>,
+    renderer: Option<UpscaleRenderer>,
+    recorder: Option<Recorder>,
+    options: Options,
+    palette: Palette,
+    frames_per_second: u32,
 }
 
-impl<'a> App<'a> {
-    pub fn new(
-        chip8: &'a mut Chip8,
-        scale: u32,
-        frames_per_second: u32,
-        cycles_per_second: u32,
-    ) -> Self {
+impl<O: Observer> App<O> {
+    pub fn new(emulator: Emulator<O>, options: Options) -> Self {
+        let frames_per_second = emulator.frames_per_second();
         Self {
-            window: None,
-            pixels: None,
-            chip8,
-            scale,
-            last_frame_instant: Instant::now(),
-            last_draw_instant: Instant::now(),
-            frame_rate: 1.0 / frames_per_second as f64,
-            cycle_rate: 1.0 / cycles_per_second as f64,
+            emulator,
+            host: WindowHost {
+                window: None,
+                pixels: None,
+                renderer: None,
+                recorder: None,
+                options,
+                palette: Palette::default(),
+                frames_per_second,
+            },
+            modifiers: ModifiersState::empty(),
+            error: None,
+        }
+    }
+
+    /// The error that stopped emulation, such as a desynced movie.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_emulator(self) -> Emulator<O> {
+        self.emulator
+    }
+}
+
+/// The [`Keymap`] name of a physical key.
+pub fn key_name(code: KeyCode) -> Option<&'static str> {
+    let name = match code {
+        KeyCode::Digit0 => "0",
+        KeyCode::Digit1 => "1",
+        KeyCode::Digit2 => "2",
+        KeyCode::Digit3 => "3",
+        KeyCode::Digit4 => "4",
+        KeyCode::Digit5 => "5",
+        KeyCode::Digit6 => "6",
+        KeyCode::Digit7 => "7",
+        KeyCode::Digit8 => "8",
+        KeyCode::Digit9 => "9",
+        KeyCode::KeyA => "A",
+        KeyCode::KeyB => "B",
+        KeyCode::KeyC => "C",
+        KeyCode::KeyD => "D",
+        KeyCode::KeyE => "E",
+        KeyCode::KeyF => "F",
+        KeyCode::KeyG => "G",
+        KeyCode::KeyH => "H",
+        KeyCode::KeyI => "I",
+        KeyCode::KeyJ => "J",
+        KeyCode::KeyK => "K",
+        KeyCode::KeyL => "L",
+        KeyCode::KeyM => "M",
+        KeyCode::KeyN => "N",
+        KeyCode::KeyO => "O",
+        KeyCode::KeyP => "P",
+        KeyCode::KeyQ => "Q",
+        KeyCode::KeyR => "R",
+        KeyCode::KeyS => "S",
+        KeyCode::KeyT => "T",
+        KeyCode::KeyU => "U",
+        KeyCode::KeyV => "V",
+        KeyCode::KeyW => "W",
+        KeyCode::KeyX => "X",
+        KeyCode::KeyY => "Y",
+        KeyCode::KeyZ => "Z",
+        KeyCode::Numpad0 => "Numpad0",
+        KeyCode::Numpad1 => "Numpad1",
+        KeyCode::Numpad2 => "Numpad2",
+        KeyCode::Numpad3 => "Numpad3",
+        KeyCode::Numpad4 => "Numpad4",
+        KeyCode::Numpad5 => "Numpad5",
+        KeyCode::Numpad6 => "Numpad6",
+        KeyCode::Numpad7 => "Numpad7",
+        KeyCode::Numpad8 => "Numpad8",
+        KeyCode::Numpad9 => "Numpad9",
+        KeyCode::NumpadAdd => "NumpadAdd",
+        KeyCode::NumpadSubtract => "NumpadSubtract",
+        KeyCode::NumpadMultiply => "NumpadMultiply",
+        KeyCode::NumpadDivide => "NumpadDivide",
+        KeyCode::NumpadDecimal => "NumpadDecimal",
+        KeyCode::NumpadEnter => "NumpadEnter",
+        KeyCode::F1 => "F1",
+        KeyCode::F2 => "F2",
+        KeyCode::F3 => "F3",
+        KeyCode::F4 => "F4",
+        KeyCode::F5 => "F5",
+        KeyCode::F6 => "F6",
+        KeyCode::F7 => "F7",
+        KeyCode::F8 => "F8",
+        KeyCode::F9 => "F9",
+        KeyCode::F10 => "F10",
+        KeyCode::F11 => "F11",
+        KeyCode::F12 => "F12",
+        KeyCode::ArrowUp => "Up",
+        KeyCode::ArrowDown => "Down",
+        KeyCode::ArrowLeft => "Left",
+        KeyCode::ArrowRight => "Right",
+        KeyCode::Space => "Space",
+        KeyCode::Enter => "Enter",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Escape => "Escape",
+        KeyCode::Insert => "Insert",
+        KeyCode::Delete => "Delete",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        KeyCode::Minus => "Minus",
+        KeyCode::Equal => "Equal",
+        KeyCode::Comma => "Comma",
+        KeyCode::Period => "Period",
+        KeyCode::Slash => "Slash",
+        KeyCode::Semicolon => "Semicolon",
+        KeyCode::Quote => "Quote",
+        KeyCode::BracketLeft => "BracketLeft",
+        KeyCode::BracketRight => "BracketRight",
+        KeyCode::Backslash => "Backslash",
+        KeyCode::Backquote => "Backquote",
+        KeyCode::ShiftLeft => "ShiftLeft",
+        KeyCode::ShiftRight => "ShiftRight",
+        KeyCode::ControlLeft => "ControlLeft",
+        KeyCode::ControlRight => "ControlRight",
+        KeyCode::AltLeft => "AltLeft",
+        KeyCode::AltRight => "AltRight",
+        _ => return None,
+    };
+    Some(name)
+}
+
+impl WindowHost {
+    fn save_screenshot(&self, chip8: &Chip8, path: PathBuf, native: bool) {
+        let (scale, filter) = if native {
+            (1, Filter::None)
+        } else {
+            (self.options.scale, self.options.filter)
+        };
+        match capture::save_png(&path, &chip8.gfx, scale, &self.palette, filter) {
+            Ok(()) => println!("Saved screenshot to {}", path.display()),
+            Err(err) => eprintln!("Could not save screenshot to {}: {}", path.display(), err),
+        }
+    }
+
+    fn start_recording(&mut self, path: PathBuf) {
+        match Recorder::create(
+            &path,
+            self.options.scale,
+            &self.palette,
+            self.frames_per_second,
+        ) {
+            Ok(recorder) => {
+                println!("Recording to {}", path.display());
+                self.recorder = Some(recorder);
+            }
+            Err(err) => eprintln!("Could not record to {}: {}", path.display(), err),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            match recorder.finish() {
+                Ok(()) => println!("Recording stopped"),
+                Err(err) => eprintln!("Could not finish recording: {}", err),
+            }
+        }
+    }
+}
+
+impl Frontend for WindowHost {
+    fn present(&mut self, chip8: &Chip8, dirty_rows: u32) -> io::Result<()> {
+        if dirty_rows == 0 {
+            return Ok(());
+        }
+        let pixels = self.pixels.as_mut().unwrap();
+        let renderer = self.renderer.as_ref().unwrap();
+
+        render::rasterize_rows(&chip8.gfx, dirty_rows, &self.palette, pixels.frame_mut());
+        pixels
+            .render_with(|encoder, render_target, _context| {
+                renderer.render(encoder, render_target);
+                Ok(())
+            })
+            .map_err(io::Error::other)
+    }
+
+    fn poll_input(&mut self, _deadline: Instant) -> io::Result<Option<InputEvent>> {
+        // Input arrives through winit's window events instead
+        Ok(None)
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        let title = if paused { "Chip8 (paused)" } else { "Chip8" };
+        self.window.as_ref().unwrap().set_title(title);
+    }
+
+    fn frame_finished(&mut self, chip8: &Chip8, frame: u64) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.record_frame(&chip8.gfx) {
+                eprintln!("Recording failed, stopping: {}", err);
+                self.recorder = None;
+            }
+        }
+
+        if let Some((screenshot_frame, path)) = &self.options.screenshot_at {
+            if *screenshot_frame == frame {
+                self.save_screenshot(chip8, path.clone(), false);
+            }
         }
     }
+}
 
-    pub fn keymap(&mut self, code: KeyCode) -> Option<u8> {
-        match code {
-            KeyCode::Digit1 => Some(0x1),
-            KeyCode::Digit2 => Some(0x2),
-            KeyCode::Digit3 => Some(0x3),
-            KeyCode::Digit4 => Some(0xC),
-            KeyCode::KeyQ => Some(0x4),
-            KeyCode::KeyW => Some(0x5),
-            KeyCode::KeyE => Some(0x6),
-            KeyCode::KeyR => Some(0xD),
-            KeyCode::KeyA => Some(0x7),
-            KeyCode::KeyS => Some(0x8),
-            KeyCode::KeyD => Some(0x9),
-            KeyCode::KeyF => Some(0xE),
-            KeyCode::KeyZ => Some(0xA),
-            KeyCode::KeyX => Some(0x0),
-            KeyCode::KeyC => Some(0xB),
-            KeyCode::KeyV => Some(0xF),
-            _ => None,
+impl<O: Observer> App<O> {
+    fn hotkey(&mut self, event_loop: &ActiveEventLoop, hotkey: Hotkey) {
+        match hotkey {
+            Hotkey::Quit => event_loop.exit(),
+            Hotkey::Pause => self.emulator.set_paused(!self.emulator.is_paused()),
+            Hotkey::Reset => self.emulator.reset(),
+            Hotkey::SaveState => {
+                self.emulator.save_state();
+                println!("State saved");
+            }
+            Hotkey::LoadState => {
+                if !self.emulator.load_state() {
+                    eprintln!("Could not load state: nothing saved yet, or a movie is running");
+                }
+            }
+            Hotkey::FastForward => (),
+            Hotkey::Record => {
+                if self.host.recorder.is_some() {
+                    self.host.stop_recording();
+                } else {
+                    let path = PathBuf::from(format!("recording-{:06}.gif", self.emulator.frame()));
+                    self.host.start_recording(path);
+                }
+            }
+            Hotkey::Fullscreen => {
+                let window = self.host.window.as_ref().unwrap();
+                if window.fullscreen().is_some() {
+                    window.set_fullscreen(None);
+                } else {
+                    window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+                }
+            }
+            Hotkey::Screenshot => {
+                let path = PathBuf::from(format!("screenshot-{:06}.png", self.emulator.frame()));
+                self.host
+                    .save_screenshot(self.emulator.chip8(), path, self.modifiers.shift_key());
+            }
         }
     }
 }
 
-impl<'a> ApplicationHandler for App<'a> {
+impl<O: Observer> ApplicationHandler for App<O> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        self.window = {
-            let size = LogicalSize::new((self.scale * WIDTH) as f64, (self.scale * HEIGHT) as f64);
+        let host = &mut self.host;
+        host.window = {
+            let size = LogicalSize::new(
+                (host.options.scale * WIDTH) as f64,
+                (host.options.scale * HEIGHT) as f64,
+            );
+            let min_size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
             Some(
                 event_loop
                     .create_window(
                         Window::default_attributes()
                             .with_title("Chip8")
                             .with_inner_size(size)
-                            .with_min_inner_size(size),
+                            .with_min_inner_size(min_size),
                     )
                     .unwrap(),
             )
         };
-        self.pixels = {
-            let window_size = self.window.as_ref().unwrap().inner_size();
+        host.pixels = {
+            let window_size = host.window.as_ref().unwrap().inner_size();
             let surface_texture = SurfaceTexture::new(
                 window_size.width,
                 window_size.height,
-                self.window.as_ref().unwrap(),
+                host.window.as_ref().unwrap(),
             );
             Some(
-                Pixels::new(self.scale * WIDTH, self.scale * HEIGHT, surface_texture)
+                Pixels::new(WIDTH, HEIGHT, surface_texture)
                     .expect("Pixel surface should be created"),
             )
         };
+        host.renderer = {
+            let window_size = host.window.as_ref().unwrap().inner_size();
+            Some(UpscaleRenderer::new(
+                host.pixels.as_ref().unwrap(),
+                window_size.width,
+                window_size.height,
+                host.options.filter,
+                host.options.integer_scaling,
+            ))
+        };
+        self.emulator.request_redraw();
+
+        if let Some(path) = host.options.record.take() {
+            host.start_recording(path);
+        }
     }
 
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, _event: ()) {}
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        let window = self.window.as_mut().unwrap();
-        let pixels = self.pixels.as_mut().unwrap();
         match event {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
                 event_loop.exit();
             }
-            WindowEvent::RedrawRequested => {
-                let time_since_last_frame = self.last_frame_instant.elapsed();
-                let time_since_last_draw = self.last_draw_instant.elapsed();
-
-                if time_since_last_frame.as_secs_f64() > self.cycle_rate {
-                    self.chip8.cycle();
-                    self.last_frame_instant = Instant::now();
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                let size = self.host.window.as_ref().unwrap().inner_size();
+                if size.width > 0 && size.height > 0 {
+                    let pixels = self.host.pixels.as_mut().unwrap();
+                    pixels
+                        .resize_surface(size.width, size.height)
+                        .expect("Pixel surface should be resized");
+                    self.host.renderer.as_mut().unwrap().resize(
+                        pixels.queue(),
+                        size.width,
+                        size.height,
+                    );
+                    self.emulator.request_redraw();
                 }
-
-                if time_since_last_draw.as_secs_f64() > self.frame_rate && self.chip8.draw_flag {
-                    self.chip8.draw_flag = false;
-                    self.last_draw_instant = Instant::now();
-
-                    let framebuf = pixels.frame_mut();
-                    for (i, pixel) in framebuf.chunks_exact_mut(4).enumerate() {
-                        let x = (i % (self.scale * WIDTH) as usize) / self.scale as usize;
-                        let y = (i / (self.scale * WIDTH) as usize) / self.scale as usize;
-
-                        let colored = self.chip8.gfx[x + y * 64] == 1;
-
-                        let rgba = if colored {
-                            [0xFF, 0xFF, 0xFF, 0xFF]
-                        } else {
-                            [0x00, 0x00, 0x00, 0x00]
-                        };
-
-                        pixel.copy_from_slice(&rgba);
-                    }
-                    pixels.render().unwrap();
+            }
+            WindowEvent::RedrawRequested => {
+                if let Err(err) = self.emulator.update(&mut self.host) {
+                    self.error = Some(err);
+                    event_loop.exit();
+                    return;
                 }
-
-                window.request_redraw();
+                self.host.window.as_ref().unwrap().request_redraw();
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
             }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
-                        physical_key,
+                        physical_key: PhysicalKey::Code(code),
                         state,
+                        repeat,
                         ..
                     },
                 ..
-            } => match physical_key {
-                PhysicalKey::Code(KeyCode::Escape) if state == ElementState::Pressed => {
-                    event_loop.exit();
-                }
-                PhysicalKey::Code(code) => {
-                    if let Some(key) = self.keymap(code) {
-                        match state {
-                            ElementState::Pressed => {
-                                self.chip8.key[key as usize] = 1;
-                                if self.chip8.await_key_flag {
-                                    self.chip8.await_key_pressed = key;
-                                }
-                            }
-                            ElementState::Released => {
-                                self.chip8.key[key as usize] = 0;
-                                if self.chip8.await_key_flag {
-                                    if key == self.chip8.await_key_pressed {
-                                        self.chip8.await_key_notify = true;
-                                    }
-                                }
-                            }
-                        }
-                    };
+            } => {
+                let Some(action) =
+                    key_name(code).and_then(|name| self.host.options.keymap.action(name))
+                else {
+                    return;
+                };
+                let pressed = state == ElementState::Pressed;
+                match action {
+                    Action::Key(key) if pressed => self.emulator.press_key(key),
+                    Action::Key(key) => self.emulator.release_key(key),
+                    Action::Hotkey(Hotkey::FastForward) => self.emulator.set_fast_forward(pressed),
+                    Action::Hotkey(hotkey) if pressed && !repeat => self.hotkey(event_loop, hotkey),
+                    Action::Hotkey(_) => (),
                 }
-                _ => (),
-            },
+            }
             _ => (),
         }
     }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.host.stop_recording();
+        if let Err(err) = self.emulator.finish_movie() {
+            eprintln!("Could not finish movie: {}", err);
+        }
+    }
 }
@@ -0,0 +1,201 @@
+use pixels::wgpu;
+use pixels::Pixels;
+
+use crate::render::Filter;
+
+/// Scales the native-resolution pixel buffer to the window on the GPU.
+///
+/// The image keeps its aspect ratio and is centered with black borders. With
+/// integer scaling enabled, only whole multiples of the native size are used.
+/// Post-processing filters are applied in the same pass.
+pub struct UpscaleRenderer {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+    texture_size: (u32, u32),
+    viewport: (f32, f32, f32, f32),
+    filter: Filter,
+    integer_scaling: bool,
+}
+
+impl UpscaleRenderer {
+    pub fn new(
+        pixels: &Pixels,
+        surface_width: u32,
+        surface_height: u32,
+        filter: Filter,
+        integer_scaling: bool,
+    ) -> Self {
+        let device = pixels.device();
+        let module = device.create_shader_module(wgpu::include_wgsl!("upscale.wgsl"));
+
+        let texture_view = pixels
+            .texture()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("chippers_upscale_sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("chippers_upscale_uniform_buffer"),
+            size: LOCALS_SIZE as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("chippers_upscale_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(LOCALS_SIZE as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("chippers_upscale_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("chippers_upscale_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("chippers_upscale_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: pixels.render_texture_format(),
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let extent = pixels.context().texture_extent;
+        let mut renderer = Self {
+            uniform_buffer,
+            bind_group,
+            render_pipeline,
+            texture_size: (extent.width, extent.height),
+            viewport: (0.0, 0.0, 0.0, 0.0),
+            filter,
+            integer_scaling,
+        };
+        renderer.resize(pixels.queue(), surface_width, surface_height);
+        renderer
+    }
+
+    /// Recomputes the letterboxed viewport for a new surface size.
+    pub fn resize(&mut self, queue: &wgpu::Queue, surface_width: u32, surface_height: u32) {
+        let (tex_w, tex_h) = (self.texture_size.0 as f32, self.texture_size.1 as f32);
+        let (surf_w, surf_h) = (surface_width.max(1) as f32, surface_height.max(1) as f32);
+
+        let mut scale = (surf_w / tex_w).min(surf_h / tex_h);
+        if self.integer_scaling {
+            scale = scale.floor().max(1.0);
+        }
+
+        let (view_w, view_h) = (tex_w * scale, tex_h * scale);
+        self.viewport = (
+            ((surf_w - view_w) / 2.0).max(0.0).floor(),
+            ((surf_h - view_h) / 2.0).max(0.0).floor(),
+            view_w.min(surf_w),
+            view_h.min(surf_h),
+        );
+
+        let mode: u32 = match self.filter {
+            Filter::None => 0,
+            Filter::Scanlines => 1,
+            Filter::Lcd => 2,
+            Filter::Crt => 3,
+        };
+        let mut locals = [0u8; LOCALS_SIZE];
+        for (chunk, value) in locals
+            .chunks_exact_mut(4)
+            .zip([tex_w, tex_h, view_w, view_h])
+        {
+            chunk.copy_from_slice(&value.to_ne_bytes());
+        }
+        locals[16..20].copy_from_slice(&mode.to_ne_bytes());
+        queue.write_buffer(&self.uniform_buffer, 0, &locals);
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, render_target: &wgpu::TextureView) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("chippers_upscale_render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: render_target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        let (x, y, w, h) = self.viewport;
+        rpass.set_pipeline(&self.render_pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_viewport(x, y, w, h, 0.0, 1.0);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+/// Size of the `Locals` uniform in `upscale.wgsl`.
+const LOCALS_SIZE: usize = 32;
@@ -0,0 +1,100 @@
+// Upscales the native-resolution framebuffer into the letterboxed viewport
+// and optionally applies one of the post-processing filters.
+
+const FILTER_NONE: u32 = 0u;
+const FILTER_SCANLINES: u32 = 1u;
+const FILTER_LCD: u32 = 2u;
+const FILTER_CRT: u32 = 3u;
+
+const CRT_CURVATURE: f32 = 0.06;
+const CRT_BLOOM: f32 = 0.18;
+
+struct Locals {
+    tex_size: vec2<f32>,
+    view_size: vec2<f32>,
+    mode: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+struct VertexOutput {
+    @location(0) tex_coord: vec2<f32>,
+    @builtin(position) position: vec4<f32>,
+}
+
+@group(0) @binding(0) var r_tex_color: texture_2d<f32>;
+@group(0) @binding(1) var r_tex_sampler: sampler;
+@group(0) @binding(2) var<uniform> r_locals: Locals;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One full-screen triangle covering the viewport
+    let position = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.tex_coord = fma(position, vec2<f32>(0.5, -0.5), vec2<f32>(0.5, 0.5));
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    return out;
+}
+
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(r_tex_color, r_tex_sampler, uv, 0.0).rgb;
+}
+
+fn scanline_mask(cell_y: f32, scale: f32) -> f32 {
+    if scale >= 2.0 && cell_y >= 0.7 {
+        return 0.55;
+    }
+    return 1.0;
+}
+
+fn grid_mask(cell: vec2<f32>, scale: f32) -> f32 {
+    let edge = 1.0 - 1.0 / scale;
+    if scale >= 3.0 && (cell.x >= edge || cell.y >= edge) {
+        return 0.6;
+    }
+    return 1.0;
+}
+
+@fragment
+fn fs_main(@location(0) tex_coord: vec2<f32>) -> @location(0) vec4<f32> {
+    let scale = r_locals.view_size.y / r_locals.tex_size.y;
+    var uv = tex_coord;
+    var vignette = 1.0;
+
+    if r_locals.mode == FILTER_CRT {
+        let c = tex_coord * 2.0 - 1.0;
+        let curved = vec2<f32>(
+            c.x * (1.0 + CRT_CURVATURE * c.y * c.y),
+            c.y * (1.0 + CRT_CURVATURE * c.x * c.x),
+        );
+        if abs(curved.x) >= 1.0 || abs(curved.y) >= 1.0 {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+        vignette = clamp(1.0 - 0.2 * dot(c, c), 0.0, 1.0);
+        uv = (curved + 1.0) / 2.0;
+    }
+
+    let cell = fract(uv * r_locals.tex_size);
+    var color = sample(uv);
+
+    if r_locals.mode == FILTER_SCANLINES {
+        color *= scanline_mask(cell.y, scale);
+    } else if r_locals.mode == FILTER_LCD {
+        color *= grid_mask(cell, scale);
+    } else if r_locals.mode == FILTER_CRT {
+        let texel = 1.0 / r_locals.tex_size;
+        var glow = vec3<f32>(0.0);
+        for (var dy = -1; dy <= 1; dy++) {
+            for (var dx = -1; dx <= 1; dx++) {
+                if dx != 0 || dy != 0 {
+                    glow += sample(uv + vec2<f32>(f32(dx), f32(dy)) * texel);
+                }
+            }
+        }
+        color = min(color + CRT_BLOOM * glow / 8.0, vec3<f32>(1.0));
+        color *= scanline_mask(cell.y, scale) * vignette;
+    }
+
+    return vec4<f32>(color, 1.0);
+}
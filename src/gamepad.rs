@@ -0,0 +1,507 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::emulator::InputEvent;
+
+/// Gamepad buttons, named after their position like the SDL and browser gamepad APIs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// The bottom face button: A on Xbox pads, cross on PlayStation pads.
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub const ALL: [Button; 12] = [
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+        Button::South,
+        Button::East,
+        Button::West,
+        Button::North,
+        Button::LeftShoulder,
+        Button::RightShoulder,
+        Button::Select,
+        Button::Start,
+    ];
+
+    /// The name used for this button in config files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::Left => "left",
+            Button::Right => "right",
+            Button::South => "south",
+            Button::East => "east",
+            Button::West => "west",
+            Button::North => "north",
+            Button::LeftShoulder => "left_shoulder",
+            Button::RightShoulder => "right_shoulder",
+            Button::Select => "select",
+            Button::Start => "start",
+        }
+    }
+}
+
+impl std::str::FromStr for Button {
+    type Err = String;
+
+    /// Parses a button name; the Xbox labels a, b, x and y are accepted for the face buttons.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let button = match lower.as_str() {
+            "a" => Some(Button::South),
+            "b" => Some(Button::East),
+            "x" => Some(Button::West),
+            "y" => Some(Button::North),
+            name => Button::ALL.into_iter().find(|button| button.name() == name),
+        };
+        button.ok_or_else(|| {
+            let names: Vec<_> = Button::ALL.iter().map(|button| button.name()).collect();
+            format!(
+                "unknown gamepad button '{}', expected one of {}",
+                s,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+/// A button going down or up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonEvent {
+    pub button: Button,
+    pub pressed: bool,
+}
+
+/// A source of gamepad button changes.
+pub trait GamepadBackend: Send {
+    /// Returns the next button change, or `None` if there is nothing new right now.
+    ///
+    /// Must not block. A backend that loses its device reports its held buttons as
+    /// released and then stays quiet.
+    fn poll(&mut self) -> Option<ButtonEvent>;
+}
+
+/// Which CHIP-8 key each gamepad button presses.
+///
+/// By default the d-pad presses 5/7/8/9 (W/A/S/D on the default keyboard layout), south
+/// presses 6 and east presses 4, which covers most games moving with 5/7/8/9. Games using
+/// another set of keys, such as 2/4/6/8, need a per-ROM profile in the keymap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GamepadMap {
+    buttons: HashMap<Button, u8>,
+}
+
+impl GamepadMap {
+    /// A map without any bindings.
+    pub fn empty() -> Self {
+        Self {
+            buttons: HashMap::new(),
+        }
+    }
+
+    /// Makes `button` press the CHIP-8 key `key`, replacing what it did before.
+    pub fn bind(&mut self, button: Button, key: u8) {
+        self.buttons.insert(button, key & 0xF);
+    }
+
+    /// Removes every button bound to the CHIP-8 key `key`.
+    pub fn unbind_key(&mut self, key: u8) {
+        self.buttons.retain(|_, bound| *bound != key);
+    }
+
+    pub fn key(&self, button: Button) -> Option<u8> {
+        self.buttons.get(&button).copied()
+    }
+}
+
+impl Default for GamepadMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+        for (button, key) in [
+            (Button::Up, 0x5),
+            (Button::Left, 0x7),
+            (Button::Down, 0x8),
+            (Button::Right, 0x9),
+            (Button::South, 0x6),
+            (Button::East, 0x4),
+        ] {
+            map.bind(button, key);
+        }
+        map
+    }
+}
+
+/// A gamepad translated to CHIP-8 key presses.
+///
+/// A CHIP-8 key bound to several buttons stays down until all of them are released.
+pub struct Gamepad {
+    backend: Box<dyn GamepadBackend>,
+    map: GamepadMap,
+    held: [u8; 16],
+}
+
+impl Gamepad {
+    pub fn new(backend: impl GamepadBackend + 'static, map: GamepadMap) -> Self {
+        Self {
+            backend: Box::new(backend),
+            map,
+            held: [0; 16],
+        }
+    }
+
+    /// Opens the first connected gamepad, if there is one.
+    #[cfg(all(feature = "gamepad", target_os = "linux"))]
+    pub fn connect(map: GamepadMap) -> Option<Self> {
+        Some(Self::new(evdev_backend::EvdevGamepad::open_first()?, map))
+    }
+
+    /// Opens the first connected gamepad, if there is one.
+    #[cfg(not(all(feature = "gamepad", target_os = "linux")))]
+    pub fn connect(_map: GamepadMap) -> Option<Self> {
+        None
+    }
+
+    /// Returns the next keypad event caused by the gamepad, if any.
+    pub fn poll(&mut self) -> Option<InputEvent> {
+        while let Some(ButtonEvent { button, pressed }) = self.backend.poll() {
+            let Some(key) = self.map.key(button) else {
+                continue;
+            };
+            let held = &mut self.held[key as usize];
+            if pressed {
+                *held += 1;
+                if *held == 1 {
+                    return Some(InputEvent::KeyDown(key));
+                }
+            } else if *held > 0 {
+                *held -= 1;
+                if *held == 0 {
+                    return Some(InputEvent::KeyUp(key));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The buttons held on a device, queued up as button changes for [`GamepadBackend::poll`].
+#[derive(Debug, Default)]
+struct Buttons {
+    held: HashSet<Button>,
+    pending: VecDeque<ButtonEvent>,
+    unplugged: bool,
+}
+
+impl Buttons {
+    /// Queues a change if `button` was not already in that state.
+    fn set(&mut self, button: Button, pressed: bool) {
+        if self.unplugged {
+            return;
+        }
+        let changed = if pressed {
+            self.held.insert(button)
+        } else {
+            self.held.remove(&button)
+        };
+        if changed {
+            self.pending.push_back(ButtonEvent { button, pressed });
+        }
+    }
+
+    /// Presses `negative` or `positive` depending on the direction of an axis.
+    #[cfg_attr(not(all(feature = "gamepad", target_os = "linux")), allow(dead_code))]
+    fn set_axis(&mut self, negative: Button, positive: Button, direction: i32) {
+        self.set(negative, direction < 0);
+        self.set(positive, direction > 0);
+    }
+
+    /// Lets go of everything and ignores any later changes.
+    fn unplug(&mut self) {
+        for button in std::mem::take(&mut self.held) {
+            self.pending.push_back(ButtonEvent {
+                button,
+                pressed: false,
+            });
+        }
+        self.unplugged = true;
+    }
+
+    fn next(&mut self) -> Option<ButtonEvent> {
+        self.pending.pop_front()
+    }
+}
+
+/// A gamepad driven by code, for tests and scripted input.
+///
+/// Clones share the same buttons, so one clone can be handed to a [`Gamepad`] while
+/// another presses and releases them.
+#[derive(Debug, Clone, Default)]
+pub struct MockGamepad {
+    buttons: Arc<Mutex<Buttons>>,
+}
+
+impl MockGamepad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&self, button: Button) {
+        self.buttons.lock().unwrap().set(button, true);
+    }
+
+    pub fn release(&self, button: Button) {
+        self.buttons.lock().unwrap().set(button, false);
+    }
+
+    /// Disconnects the gamepad, releasing every held button like a real device would.
+    pub fn unplug(&self) {
+        self.buttons.lock().unwrap().unplug();
+    }
+}
+
+impl GamepadBackend for MockGamepad {
+    fn poll(&mut self) -> Option<ButtonEvent> {
+        self.buttons.lock().unwrap().next()
+    }
+}
+
+#[cfg(all(feature = "gamepad", target_os = "linux"))]
+mod evdev_backend {
+    use evdev::{AbsoluteAxisCode, Device, EventSummary, KeyCode};
+
+    use super::{Button, ButtonEvent, Buttons, GamepadBackend};
+
+    /// A gamepad or joystick read through the Linux evdev interface.
+    pub struct EvdevGamepad {
+        device: Option<Device>,
+        /// Ranges of the left stick's X and Y axes, which stand in for the d-pad on pads
+        /// without one. `None` on pads with a d-pad, whose stick is ignored.
+        stick_ranges: Option<[(i32, i32); 2]>,
+        buttons: Buttons,
+    }
+
+    impl EvdevGamepad {
+        /// Opens the first input device with gamepad or joystick buttons.
+        pub fn open_first() -> Option<Self> {
+            let (_, device) = evdev::enumerate().find(|(_, device)| {
+                device.supported_keys().is_some_and(|keys| {
+                    keys.contains(KeyCode::BTN_SOUTH) || keys.contains(KeyCode::BTN_TRIGGER)
+                })
+            })?;
+            device.set_nonblocking(true).ok()?;
+            let has_dpad = device
+                .supported_keys()
+                .is_some_and(|keys| keys.contains(KeyCode::BTN_DPAD_UP))
+                || device
+                    .supported_absolute_axes()
+                    .is_some_and(|axes| axes.contains(AbsoluteAxisCode::ABS_HAT0X));
+            let stick_ranges = if has_dpad {
+                None
+            } else {
+                let range = |code| {
+                    let mut axes = device.get_absinfo().ok()?;
+                    let (_, info) = axes.find(|(axis, _)| *axis == code)?;
+                    Some((info.minimum(), info.maximum()))
+                };
+                Some([
+                    range(AbsoluteAxisCode::ABS_X)?,
+                    range(AbsoluteAxisCode::ABS_Y)?,
+                ])
+            };
+            Some(Self {
+                device: Some(device),
+                stick_ranges,
+                buttons: Buttons::default(),
+            })
+        }
+
+        fn read_events(&mut self) {
+            let Some(device) = &mut self.device else {
+                return;
+            };
+            let events: Result<Vec<_>, _> = device
+                .fetch_events()
+                .map(|events| events.map(|event| event.destructure()).collect());
+            let events = match events {
+                Ok(events) => events,
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return,
+                Err(_) => {
+                    self.device = None;
+                    self.buttons.unplug();
+                    return;
+                }
+            };
+            for event in events {
+                match event {
+                    EventSummary::Key(_, code, value) if value != 2 => {
+                        if let Some(button) = button(code) {
+                            self.buttons.set(button, value == 1);
+                        }
+                    }
+                    EventSummary::AbsoluteAxis(_, AbsoluteAxisCode::ABS_HAT0X, value) => {
+                        self.buttons.set_axis(Button::Left, Button::Right, value)
+                    }
+                    EventSummary::AbsoluteAxis(_, AbsoluteAxisCode::ABS_HAT0Y, value) => {
+                        self.buttons.set_axis(Button::Up, Button::Down, value)
+                    }
+                    EventSummary::AbsoluteAxis(_, AbsoluteAxisCode::ABS_X, value) => {
+                        if let Some([x_range, _]) = self.stick_ranges {
+                            let direction = stick_direction(x_range, value);
+                            self.buttons
+                                .set_axis(Button::Left, Button::Right, direction)
+                        }
+                    }
+                    EventSummary::AbsoluteAxis(_, AbsoluteAxisCode::ABS_Y, value) => {
+                        if let Some([_, y_range]) = self.stick_ranges {
+                            let direction = stick_direction(y_range, value);
+                            self.buttons.set_axis(Button::Up, Button::Down, direction)
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Direction of a stick axis spanning `min..=max`, with a dead zone covering the
+    /// middle half.
+    fn stick_direction((min, max): (i32, i32), value: i32) -> i32 {
+        let center = (min + max) / 2;
+        let dead_zone = (max - min) / 4;
+        if value < center - dead_zone {
+            -1
+        } else if value > center + dead_zone {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn button(code: KeyCode) -> Option<Button> {
+        let button = match code {
+            KeyCode::BTN_DPAD_UP => Button::Up,
+            KeyCode::BTN_DPAD_DOWN => Button::Down,
+            KeyCode::BTN_DPAD_LEFT => Button::Left,
+            KeyCode::BTN_DPAD_RIGHT => Button::Right,
+            KeyCode::BTN_SOUTH | KeyCode::BTN_TRIGGER => Button::South,
+            KeyCode::BTN_EAST | KeyCode::BTN_THUMB => Button::East,
+            KeyCode::BTN_WEST => Button::West,
+            KeyCode::BTN_NORTH => Button::North,
+            KeyCode::BTN_TL => Button::LeftShoulder,
+            KeyCode::BTN_TR => Button::RightShoulder,
+            KeyCode::BTN_SELECT => Button::Select,
+            KeyCode::BTN_START => Button::Start,
+            _ => return None,
+        };
+        Some(button)
+    }
+
+    impl GamepadBackend for EvdevGamepad {
+        fn poll(&mut self) -> Option<ButtonEvent> {
+            if self.buttons.pending.is_empty() {
+                self.read_events();
+            }
+            self.buttons.next()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gamepad(map: GamepadMap) -> (MockGamepad, Gamepad) {
+        let mock = MockGamepad::new();
+        (mock.clone(), Gamepad::new(mock, map))
+    }
+
+    fn drain(gamepad: &mut Gamepad) -> Vec<InputEvent> {
+        std::iter::from_fn(|| gamepad.poll()).collect()
+    }
+
+    #[test]
+    fn default_map_presses_keys() {
+        let (mock, mut gamepad) = gamepad(GamepadMap::default());
+        mock.press(Button::Up);
+        mock.press(Button::South);
+        mock.press(Button::Start);
+        assert_eq!(
+            drain(&mut gamepad),
+            [InputEvent::KeyDown(0x5), InputEvent::KeyDown(0x6)]
+        );
+        mock.release(Button::Up);
+        mock.release(Button::Start);
+        assert_eq!(drain(&mut gamepad), [InputEvent::KeyUp(0x5)]);
+    }
+
+    #[test]
+    fn profile_bindings_replace_defaults() {
+        let mut map = GamepadMap::default();
+        map.bind(Button::Up, 0x2);
+        map.bind(Button::Start, 0xF);
+        let (mock, mut gamepad) = gamepad(map);
+        mock.press(Button::Up);
+        mock.press(Button::Start);
+        assert_eq!(
+            drain(&mut gamepad),
+            [InputEvent::KeyDown(0x2), InputEvent::KeyDown(0xF)]
+        );
+    }
+
+    #[test]
+    fn unbound_keys_are_not_pressed() {
+        let mut map = GamepadMap::default();
+        map.unbind_key(0x6);
+        assert_eq!(map.key(Button::South), None);
+        let (mock, mut gamepad) = gamepad(map);
+        mock.press(Button::South);
+        mock.release(Button::South);
+        assert_eq!(drain(&mut gamepad), []);
+    }
+
+    #[test]
+    fn shared_key_stays_down_until_every_button_is_released() {
+        let mut map = GamepadMap::empty();
+        map.bind(Button::South, 0xA);
+        map.bind(Button::East, 0xA);
+        let (mock, mut gamepad) = gamepad(map);
+        mock.press(Button::South);
+        mock.press(Button::East);
+        assert_eq!(drain(&mut gamepad), [InputEvent::KeyDown(0xA)]);
+        mock.release(Button::South);
+        assert_eq!(drain(&mut gamepad), []);
+        mock.release(Button::East);
+        assert_eq!(drain(&mut gamepad), [InputEvent::KeyUp(0xA)]);
+    }
+
+    #[test]
+    fn unplugging_releases_held_keys() {
+        let (mock, mut gamepad) = gamepad(GamepadMap::default());
+        mock.press(Button::Up);
+        mock.press(Button::South);
+        drain(&mut gamepad);
+        mock.unplug();
+        // Held buttons come back in no particular order
+        let released = drain(&mut gamepad);
+        assert_eq!(released.len(), 2);
+        assert!(released.contains(&InputEvent::KeyUp(0x5)));
+        assert!(released.contains(&InputEvent::KeyUp(0x6)));
+        mock.press(Button::Up);
+        assert_eq!(drain(&mut gamepad), []);
+    }
+}
@@ -0,0 +1,677 @@
+use std::io;
+use std::mem::{offset_of, ManuallyDrop};
+use std::ops::Range;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, Block, InstBuilder, MemFlags, Type, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::Context as CodegenContext;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::Module;
+use rand::RngCore;
+
+use crate::chip8::{self, Chip8, Cpu};
+use crate::disasm::Instruction;
+use crate::emulator::Backend;
+
+/// Most instructions compiled into one block.
+const MAX_BLOCK_LEN: usize = 32;
+
+/// Times the code at an address is compiled before it counts as self-modifying and is
+/// left to the interpreter.
+const MAX_COMPILES: u8 = 4;
+
+/// A compiled block: runs its instructions on the CPU, no more than the budget it is handed,
+/// and returns how many it ran. It stops early at an instruction it has to leave to the
+/// interpreter.
+type BlockFn = unsafe extern "C" fn(*mut Cpu, u64) -> u64;
+
+#[derive(Clone, Copy)]
+enum Entry {
+    /// Nothing compiled yet.
+    Unknown,
+    /// The instruction here runs in the interpreter.
+    Interpret,
+    Compiled {
+        function: BlockFn,
+        len: u32,
+    },
+}
+
+/// Runs a [`Chip8`] by translating basic blocks of its code into native code with Cranelift.
+///
+/// A block runs up to the first jump, call or return, and follows skips and jumps back into
+/// itself without leaving native code, so loops waiting on the delay timer run natively.
+/// Instructions that draw, wait for keys, use the RNG or touch memory run in the
+/// interpreter, and blocks stop when the cycles run out, so the machine ends up in exactly
+/// the state [`Chip8::run`] leaves it in. Blocks are compiled the first time they run and
+/// dropped when memory under them changes; code that keeps changing is left to the
+/// interpreter.
+///
+/// The compiled code is kept between runs and shared by every machine the `Jit` runs. Many
+/// machines running the same ROM can share one, as only blocks whose code differs between
+/// them are compiled again.
+pub struct Jit {
+    module: ManuallyDrop<JITModule>,
+    codegen: CodegenContext,
+    builder: FunctionBuilderContext,
+    entries: Vec<Entry>,
+    compiles: Vec<u8>,
+    /// The memory the blocks were compiled from.
+    code: Box<[u8; 0x1000]>,
+    blocks: usize,
+}
+
+impl Jit {
+    /// Sets up code generation for the host, which fails on architectures Cranelift does
+    /// not support.
+    pub fn new() -> io::Result<Self> {
+        let unsupported = |err: String| io::Error::new(io::ErrorKind::Unsupported, err);
+        let mut flags = settings::builder();
+        flags
+            .set("opt_level", "speed")
+            .map_err(|err| unsupported(err.to_string()))?;
+        let isa = cranelift_native::builder()
+            .map_err(|err| unsupported(err.to_string()))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|err| unsupported(err.to_string()))?;
+        let module = JITModule::new(JITBuilder::with_isa(
+            isa,
+            cranelift_module::default_libcall_names(),
+        ));
+        Ok(Self {
+            codegen: module.make_context(),
+            module: ManuallyDrop::new(module),
+            builder: FunctionBuilderContext::new(),
+            entries: vec![Entry::Unknown; 0x1000],
+            compiles: vec![0; 0x1000],
+            code: Box::new([0; 0x1000]),
+            blocks: 0,
+        })
+    }
+
+    /// Number of blocks compiled so far.
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Runs `cycles` instructions like [`Chip8::run`].
+    pub fn run<R: RngCore>(&mut self, chip8: &mut Chip8<R>, cycles: u64) {
+        self.sync_code(chip8.memory(), 0..0x1000);
+        let mut cpu = chip8.cpu();
+        let mut remaining = cycles;
+        while remaining > 0 {
+            let pc = cpu.pc as usize;
+            if pc >= 0x1000 {
+                // Every cycle from here on does nothing
+                break;
+            }
+            if let Entry::Compiled { function, .. } = self.entry(pc) {
+                // SAFETY: blocks only touch the CPU they are handed, and stay valid until
+                // the module is freed on drop
+                let ran = unsafe { function(&mut cpu, remaining) };
+                remaining -= ran;
+                if ran > 0 {
+                    continue;
+                }
+            }
+            // The interpreter panics on the last byte of memory before looking at it
+            let opcode = (self.code[pc] as u16) << 8 | *self.code.get(pc + 1).unwrap_or(&0) as u16;
+            if opcode == 0 && pc < 0xFFF {
+                // 0000 stops the machine
+                break;
+            }
+            chip8.set_cpu(&cpu);
+            chip8.cycle();
+            remaining -= 1;
+            // Only Fx33 and Fx55 write memory, starting at I
+            let written = match opcode & 0xF0FF {
+                0xF033 => 3,
+                0xF055 => (opcode as usize >> 8 & 0xF) + 1,
+                _ => 0,
+            };
+            if written > 0 {
+                let start = cpu.i as usize;
+                self.sync_code(chip8.memory(), start..start + written);
+            }
+            cpu = chip8.cpu();
+        }
+        chip8.set_cpu(&cpu);
+    }
+
+    /// The entry at `pc`, compiling a block there if nothing was tried yet.
+    fn entry(&mut self, pc: usize) -> Entry {
+        if let Entry::Unknown = self.entries[pc] {
+            self.entries[pc] = self.compile(pc as u16);
+        }
+        self.entries[pc]
+    }
+
+    /// Drops the blocks overlapping any byte of `memory` in `range` that changed since they
+    /// were compiled.
+    fn sync_code(&mut self, memory: &[u8; 0x1000], range: Range<usize>) {
+        if memory[range.clone()] == self.code[range.clone()] {
+            return;
+        }
+        for address in range {
+            if memory[address] == self.code[address] {
+                continue;
+            }
+            self.code[address] = memory[address];
+            let first = address.saturating_sub(2 * MAX_BLOCK_LEN - 1);
+            for start in first..=address {
+                let end = match self.entries[start] {
+                    Entry::Unknown => continue,
+                    Entry::Interpret => start + 2,
+                    Entry::Compiled { len, .. } => start + 2 * len as usize,
+                };
+                if address < end {
+                    self.entries[start] = if self.compiles[start] >= MAX_COMPILES {
+                        Entry::Interpret
+                    } else {
+                        Entry::Unknown
+                    };
+                }
+            }
+        }
+    }
+
+    /// Compiles the block starting at `start`, or leaves it to the interpreter if its first
+    /// instruction cannot be compiled.
+    fn compile(&mut self, start: u16) -> Entry {
+        if self.compiles[start as usize] >= MAX_COMPILES {
+            return Entry::Interpret;
+        }
+        let mut instructions = Vec::new();
+        let mut address = start as usize;
+        while instructions.len() < MAX_BLOCK_LEN && address < 0xFFF {
+            let opcode = (self.code[address] as u16) << 8 | self.code[address + 1] as u16;
+            let instruction = chip8::decode(opcode);
+            match Step::of(instruction) {
+                Step::Straight | Step::Skip => instructions.push(instruction),
+                Step::Leave => {
+                    instructions.push(instruction);
+                    break;
+                }
+                Step::Interpret => break,
+            }
+            address += 2;
+        }
+        if instructions.is_empty() {
+            return Entry::Interpret;
+        }
+
+        self.compiles[start as usize] += 1;
+        self.blocks += 1;
+        let function = self.define(start, &instructions);
+        Entry::Compiled {
+            function,
+            len: instructions.len() as u32,
+        }
+    }
+
+    fn define(&mut self, start: u16, instructions: &[Instruction]) -> BlockFn {
+        let pointer = self.module.target_config().pointer_type();
+        self.module.clear_context(&mut self.codegen);
+        let signature = &mut self.codegen.func.signature;
+        signature.params.push(AbiParam::new(pointer));
+        signature.params.push(AbiParam::new(types::I64));
+        signature.returns.push(AbiParam::new(types::I64));
+
+        let builder = FunctionBuilder::new(&mut self.codegen.func, &mut self.builder);
+        BlockBuilder::new(builder, pointer, start, instructions).build();
+
+        let id = self
+            .module
+            .declare_anonymous_function(&self.codegen.func.signature)
+            .expect("blocks should be declarable");
+        self.module
+            .define_function(id, &mut self.codegen)
+            .expect("generated code should compile");
+        self.module
+            .finalize_definitions()
+            .expect("compiled code should be loadable");
+        let code = self.module.get_finalized_function(id);
+        // SAFETY: the function was declared with this signature
+        unsafe { std::mem::transmute::<*const u8, BlockFn>(code) }
+    }
+}
+
+impl Backend for Jit {
+    fn run(&mut self, chip8: &mut Chip8, cycles: u64) {
+        Jit::run(self, chip8, cycles);
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        // SAFETY: the blocks in `entries` are never called again
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() };
+    }
+}
+
+/// How an instruction fits in a block.
+enum Step {
+    /// Compiled, and followed by the next instruction.
+    Straight,
+    /// Compiled, and followed by the next instruction or the one after.
+    Skip,
+    /// Compiled, and followed by code elsewhere, so the block ends with it.
+    Leave,
+    /// Left to the interpreter.
+    Interpret,
+}
+
+impl Step {
+    fn of(instruction: Instruction) -> Self {
+        use Instruction::*;
+        match instruction {
+            LoadImm { .. }
+            | AddImm { .. }
+            | Move { .. }
+            | Or { .. }
+            | And { .. }
+            | Xor { .. }
+            | Add { .. }
+            | Sub { .. }
+            | ShiftRight { .. }
+            | SubReverse { .. }
+            | ShiftLeft { .. }
+            | LoadI { .. }
+            | AddI { .. }
+            | Font { .. }
+            | GetDelay { .. }
+            | SetDelay { .. }
+            | SetSound { .. } => Step::Straight,
+            SkipEqImm { .. }
+            | SkipNeImm { .. }
+            | SkipEq { .. }
+            | SkipNe { .. }
+            | SkipKey { .. }
+            | SkipNotKey { .. } => Step::Skip,
+            Jump { .. } | Call { .. } | Return => Step::Leave,
+            Clear
+            | Sys { .. }
+            | JumpV0 { .. }
+            | Random { .. }
+            | Draw { .. }
+            | WaitKey { .. }
+            | Bcd { .. }
+            | Store { .. }
+            | Load { .. }
+            | Unknown(_) => Step::Interpret,
+        }
+    }
+}
+
+/// Variables past V0-VF.
+const I: usize = 16;
+const DELAY_TIMER: usize = 17;
+const SOUND_TIMER: usize = 18;
+/// Instructions run so far.
+const RAN: usize = 19;
+
+/// Emits the code of one block, keeping the registers in variables until it exits.
+///
+/// Every instruction gets a Cranelift block of its own, so skips and jumps within the block
+/// stay in native code. Before each instruction the block exits if it ran out of cycles.
+struct BlockBuilder<'a> {
+    builder: FunctionBuilder<'a>,
+    cpu: Value,
+    budget: Value,
+    pointer: Type,
+    start: u16,
+    instructions: &'a [Instruction],
+    labels: Vec<Block>,
+    /// Writes back the registers and returns, with the address to continue at.
+    exit: Block,
+    /// Which of V0-VF, I, DT and ST the block changes.
+    written: [bool; 19],
+}
+
+impl<'a> BlockBuilder<'a> {
+    fn new(
+        mut builder: FunctionBuilder<'a>,
+        pointer: Type,
+        start: u16,
+        instructions: &'a [Instruction],
+    ) -> Self {
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        let cpu = builder.block_params(entry)[0];
+        let budget = builder.block_params(entry)[1];
+        let labels = instructions
+            .iter()
+            .map(|_| builder.create_block())
+            .collect();
+        let exit = builder.create_block();
+        builder.append_block_param(exit, types::I16);
+        Self {
+            builder,
+            cpu,
+            budget,
+            pointer,
+            start,
+            instructions,
+            labels,
+            exit,
+            written: [false; 19],
+        }
+    }
+
+    fn build(mut self) {
+        for x in 0..16 {
+            let v = self.load(types::I8, offset_of!(Cpu, v) + x);
+            self.declare(x, types::I8, v);
+        }
+        let i = self.load(types::I16, offset_of!(Cpu, i));
+        self.declare(I, types::I16, i);
+        let delay_timer = self.load(types::I8, offset_of!(Cpu, delay_timer));
+        self.declare(DELAY_TIMER, types::I8, delay_timer);
+        let sound_timer = self.load(types::I8, offset_of!(Cpu, sound_timer));
+        self.declare(SOUND_TIMER, types::I8, sound_timer);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.declare(RAN, types::I64, zero);
+        self.builder.ins().jump(self.labels[0], &[]);
+
+        for (index, &instruction) in self.instructions.iter().enumerate() {
+            let pc = self.start + 2 * index as u16;
+            self.builder.switch_to_block(self.labels[index]);
+            let ran = self.get(RAN);
+            let out_of_cycles =
+                self.builder
+                    .ins()
+                    .icmp(IntCC::UnsignedGreaterThanOrEqual, ran, self.budget);
+            let run = self.builder.create_block();
+            let here = self.constant(types::I16, pc);
+            self.builder
+                .ins()
+                .brif(out_of_cycles, self.exit, &[here], run, &[]);
+            self.builder.switch_to_block(run);
+            self.instruction(instruction, pc);
+        }
+
+        self.builder.switch_to_block(self.exit);
+        let pc = self.builder.block_params(self.exit)[0];
+        for variable in 0..self.written.len() {
+            if !self.written[variable] {
+                continue;
+            }
+            let value = self.get(variable);
+            let offset = match variable {
+                I => offset_of!(Cpu, i),
+                DELAY_TIMER => offset_of!(Cpu, delay_timer),
+                SOUND_TIMER => offset_of!(Cpu, sound_timer),
+                x => offset_of!(Cpu, v) + x,
+            };
+            self.store(value, offset);
+        }
+        self.store(pc, offset_of!(Cpu, pc));
+        let ran = self.get(RAN);
+        self.builder.ins().return_(&[ran]);
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+    }
+
+    fn declare(&mut self, variable: usize, ty: Type, value: Value) {
+        let variable = Variable::from_u32(variable as u32);
+        self.builder.declare_var(variable, ty);
+        self.builder.def_var(variable, value);
+    }
+
+    fn get(&mut self, variable: usize) -> Value {
+        self.builder.use_var(Variable::from_u32(variable as u32))
+    }
+
+    fn set(&mut self, variable: usize, value: Value) {
+        self.builder
+            .def_var(Variable::from_u32(variable as u32), value);
+        self.written[variable] = true;
+    }
+
+    fn constant(&mut self, ty: Type, value: u16) -> Value {
+        self.builder.ins().iconst(ty, value as i64)
+    }
+
+    /// Emits `instruction` at `pc`, ending with a branch to what runs next.
+    fn instruction(&mut self, instruction: Instruction, pc: u16) {
+        use Instruction::*;
+        match instruction {
+            LoadImm { x, nn } => {
+                let value = self.constant(types::I8, nn as u16);
+                self.set(x as usize, value);
+            }
+            AddImm { x, nn } => {
+                let vx = self.get(x as usize);
+                let sum = self.builder.ins().iadd_imm(vx, nn as i64);
+                self.set(x as usize, sum);
+            }
+            Move { x, y } => {
+                let vy = self.get(y as usize);
+                self.set(x as usize, vy);
+            }
+            Or { x, y } | And { x, y } | Xor { x, y } => {
+                let (vx, vy) = (self.get(x as usize), self.get(y as usize));
+                let result = match instruction {
+                    Or { .. } => self.builder.ins().bor(vx, vy),
+                    And { .. } => self.builder.ins().band(vx, vy),
+                    _ => self.builder.ins().bxor(vx, vy),
+                };
+                self.set(x as usize, result);
+                let zero = self.constant(types::I8, 0);
+                self.set(0xF, zero);
+            }
+            Add { x, y } => {
+                let (vx, vy) = (self.get(x as usize), self.get(y as usize));
+                let sum = self.builder.ins().iadd(vx, vy);
+                let carry = self.builder.ins().icmp(IntCC::UnsignedLessThan, sum, vx);
+                self.set(x as usize, sum);
+                self.set(0xF, carry);
+            }
+            Sub { x, y } | SubReverse { x, y } => {
+                let (vx, vy) = (self.get(x as usize), self.get(y as usize));
+                let (a, b) = match instruction {
+                    Sub { .. } => (vx, vy),
+                    _ => (vy, vx),
+                };
+                let no_borrow = self
+                    .builder
+                    .ins()
+                    .icmp(IntCC::UnsignedGreaterThanOrEqual, a, b);
+                let difference = self.builder.ins().isub(a, b);
+                self.set(x as usize, difference);
+                self.set(0xF, no_borrow);
+            }
+            ShiftRight { x, y } => {
+                let vy = self.get(y as usize);
+                let lsb = self.builder.ins().band_imm(vy, 1);
+                let shifted = self.builder.ins().ushr_imm(vy, 1);
+                self.set(x as usize, shifted);
+                self.set(0xF, lsb);
+            }
+            ShiftLeft { x, y } => {
+                let vy = self.get(y as usize);
+                let msb = self.builder.ins().ushr_imm(vy, 7);
+                let shifted = self.builder.ins().ishl_imm(vy, 1);
+                self.set(x as usize, shifted);
+                self.set(0xF, msb);
+            }
+            LoadI { nnn } => {
+                let value = self.constant(types::I16, nnn);
+                self.set(I, value);
+            }
+            AddI { x } => {
+                let vx = self.get(x as usize);
+                let vx = self.builder.ins().uextend(types::I16, vx);
+                let i = self.get(I);
+                let sum = self.builder.ins().iadd(i, vx);
+                self.set(I, sum);
+            }
+            Font { x } => {
+                let vx = self.get(x as usize);
+                let vx = self.builder.ins().uextend(types::I16, vx);
+                let address = self.builder.ins().imul_imm(vx, 5);
+                self.set(I, address);
+            }
+            GetDelay { x } => {
+                let delay_timer = self.get(DELAY_TIMER);
+                self.set(x as usize, delay_timer);
+            }
+            SetDelay { x } => {
+                let vx = self.get(x as usize);
+                self.set(DELAY_TIMER, vx);
+            }
+            SetSound { x } => {
+                let vx = self.get(x as usize);
+                self.set(SOUND_TIMER, vx);
+            }
+            SkipEqImm { x, nn } | SkipNeImm { x, nn } => {
+                let vx = self.get(x as usize);
+                let condition = match instruction {
+                    SkipEqImm { .. } => IntCC::Equal,
+                    _ => IntCC::NotEqual,
+                };
+                let skip = self.builder.ins().icmp_imm(condition, vx, nn as i64);
+                return self.skip_if(skip, pc);
+            }
+            SkipEq { x, y } | SkipNe { x, y } => {
+                let (vx, vy) = (self.get(x as usize), self.get(y as usize));
+                let condition = match instruction {
+                    SkipEq { .. } => IntCC::Equal,
+                    _ => IntCC::NotEqual,
+                };
+                let skip = self.builder.ins().icmp(condition, vx, vy);
+                return self.skip_if(skip, pc);
+            }
+            SkipKey { x } | SkipNotKey { x } => {
+                let keys = self.load(types::I16, offset_of!(Cpu, keys));
+                let vx = self.get(x as usize);
+                let key = self.builder.ins().band_imm(vx, 0xF);
+                let key = self.builder.ins().uextend(types::I16, key);
+                let held = self.builder.ins().ushr(keys, key);
+                let held = self.builder.ins().band_imm(held, 1);
+                let condition = match instruction {
+                    SkipKey { .. } => IntCC::NotEqual,
+                    _ => IntCC::Equal,
+                };
+                let skip = self.builder.ins().icmp_imm(condition, held, 0);
+                return self.skip_if(skip, pc);
+            }
+            Jump { nnn } => {
+                self.count();
+                return self.goto(nnn);
+            }
+            Call { nnn } => {
+                let sp = self.load(types::I16, offset_of!(Cpu, sp));
+                let full = self
+                    .builder
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, sp, 16);
+                self.bail_if(full, pc);
+                let slot = self.stack_slot(sp);
+                let return_address = self.constant(types::I16, pc + 2);
+                self.builder.ins().store(
+                    flags(),
+                    return_address,
+                    slot,
+                    offset_of!(Cpu, stack) as i32,
+                );
+                let sp = self.builder.ins().iadd_imm(sp, 1);
+                self.store(sp, offset_of!(Cpu, sp));
+                self.count();
+                return self.goto(nnn);
+            }
+            Return => {
+                let sp = self.load(types::I16, offset_of!(Cpu, sp));
+                let empty = self.builder.ins().icmp_imm(IntCC::Equal, sp, 0);
+                self.bail_if(empty, pc);
+                let one = self.constant(types::I16, 1);
+                let sp = self.builder.ins().isub(sp, one);
+                self.store(sp, offset_of!(Cpu, sp));
+                let slot = self.stack_slot(sp);
+                let target = self.builder.ins().load(
+                    types::I16,
+                    flags(),
+                    slot,
+                    offset_of!(Cpu, stack) as i32,
+                );
+                self.count();
+                self.builder.ins().jump(self.exit, &[target]);
+                return;
+            }
+            _ => unreachable!("{} is left to the interpreter", instruction),
+        }
+        self.count();
+        self.goto(pc + 2);
+    }
+
+    /// Counts the instruction just emitted as run.
+    fn count(&mut self) {
+        let ran = self.get(RAN);
+        let ran = self.builder.ins().iadd_imm(ran, 1);
+        self.builder.def_var(Variable::from_u32(RAN as u32), ran);
+    }
+
+    /// The block and arguments that continue at `address`: the instruction's own block if
+    /// it is in this block, or else the exit.
+    fn target(&mut self, address: u16) -> (Block, Vec<Value>) {
+        let offset = address.wrapping_sub(self.start) as usize;
+        if offset.is_multiple_of(2) && offset / 2 < self.labels.len() {
+            (self.labels[offset / 2], Vec::new())
+        } else {
+            let address = self.constant(types::I16, address);
+            (self.exit, vec![address])
+        }
+    }
+
+    fn goto(&mut self, address: u16) {
+        let (block, arguments) = self.target(address);
+        self.builder.ins().jump(block, &arguments);
+    }
+
+    /// Goes on at the instruction after the next if `skip` is set, or else the next.
+    fn skip_if(&mut self, skip: Value, pc: u16) {
+        self.count();
+        let (skipped, skipped_arguments) = self.target(pc + 4);
+        let (next, next_arguments) = self.target(pc + 2);
+        self.builder
+            .ins()
+            .brif(skip, skipped, &skipped_arguments, next, &next_arguments);
+    }
+
+    /// Leaves the instruction at `pc` to the interpreter if `condition` is set, which is
+    /// how stack overflows panic just like they do there.
+    fn bail_if(&mut self, condition: Value, pc: u16) {
+        let go_on = self.builder.create_block();
+        let here = self.constant(types::I16, pc);
+        self.builder
+            .ins()
+            .brif(condition, self.exit, &[here], go_on, &[]);
+        self.builder.switch_to_block(go_on);
+    }
+
+    /// The address of stack entry `sp`, less the offset of the stack.
+    fn stack_slot(&mut self, sp: Value) -> Value {
+        let sp = self.builder.ins().uextend(self.pointer, sp);
+        let offset = self.builder.ins().ishl_imm(sp, 1);
+        self.builder.ins().iadd(self.cpu, offset)
+    }
+
+    fn load(&mut self, ty: Type, offset: usize) -> Value {
+        self.builder
+            .ins()
+            .load(ty, flags(), self.cpu, offset as i32)
+    }
+
+    fn store(&mut self, value: Value, offset: usize) {
+        self.builder
+            .ins()
+            .store(flags(), value, self.cpu, offset as i32);
+    }
+}
+
+fn flags() -> MemFlags {
+    MemFlags::trusted()
+}
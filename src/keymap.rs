@@ -0,0 +1,654 @@
+use std::collections::HashMap;
+#[cfg(feature = "config")]
+use std::collections::{BTreeMap, HashSet};
+#[cfg(feature = "config")]
+use std::{fs, path::Path, path::PathBuf};
+
+#[cfg(feature = "config")]
+use serde::Deserialize;
+
+#[cfg(feature = "config")]
+use crate::gamepad::Button;
+use crate::gamepad::GamepadMap;
+
+/// Names of the host keys that can be bound, in their canonical spelling.
+///
+/// Frontends translate their native key codes to these names; the names are matched
+/// case-insensitively when read from a config file.
+#[rustfmt::skip]
+pub const KEY_NAMES: &[&str] = &[
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9",
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M",
+    "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z",
+    "Numpad0", "Numpad1", "Numpad2", "Numpad3", "Numpad4",
+    "Numpad5", "Numpad6", "Numpad7", "Numpad8", "Numpad9",
+    "NumpadAdd", "NumpadSubtract", "NumpadMultiply", "NumpadDivide",
+    "NumpadDecimal", "NumpadEnter",
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+    "Up", "Down", "Left", "Right",
+    "Space", "Enter", "Tab", "Backspace", "Escape",
+    "Insert", "Delete", "Home", "End", "PageUp", "PageDown",
+    "Minus", "Equal", "Comma", "Period", "Slash", "Semicolon", "Quote",
+    "BracketLeft", "BracketRight", "Backslash", "Backquote",
+    "ShiftLeft", "ShiftRight", "ControlLeft", "ControlRight", "AltLeft", "AltRight",
+];
+
+/// Alternative spellings accepted for some key names.
+const KEY_ALIASES: &[(&str, &str)] = &[
+    ("esc", "Escape"),
+    ("return", "Enter"),
+    ("ctrl", "ControlLeft"),
+    ("shift", "ShiftLeft"),
+    ("alt", "AltLeft"),
+];
+
+/// Emulator functions that can be bound to host keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hotkey {
+    Quit,
+    Pause,
+    Reset,
+    SaveState,
+    LoadState,
+    /// Runs faster than real time while held.
+    FastForward,
+    Screenshot,
+    Record,
+    Fullscreen,
+}
+
+impl Hotkey {
+    pub const ALL: [Hotkey; 9] = [
+        Hotkey::Quit,
+        Hotkey::Pause,
+        Hotkey::Reset,
+        Hotkey::SaveState,
+        Hotkey::LoadState,
+        Hotkey::FastForward,
+        Hotkey::Screenshot,
+        Hotkey::Record,
+        Hotkey::Fullscreen,
+    ];
+
+    /// The name used for this hotkey in config files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Hotkey::Quit => "quit",
+            Hotkey::Pause => "pause",
+            Hotkey::Reset => "reset",
+            Hotkey::SaveState => "save_state",
+            Hotkey::LoadState => "load_state",
+            Hotkey::FastForward => "fast_forward",
+            Hotkey::Screenshot => "screenshot",
+            Hotkey::Record => "record",
+            Hotkey::Fullscreen => "fullscreen",
+        }
+    }
+}
+
+/// What a host key does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Presses a key of the CHIP-8 keypad.
+    Key(u8),
+    Hotkey(Hotkey),
+}
+
+/// Built-in layouts for the 16 CHIP-8 keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// The left hand block 1234/QWER/ASDF/ZXCV, matching the shape of the COSMAC VIP keypad.
+    #[default]
+    Qwerty,
+    /// Digits on the same numpad digits, A-F on the numpad operator keys.
+    Numpad,
+}
+
+impl Layout {
+    fn keys(self) -> [(&'static str, u8); 16] {
+        match self {
+            Layout::Qwerty => [
+                ("1", 0x1),
+                ("2", 0x2),
+                ("3", 0x3),
+                ("4", 0xC),
+                ("Q", 0x4),
+                ("W", 0x5),
+                ("E", 0x6),
+                ("R", 0xD),
+                ("A", 0x7),
+                ("S", 0x8),
+                ("D", 0x9),
+                ("F", 0xE),
+                ("Z", 0xA),
+                ("X", 0x0),
+                ("C", 0xB),
+                ("V", 0xF),
+            ],
+            Layout::Numpad => [
+                ("Numpad0", 0x0),
+                ("Numpad1", 0x1),
+                ("Numpad2", 0x2),
+                ("Numpad3", 0x3),
+                ("Numpad4", 0x4),
+                ("Numpad5", 0x5),
+                ("Numpad6", 0x6),
+                ("Numpad7", 0x7),
+                ("Numpad8", 0x8),
+                ("Numpad9", 0x9),
+                ("NumpadDivide", 0xA),
+                ("NumpadMultiply", 0xB),
+                ("NumpadSubtract", 0xC),
+                ("NumpadAdd", 0xD),
+                ("NumpadEnter", 0xE),
+                ("NumpadDecimal", 0xF),
+            ],
+        }
+    }
+}
+
+impl std::str::FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "qwerty" => Ok(Layout::Qwerty),
+            "numpad" => Ok(Layout::Numpad),
+            _ => Err(format!("unknown layout '{}', expected qwerty or numpad", s)),
+        }
+    }
+}
+
+/// Maps host key names to CHIP-8 keys and hotkeys.
+///
+/// The default keymap uses the [`Layout::Qwerty`] layout with these hotkeys: Escape quits,
+/// P pauses, Backspace resets, F5 saves and F7 loads the state, holding Tab fast-forwards,
+/// F9 records, F11 toggles fullscreen and F12 takes a screenshot. Gamepads use the
+/// default [`GamepadMap`].
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<&'static str, Action>,
+    gamepad: GamepadMap,
+}
+
+impl Keymap {
+    pub fn new(layout: Layout) -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+            gamepad: GamepadMap::default(),
+        };
+        for (name, key) in layout.keys() {
+            keymap.bindings.insert(name, Action::Key(key));
+        }
+        for (name, hotkey) in [
+            ("Escape", Hotkey::Quit),
+            ("P", Hotkey::Pause),
+            ("Backspace", Hotkey::Reset),
+            ("F5", Hotkey::SaveState),
+            ("F7", Hotkey::LoadState),
+            ("Tab", Hotkey::FastForward),
+            ("F9", Hotkey::Record),
+            ("F11", Hotkey::Fullscreen),
+            ("F12", Hotkey::Screenshot),
+        ] {
+            keymap.bindings.insert(name, Action::Hotkey(hotkey));
+        }
+        keymap
+    }
+
+    /// Looks up the action bound to the host key called `name`, see [`KEY_NAMES`].
+    pub fn action(&self, name: &str) -> Option<Action> {
+        self.bindings.get(name).copied()
+    }
+
+    /// Binds `name` to `action`, replacing whatever the key did before.
+    ///
+    /// Fails if `name` is not a known key name.
+    pub fn bind(&mut self, name: &str, action: Action) -> Result<(), String> {
+        let name = key_name(name).ok_or_else(|| unknown_key_message(name))?;
+        self.bindings.insert(name, action);
+        Ok(())
+    }
+
+    /// Removes every binding of `action`.
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.retain(|_, bound| *bound != action);
+    }
+
+    /// Which CHIP-8 keys the gamepad buttons press.
+    pub fn gamepad(&self) -> &GamepadMap {
+        &self.gamepad
+    }
+
+    pub fn gamepad_mut(&mut self) -> &mut GamepadMap {
+        &mut self.gamepad
+    }
+
+    /// The host keys bound to `action`, sorted by name.
+    pub fn keys_for(&self, action: Action) -> Vec<&'static str> {
+        let mut keys: Vec<_> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(name, _)| *name)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(Layout::default())
+    }
+}
+
+/// Returns the canonical spelling of a key name, see [`KEY_NAMES`].
+pub fn key_name(name: &str) -> Option<&'static str> {
+    let lower = name.to_ascii_lowercase();
+    if let Some((_, canonical)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == lower) {
+        return Some(canonical);
+    }
+    let find = |lower: &str| {
+        KEY_NAMES
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(lower))
+            .copied()
+    };
+    // Also accept the browser/winit spellings such as KeyQ, Digit1 and ArrowUp
+    let unprefixed = ["key", "digit", "arrow"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix).filter(|rest| !rest.is_empty()));
+    find(&lower).or_else(|| unprefixed.and_then(find))
+}
+
+fn unknown_key_message(name: &str) -> String {
+    let lower = name.to_ascii_lowercase();
+    let closest = KEY_NAMES
+        .iter()
+        .map(|candidate| {
+            (
+                edit_distance(&lower, &candidate.to_ascii_lowercase()),
+                candidate,
+            )
+        })
+        .min_by_key(|(distance, candidate)| (*distance, candidate.len().abs_diff(name.len())));
+    match closest {
+        Some((distance, candidate)) if distance <= 2 => {
+            format!("unknown key name '{}', did you mean '{}'?", name, candidate)
+        }
+        _ => format!(
+            "unknown key name '{}', expected a letter, a digit or a name like Numpad7, F5, Space or Up",
+            name
+        ),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Key bindings read from a config file, see the README for the format.
+#[cfg(feature = "config")]
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    layout: Option<String>,
+    #[serde(default)]
+    keys: BTreeMap<String, HostKeys>,
+    #[serde(default)]
+    hotkeys: BTreeMap<String, HostKeys>,
+    #[serde(default)]
+    gamepad: BTreeMap<String, HostKeys>,
+    #[serde(default)]
+    rom: BTreeMap<String, RomSection>,
+}
+
+#[cfg(feature = "config")]
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RomSection {
+    layout: Option<String>,
+    #[serde(default)]
+    keys: BTreeMap<String, HostKeys>,
+    #[serde(default)]
+    hotkeys: BTreeMap<String, HostKeys>,
+    #[serde(default)]
+    gamepad: BTreeMap<String, HostKeys>,
+}
+
+#[cfg(feature = "config")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HostKeys {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[cfg(feature = "config")]
+impl HostKeys {
+    fn names(&self) -> &[String] {
+        match self {
+            HostKeys::One(name) => std::slice::from_ref(name),
+            HostKeys::Many(names) => names,
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl Keymap {
+    /// Reads the keymap at `path`, applying the overrides for `rom` if it has any.
+    pub fn load(path: &Path, rom: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read keymap {}: {}", path.display(), err))?;
+        Self::from_toml(&text, rom).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Reads the keymap from the default location if the file exists, see
+    /// [`Keymap::default_path`], and falls back to the default keymap otherwise.
+    pub fn load_default(rom: &Path) -> Result<Self, String> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path, rom),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// `chippers/keymap.toml` in the user's config directory: `$XDG_CONFIG_HOME`,
+    /// `~/.config` or `%APPDATA%`.
+    pub fn default_path() -> Option<PathBuf> {
+        let env_dir = |var| {
+            std::env::var_os(var)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+        };
+        let config_dir = env_dir("XDG_CONFIG_HOME")
+            .or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+            .or_else(|| env_dir("APPDATA"))?;
+        Some(config_dir.join("chippers").join("keymap.toml"))
+    }
+
+    /// Parses a keymap config, applying the overrides for `rom` if it has any.
+    pub fn from_toml(text: &str, rom: &Path) -> Result<Self, String> {
+        let config: ConfigFile = toml::from_str(text).map_err(|err| err.to_string())?;
+        let rom_name = rom.file_name().and_then(|name| name.to_str());
+        let rom_section = rom_name.and_then(|name| config.rom.get(name));
+
+        let layout = match rom_section.and_then(|rom| rom.layout.as_ref()) {
+            Some(layout) => layout
+                .parse()
+                .map_err(|err| format!("[rom.\"{}\"] {}", rom_name.unwrap(), err))?,
+            None => config.layout.as_deref().unwrap_or("qwerty").parse()?,
+        };
+
+        let mut keymap = Self::new(layout);
+        keymap.apply("", &config.keys, &config.hotkeys)?;
+        keymap.apply_gamepad("", &config.gamepad)?;
+        if let Some(rom) = rom_section {
+            let section = format!("rom.\"{}\".", rom_name.unwrap());
+            keymap.apply(&section, &rom.keys, &rom.hotkeys)?;
+            keymap.apply_gamepad(&section, &rom.gamepad)?;
+        }
+        Ok(keymap)
+    }
+
+    fn apply(
+        &mut self,
+        section: &str,
+        keys: &BTreeMap<String, HostKeys>,
+        hotkeys: &BTreeMap<String, HostKeys>,
+    ) -> Result<(), String> {
+        let mut bindings = Vec::new();
+        for (key, host_keys) in keys {
+            let action = Action::Key(parse_chip8_key(&format!("{}keys", section), key)?);
+            bindings.push((format!("{}keys", section), action, host_keys));
+        }
+        for (hotkey, host_keys) in hotkeys {
+            let action = Hotkey::ALL
+                .into_iter()
+                .find(|candidate| candidate.name() == hotkey)
+                .map(Action::Hotkey)
+                .ok_or_else(|| {
+                    let names: Vec<_> = Hotkey::ALL.iter().map(|hotkey| hotkey.name()).collect();
+                    format!(
+                        "[{}hotkeys] unknown hotkey '{}', expected one of {}",
+                        section,
+                        hotkey,
+                        names.join(", ")
+                    )
+                })?;
+            bindings.push((format!("{}hotkeys", section), action, host_keys));
+        }
+
+        for (_, action, _) in &bindings {
+            self.unbind(*action);
+        }
+        let mut bound = HashSet::new();
+        for (table, action, host_keys) in bindings {
+            for name in host_keys.names() {
+                let canonical = key_name(name)
+                    .ok_or_else(|| format!("[{}] {}", table, unknown_key_message(name)))?;
+                if !bound.insert(canonical) {
+                    return Err(format!(
+                        "[{}] host key '{}' is bound more than once",
+                        table, canonical
+                    ));
+                }
+                self.bindings.insert(canonical, action);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_gamepad(
+        &mut self,
+        section: &str,
+        gamepad: &BTreeMap<String, HostKeys>,
+    ) -> Result<(), String> {
+        let table = format!("{}gamepad", section);
+        let mut bindings = Vec::new();
+        for (key, buttons) in gamepad {
+            let key = parse_chip8_key(&table, key)?;
+            for name in buttons.names() {
+                let button: Button = name.parse().map_err(|err| format!("[{}] {}", table, err))?;
+                bindings.push((key, button));
+            }
+        }
+
+        for (key, _) in &bindings {
+            self.gamepad.unbind_key(*key);
+        }
+        let mut bound = HashSet::new();
+        for (key, button) in bindings {
+            if !bound.insert(button) {
+                return Err(format!(
+                    "[{}] gamepad button '{}' is bound more than once",
+                    table,
+                    button.name()
+                ));
+            }
+            self.gamepad.bind(button, key);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "config")]
+fn parse_chip8_key(table: &str, key: &str) -> Result<u8, String> {
+    let digits = key.strip_prefix("0x").unwrap_or(key);
+    // from_str_radix also takes a sign
+    let parsed = if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        u8::from_str_radix(digits, 16).ok()
+    } else {
+        None
+    };
+    match parsed {
+        Some(key) if key < 16 => Ok(key),
+        _ => Err(format!(
+            "[{}] unknown CHIP-8 key '{}', expected a hex digit 0-F",
+            table, key
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keymap_uses_the_qwerty_block_and_hotkeys() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action("4"), Some(Action::Key(0xC)));
+        assert_eq!(keymap.action("Q"), Some(Action::Key(0x4)));
+        assert_eq!(keymap.action("V"), Some(Action::Key(0xF)));
+        assert_eq!(keymap.action("Escape"), Some(Action::Hotkey(Hotkey::Quit)));
+        assert_eq!(keymap.action("Numpad5"), None);
+    }
+
+    #[test]
+    fn key_names_accept_aliases_and_other_spellings() {
+        assert_eq!(key_name("esc"), Some("Escape"));
+        assert_eq!(key_name("Return"), Some("Enter"));
+        assert_eq!(key_name("ctrl"), Some("ControlLeft"));
+        assert_eq!(key_name("numpad7"), Some("Numpad7"));
+        assert_eq!(key_name("KeyQ"), Some("Q"));
+        assert_eq!(key_name("Digit1"), Some("1"));
+        assert_eq!(key_name("ArrowUp"), Some("Up"));
+        assert_eq!(key_name("Key"), None);
+        assert_eq!(key_name("Hyper"), None);
+    }
+
+    #[test]
+    fn unknown_keys_suggest_the_closest_name() {
+        assert_eq!(
+            unknown_key_message("Spcae"),
+            "unknown key name 'Spcae', did you mean 'Space'?"
+        );
+        assert_eq!(
+            unknown_key_message("numpad77"),
+            "unknown key name 'numpad77', did you mean 'Numpad7'?"
+        );
+        assert!(!unknown_key_message("Hyperdrive").contains("did you mean"));
+    }
+
+    #[cfg(feature = "config")]
+    mod config {
+        use std::path::Path;
+
+        use super::*;
+
+        fn parse(text: &str) -> Result<Keymap, String> {
+            Keymap::from_toml(text, Path::new("roms/brix.ch8"))
+        }
+
+        #[test]
+        fn keys_replace_the_defaults_of_their_chip8_key() {
+            let keymap = parse(
+                r#"
+                layout = "numpad"
+
+                [keys]
+                5 = ["Up", "W"]
+                0xA = "Space"
+
+                [hotkeys]
+                pause = "F1"
+                "#,
+            )
+            .unwrap();
+            assert_eq!(keymap.action("Up"), Some(Action::Key(5)));
+            assert_eq!(keymap.action("W"), Some(Action::Key(5)));
+            assert_eq!(keymap.action("Numpad5"), None);
+            assert_eq!(keymap.action("Space"), Some(Action::Key(0xA)));
+            assert_eq!(keymap.action("Numpad1"), Some(Action::Key(1)));
+            assert_eq!(keymap.action("F1"), Some(Action::Hotkey(Hotkey::Pause)));
+            assert_eq!(keymap.action("P"), None);
+        }
+
+        #[test]
+        fn rom_sections_only_apply_to_their_rom() {
+            let text = r#"
+                [keys]
+                4 = "A"
+
+                [rom."brix.ch8"]
+                layout = "numpad"
+
+                [rom."brix.ch8".keys]
+                4 = "Left"
+
+                [rom."brix.ch8".gamepad]
+                6 = "right"
+            "#;
+            let keymap = parse(text).unwrap();
+            assert_eq!(keymap.action("Left"), Some(Action::Key(4)));
+            assert_eq!(keymap.action("A"), None);
+            assert_eq!(keymap.action("Numpad7"), Some(Action::Key(7)));
+            assert_eq!(keymap.gamepad().key(Button::Right), Some(6));
+            assert_eq!(keymap.gamepad().key(Button::South), None);
+
+            let other = Keymap::from_toml(text, Path::new("roms/pong.ch8")).unwrap();
+            assert_eq!(other.action("A"), Some(Action::Key(4)));
+            assert_eq!(other.action("Left"), None);
+            assert_eq!(other.action("Q"), None);
+            assert_eq!(other.gamepad().key(Button::South), Some(6));
+        }
+
+        #[test]
+        fn chip8_keys_are_single_hex_digits() {
+            for key in ["0", "f", "F", "0xF", "0x0f"] {
+                assert!(parse_chip8_key("keys", key).is_ok(), "{} should parse", key);
+            }
+            for key in ["+1", "-1", "", "0x", "0x0x1", "10", "G", " 1"] {
+                assert_eq!(
+                    parse_chip8_key("keys", key),
+                    Err(format!(
+                        "[keys] unknown CHIP-8 key '{}', expected a hex digit 0-F",
+                        key
+                    ))
+                );
+            }
+        }
+
+        #[test]
+        fn invalid_keymaps_are_rejected() {
+            let cases = [
+                ("[keys]\n\"+1\" = \"A\"", "[keys] unknown CHIP-8 key '+1'"),
+                ("[keys]\n1 = \"Spcae\"", "did you mean 'Space'?"),
+                (
+                    "[keys]\n1 = \"A\"\n2 = \"A\"",
+                    "host key 'A' is bound more than once",
+                ),
+                ("[hotkeys]\njump = \"J\"", "unknown hotkey 'jump'"),
+                ("layout = \"dvorak\"", "dvorak"),
+                ("[gamepad]\n1 = \"trigger\"", "[gamepad]"),
+                (
+                    "[rom.\"brix.ch8\".keys]\nG = \"A\"",
+                    "[rom.\"brix.ch8\".keys] unknown CHIP-8 key 'G'",
+                ),
+                ("[keys", "TOML"),
+            ];
+            for (text, expected) in cases {
+                let err = parse(text)
+                    .err()
+                    .unwrap_or_else(|| panic!("{} parsed", text));
+                assert!(
+                    err.contains(expected),
+                    "{:?} does not mention {:?}",
+                    err,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+/// What completes an Fx0A wait for a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyWait {
+    /// A key has to be pressed and released again, like on the COSMAC VIP.
+    #[default]
+    Release,
+    /// The wait ends as soon as a key goes down, like on the SCHIP.
+    Press,
+}
+
+/// The 16-key hex keypad.
+///
+/// Frontends report every key going down or up, and the keypad keeps the edges needed
+/// by Fx0A itself. Only keys pressed after a wait started can end it, so a key still
+/// held from earlier input does not immediately satisfy the next Fx0A.
+#[derive(Debug, Clone, Default)]
+pub struct Keypad {
+    /// Bitmask of the keys currently held down.
+    down: u16,
+    mode: KeyWait,
+    waiting: bool,
+    /// Key pressed during the current wait, waiting to be released.
+    candidate: Option<u8>,
+    /// Key that ended the current wait, picked up by the next Fx0A.
+    result: Option<u8>,
+}
+
+impl Keypad {
+    pub fn new(mode: KeyWait) -> Self {
+        Self {
+            mode,
+            ..Self::default()
+        }
+    }
+
+    pub fn mode(&self) -> KeyWait {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: KeyWait) {
+        self.mode = mode;
+    }
+
+    /// Marks `key` as held down. Only the low nibble is used.
+    pub fn press(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.down |= 1 << key;
+        if self.waiting && self.result.is_none() && self.candidate.is_none() {
+            match self.mode {
+                KeyWait::Release => self.candidate = Some(key),
+                KeyWait::Press => self.result = Some(key),
+            }
+        }
+    }
+
+    /// Marks `key` as released. Only the low nibble is used.
+    pub fn release(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.down &= !(1 << key);
+        if self.waiting && self.candidate == Some(key) {
+            self.candidate = None;
+            self.result = Some(key);
+        }
+    }
+
+    pub fn is_down(&self, key: u8) -> bool {
+        self.down & (1 << (key & 0xF)) != 0
+    }
+
+    /// Bitmask of the keys currently held down, key 0 in the lowest bit.
+    pub fn held(&self) -> u16 {
+        self.down
+    }
+
+    /// Whether an Fx0A is currently blocked on input.
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Polled by Fx0A: starts a wait, or returns the key that ended it.
+    pub fn poll_wait(&mut self) -> Option<u8> {
+        if !self.waiting {
+            self.waiting = true;
+            self.candidate = None;
+            self.result = None;
+            return None;
+        }
+        let key = self.result.take()?;
+        self.waiting = false;
+        Some(key)
+    }
+
+    /// Releases every key and abandons a pending wait.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.mode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_mode_waits_for_a_press_and_release() {
+        let mut keypad = Keypad::new(KeyWait::Release);
+        assert_eq!(keypad.poll_wait(), None);
+        keypad.press(5);
+        assert_eq!(keypad.poll_wait(), None);
+        assert!(keypad.is_waiting());
+        keypad.release(5);
+        assert_eq!(keypad.poll_wait(), Some(5));
+        assert!(!keypad.is_waiting());
+    }
+
+    #[test]
+    fn keys_held_before_the_wait_do_not_count() {
+        for mode in [KeyWait::Release, KeyWait::Press] {
+            let mut keypad = Keypad::new(mode);
+            keypad.press(3);
+            assert_eq!(keypad.poll_wait(), None);
+            assert_eq!(keypad.poll_wait(), None, "{:?}", mode);
+            keypad.release(3);
+            assert_eq!(keypad.poll_wait(), None, "{:?}", mode);
+            keypad.press(4);
+            keypad.release(4);
+            assert_eq!(keypad.poll_wait(), Some(4), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn press_mode_completes_on_key_down() {
+        let mut keypad = Keypad::new(KeyWait::Press);
+        assert_eq!(keypad.poll_wait(), None);
+        keypad.press(0xA);
+        assert_eq!(keypad.poll_wait(), Some(0xA));
+        assert!(keypad.is_down(0xA));
+    }
+
+    #[test]
+    fn only_the_first_key_pressed_ends_the_wait() {
+        let mut keypad = Keypad::new(KeyWait::Release);
+        keypad.poll_wait();
+        keypad.press(1);
+        keypad.press(2);
+        keypad.release(2);
+        assert_eq!(keypad.poll_wait(), None);
+        keypad.release(1);
+        assert_eq!(keypad.poll_wait(), Some(1));
+    }
+}
@@ -1,2 +1,45 @@
-pub mod frontend;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "batch")]
+pub mod batch;
+#[cfg(feature = "capture")]
+pub mod capture;
+#[cfg(feature = "std")]
+pub mod cfg;
 pub mod chip8;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod decompile;
+pub mod disasm;
+#[cfg(feature = "std")]
+pub mod emulator;
+pub mod env;
+#[cfg(feature = "gui")]
+pub mod frontend;
+#[cfg(feature = "std")]
+pub mod gamepad;
+#[cfg(feature = "jit")]
+pub mod jit;
+#[cfg(feature = "std")]
+pub mod keymap;
+pub mod keypad;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod recompile;
+pub mod render;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod trace_diff;
+#[cfg(feature = "tui")]
+pub mod tui;
@@ -0,0 +1,660 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::cfg::{self, Cfg, Line};
+use crate::disasm::Instruction;
+
+/// Most instructions followed after one that leaves I or VF behind, looking for code using it.
+const LOOKAHEAD: usize = 64;
+
+/// A behaviour that differs between CHIP-8 platforms, named as in the common quirks tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Quirk {
+    /// 8xy1, 8xy2 and 8xy3 reset VF to 0.
+    VfReset,
+    /// 8xy6 and 8xyE shift Vx in place instead of shifting Vy into Vx.
+    Shifting,
+    /// Fx55 and Fx65 leave I pointing past the last register instead of where it was.
+    Memory,
+    /// Bnnn jumps to xnn plus Vx instead of nnn plus V0.
+    Jumping,
+    /// Sprites are cut off at the edges of the screen instead of wrapping around.
+    Clipping,
+}
+
+impl Quirk {
+    pub const ALL: [Quirk; 5] = [
+        Quirk::VfReset,
+        Quirk::Shifting,
+        Quirk::Memory,
+        Quirk::Jumping,
+        Quirk::Clipping,
+    ];
+
+    /// The name used for this quirk in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Quirk::VfReset => "vf-reset",
+            Quirk::Shifting => "shifting",
+            Quirk::Memory => "memory",
+            Quirk::Jumping => "jumping",
+            Quirk::Clipping => "clipping",
+        }
+    }
+}
+
+impl fmt::Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Which quirks a platform has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    pub vf_reset: bool,
+    pub shifting: bool,
+    pub memory: bool,
+    pub jumping: bool,
+    pub clipping: bool,
+}
+
+impl Quirks {
+    pub fn get(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::VfReset => self.vf_reset,
+            Quirk::Shifting => self.shifting,
+            Quirk::Memory => self.memory,
+            Quirk::Jumping => self.jumping,
+            Quirk::Clipping => self.clipping,
+        }
+    }
+}
+
+/// Writes the quirks as `vf-reset=on shifting=off ...`.
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, quirk) in Quirk::ALL.into_iter().enumerate() {
+            let setting = if self.get(quirk) { "on" } else { "off" };
+            let separator = if index == 0 { "" } else { " " };
+            write!(f, "{}{}={}", separator, quirk, setting)?;
+        }
+        Ok(())
+    }
+}
+
+/// A machine or interpreter CHIP-8 programs are written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    CosmacVip,
+    Schip,
+    XoChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 3] = [Platform::CosmacVip, Platform::Schip, Platform::XoChip];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::CosmacVip => "COSMAC VIP",
+            Platform::Schip => "SCHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::CosmacVip => Quirks {
+                vf_reset: true,
+                shifting: false,
+                memory: true,
+                jumping: false,
+                clipping: true,
+            },
+            Platform::Schip => Quirks {
+                vf_reset: false,
+                shifting: true,
+                memory: false,
+                jumping: true,
+                clipping: true,
+            },
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                shifting: false,
+                memory: true,
+                jumping: false,
+                clipping: false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An instruction that behaves differently depending on the platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub address: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    /// The quirk the instruction depends on, if any.
+    pub quirk: Option<Quirk>,
+    /// The setting of `quirk` the code around the instruction seems written for, if it tells.
+    pub expects: Option<bool>,
+    /// The only platform the instruction runs on, if any.
+    pub requires: Option<Platform>,
+    pub message: String,
+}
+
+/// Scans a ROM for code that depends on quirks, without running it.
+///
+/// Only the code found through the ROM's [`Cfg`] is scanned, and registers are only known
+/// when the straight-line code before an instruction loads them with a constant. The checks
+/// cover shifts of one register into another, Fx55 and Fx65 followed by code that uses I
+/// before setting it, Bnnn with a register other than V0 in its high nibble, sprites drawn
+/// at constant coordinates across an edge of the screen, 8xy1 to 8xyE storing their result
+/// in VF, VF read after a logic instruction, and 0nnn calls.
+///
+/// 8xy4, 8xy5 and 8xy7 with VF as Vy are not flagged: all three platforms read both
+/// operands before setting the flag, so only interpreters that get the order wrong differ.
+pub struct Linter {
+    findings: Vec<Finding>,
+}
+
+impl Linter {
+    /// Lints `rom`, loaded at 200.
+    pub fn new(rom: &[u8]) -> Self {
+        let cfg = Cfg::new(rom);
+        let lines: BTreeMap<u16, Line> = cfg
+            .blocks()
+            .values()
+            .flat_map(|block| &block.lines)
+            .map(|&line| (line.address, line))
+            .collect();
+        let mut findings = Vec::new();
+        for line in lines.values() {
+            let finding = |quirk, expects, message| Finding {
+                address: line.address,
+                opcode: line.opcode,
+                instruction: line.instruction,
+                quirk,
+                expects,
+                requires: None,
+                message,
+            };
+            match line.instruction {
+                Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } if x != y => {
+                    findings.push(finding(
+                        Some(Quirk::Shifting),
+                        shift_expects(&cfg, line.address, x, y),
+                        format!(
+                            "shifts V{:X} into V{:X} on the COSMAC VIP, but V{:X} in place on the SCHIP",
+                            y, x, x
+                        ),
+                    ))
+                }
+                Instruction::Store { x } | Instruction::Load { x } => {
+                    let used = after(&lines, line.address).into_iter().find_map(|next| {
+                        match next.instruction {
+                            Instruction::LoadI { .. } | Instruction::Font { .. } => Some(None),
+                            Instruction::Store { .. } | Instruction::Load { .. } => {
+                                Some(Some((next.address, Some(true))))
+                            }
+                            Instruction::Draw { .. } | Instruction::Bcd { .. } => {
+                                Some(Some((next.address, Some(false))))
+                            }
+                            Instruction::AddI { .. } => Some(Some((next.address, None))),
+                            _ => None,
+                        }
+                    });
+                    if let Some(Some((address, expects))) = used {
+                        findings.push(finding(
+                            Some(Quirk::Memory),
+                            expects,
+                            format!(
+                                "I is used at {:03X} while still past V{:X} on the COSMAC VIP, but unchanged on the SCHIP",
+                                address, x
+                            ),
+                        ))
+                    }
+                }
+                Instruction::JumpV0 { nnn } if nnn >> 8 != 0 => {
+                    let x = (nnn >> 8) as u8;
+                    let expects = before(&cfg, line.address).find_map(|line| {
+                        if cfg::writes(line.instruction, 0) {
+                            Some(false)
+                        } else if cfg::writes(line.instruction, x) {
+                            Some(true)
+                        } else {
+                            None
+                        }
+                    });
+                    findings.push(finding(
+                        Some(Quirk::Jumping),
+                        expects,
+                        format!(
+                            "jumps to {:03X} plus V0 on the COSMAC VIP, but {:03X} plus V{:X} on the SCHIP",
+                            nnn, nnn, x
+                        ),
+                    ))
+                }
+                Instruction::Draw { x, y, n } => {
+                    let column = constant(&cfg, line.address, x).map(|column| column % 64);
+                    let row = constant(&cfg, line.address, y).map(|row| row % 32);
+                    let across = column.is_some_and(|column| column > 64 - 8)
+                        || row.is_some_and(|row| row + n > 32);
+                    if across {
+                        let at = |value: Option<u8>| value.map_or("?".to_string(), |v| v.to_string());
+                        findings.push(finding(
+                            Some(Quirk::Clipping),
+                            None,
+                            format!(
+                                "draws across the edge of the screen at {},{}, where sprites wrap around on the XO-CHIP",
+                                at(column),
+                                at(row)
+                            ),
+                        ))
+                    }
+                }
+                Instruction::Or { x: 0xF, .. }
+                | Instruction::And { x: 0xF, .. }
+                | Instruction::Xor { x: 0xF, .. } => findings.push(finding(
+                    Some(Quirk::VfReset),
+                    None,
+                    "stores the result in VF, which the COSMAC VIP then resets to 0".to_string(),
+                )),
+                Instruction::Or { .. } | Instruction::And { .. } | Instruction::Xor { .. } => {
+                    let read = after(&lines, line.address).into_iter().find_map(|next| {
+                        if reads(next.instruction, 0xF) {
+                            Some(Some(next.address))
+                        } else if cfg::writes(next.instruction, 0xF) && !is_logic(next.instruction)
+                        {
+                            Some(None)
+                        } else {
+                            None
+                        }
+                    });
+                    if let Some(Some(address)) = read {
+                        findings.push(finding(
+                            Some(Quirk::VfReset),
+                            None,
+                            format!(
+                                "VF is read at {:03X}, after the COSMAC VIP resets it to 0 here",
+                                address
+                            ),
+                        ))
+                    }
+                }
+                Instruction::Add { x: 0xF, .. }
+                | Instruction::Sub { x: 0xF, .. }
+                | Instruction::ShiftRight { x: 0xF, .. }
+                | Instruction::SubReverse { x: 0xF, .. }
+                | Instruction::ShiftLeft { x: 0xF, .. } => findings.push(finding(
+                    None,
+                    None,
+                    "stores the result in VF, where the flag overwrites it, though some interpreters keep the result".to_string(),
+                )),
+                Instruction::Sys { nnn } => {
+                    let schip = (0x0C0..=0x0CF).contains(&nnn) || (0x0FB..=0x0FF).contains(&nnn);
+                    let (requires, message) = if schip {
+                        (Platform::Schip, "is a SCHIP instruction".to_string())
+                    } else {
+                        (
+                            Platform::CosmacVip,
+                            format!("calls COSMAC VIP machine code at {:03X}", nnn),
+                        )
+                    };
+                    findings.push(Finding {
+                        requires: Some(requires),
+                        ..finding(None, None, message)
+                    })
+                }
+                _ => (),
+            }
+        }
+        Self { findings }
+    }
+
+    /// Findings in address order.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// The quirks some code of the ROM depends on.
+    pub fn quirks(&self) -> BTreeSet<Quirk> {
+        self.findings
+            .iter()
+            .filter_map(|finding| finding.quirk)
+            .collect()
+    }
+
+    /// The platform the ROM seems written for, or `None` if it runs the same on all of them.
+    ///
+    /// A platform only the ROM's instructions run on wins. Otherwise every finding whose
+    /// code tells which setting of its quirk it expects counts for the platforms with that
+    /// setting and against the others, and ties go to the COSMAC VIP, the original.
+    pub fn recommend(&self) -> Option<Platform> {
+        if let Some(platform) = self.findings.iter().find_map(|finding| finding.requires) {
+            return Some(platform);
+        }
+        if self.quirks().is_empty() {
+            return None;
+        }
+        let score = |platform: Platform| -> i32 {
+            let quirks = platform.quirks();
+            self.findings
+                .iter()
+                .filter_map(|finding| Some((finding.quirk?, finding.expects?)))
+                .map(|(quirk, expects)| if quirks.get(quirk) == expects { 1 } else { -1 })
+                .sum()
+        };
+        Platform::ALL
+            .into_iter()
+            .rev()
+            .max_by_key(|&platform| score(platform))
+    }
+
+    /// Writes every finding with its disassembly, the quirks that matter and the platform
+    /// to pick.
+    pub fn write_report(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        for finding in &self.findings {
+            let quirk = finding
+                .quirk
+                .map_or(String::new(), |quirk| format!("{}: ", quirk));
+            writeln!(
+                out,
+                "{}:{:03X}  {:04X}  {:<16} {}{}",
+                name,
+                finding.address,
+                finding.opcode,
+                finding.instruction.to_string(),
+                quirk,
+                finding.message
+            )?;
+        }
+        if !self.findings.is_empty() {
+            writeln!(out)?;
+        }
+        let quirks: Vec<_> = self.quirks().iter().map(|quirk| quirk.name()).collect();
+        if quirks.is_empty() {
+            writeln!(out, "Quirks that matter: none")?;
+        } else {
+            writeln!(out, "Quirks that matter: {}", quirks.join(", "))?;
+        }
+        match self.recommend() {
+            Some(platform) => writeln!(
+                out,
+                "Recommended platform: {} ({})",
+                platform,
+                platform.quirks()
+            ),
+            None => writeln!(out, "Recommended platform: any"),
+        }
+    }
+}
+
+/// Whether a shift of Vy into Vx at `address` seems written for the shifting quirk: true if
+/// the code before it last sets Vx, false if it last sets Vy, and V0 as Vy counts as an
+/// assembler filling in an unused operand.
+fn shift_expects(cfg: &Cfg, address: u16, x: u8, y: u8) -> Option<bool> {
+    before(cfg, address)
+        .find_map(|line| {
+            if cfg::writes(line.instruction, y) {
+                Some(false)
+            } else if cfg::writes(line.instruction, x) {
+                Some(true)
+            } else {
+                None
+            }
+        })
+        .or((y == 0).then_some(true))
+}
+
+/// The constant the straight-line code before `address` last loads into `register`.
+fn constant(cfg: &Cfg, address: u16, register: u8) -> Option<u8> {
+    before(cfg, address)
+        .find(|line| cfg::writes(line.instruction, register))
+        .and_then(|line| match line.instruction {
+            Instruction::LoadImm { nn, .. } => Some(nn),
+            _ => None,
+        })
+}
+
+/// The instructions of the block of `address` before it, the closest first.
+fn before(cfg: &Cfg, address: u16) -> impl Iterator<Item = &Line> {
+    cfg.blocks()
+        .range(..=address)
+        .next_back()
+        .into_iter()
+        .flat_map(move |(_, block)| {
+            block
+                .lines
+                .iter()
+                .rev()
+                .skip_while(move |line| line.address >= address)
+        })
+}
+
+/// The instructions run after the one at `address` while control is certain, through
+/// straight-line code and 1nnn jumps.
+fn after(lines: &BTreeMap<u16, Line>, address: u16) -> Vec<Line> {
+    let mut result = Vec::new();
+    let mut seen = HashSet::new();
+    let mut next = lines.get(&address).and_then(following);
+    while let Some(address) = next {
+        if result.len() == LOOKAHEAD || !seen.insert(address) {
+            break;
+        }
+        let Some(&line) = lines.get(&address) else {
+            break;
+        };
+        result.push(line);
+        next = following(&line);
+    }
+    result
+}
+
+fn following(line: &Line) -> Option<u16> {
+    match line.instruction {
+        Instruction::Jump { nnn } => Some(nnn),
+        instruction if cfg::ends_block(instruction) => None,
+        _ => Some(line.address + 2),
+    }
+}
+
+fn is_logic(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Or { .. } | Instruction::And { .. } | Instruction::Xor { .. }
+    )
+}
+
+/// Whether `instruction` uses the value of `register`.
+fn reads(instruction: Instruction, register: u8) -> bool {
+    use Instruction::*;
+    match instruction {
+        SkipEqImm { x, .. }
+        | SkipNeImm { x, .. }
+        | AddImm { x, .. }
+        | SkipKey { x }
+        | SkipNotKey { x }
+        | SetDelay { x }
+        | SetSound { x }
+        | AddI { x }
+        | Font { x }
+        | Bcd { x } => x == register,
+        Move { y, .. } => y == register,
+        SkipEq { x, y }
+        | SkipNe { x, y }
+        | Or { x, y }
+        | And { x, y }
+        | Xor { x, y }
+        | Add { x, y }
+        | Sub { x, y }
+        | ShiftRight { x, y }
+        | SubReverse { x, y }
+        | ShiftLeft { x, y }
+        | Draw { x, y, .. } => x == register || y == register,
+        JumpV0 { .. } => register == 0,
+        // Stores V0 to Vx
+        Store { x } => register <= x,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assemble;
+
+    fn lint(opcodes: &[u16]) -> Linter {
+        Linter::new(&assemble(opcodes))
+    }
+
+    #[test]
+    fn portable_code_needs_no_platform() {
+        let linter = lint(&[0x6105, 0x8124, 0x1204]);
+        assert!(linter.findings().is_empty());
+        assert_eq!(linter.recommend(), None);
+    }
+
+    #[test]
+    fn quirks_without_evidence_fall_back_to_cosmac_vip() {
+        // OR VF, V1 does not tell which platform it expects, so they all score 0
+        let linter = lint(&[0x8F11, 0x1202]);
+        assert_eq!(linter.quirks(), BTreeSet::from([Quirk::VfReset]));
+        assert_eq!(linter.recommend(), Some(Platform::CosmacVip));
+    }
+
+    #[test]
+    fn shift_in_place_recommends_schip() {
+        // V1 was just set, so SHR V1, V2 means to shift V1
+        let linter = lint(&[0x6105, 0x8126, 0x1204]);
+        assert_eq!(linter.findings()[0].expects, Some(true));
+        assert_eq!(linter.recommend(), Some(Platform::Schip));
+    }
+
+    #[test]
+    fn tie_between_cosmac_vip_and_xo_chip_goes_to_cosmac_vip() {
+        // V2 was just set, so SHR V1, V2 means to shift V2 into V1, as on both of them
+        let linter = lint(&[0x6205, 0x8126, 0x1204]);
+        assert_eq!(linter.findings()[0].expects, Some(false));
+        assert_eq!(linter.recommend(), Some(Platform::CosmacVip));
+    }
+
+    #[test]
+    fn platform_instructions_win_over_quirks() {
+        // Evidence for the COSMAC VIP, then the SCHIP's high resolution mode
+        let linter = lint(&[0x6205, 0x8126, 0x00FF]);
+        assert_eq!(linter.recommend(), Some(Platform::Schip));
+        // Evidence for the SCHIP, then COSMAC VIP machine code
+        let linter = lint(&[0x6105, 0x8126, 0x0123]);
+        assert_eq!(linter.recommend(), Some(Platform::CosmacVip));
+    }
+
+    #[test]
+    fn fx55_then_using_i_needs_the_memory_quirk() {
+        // 200: LD I, 300
+        // 202: LD [I], V1
+        // 204: DRW V0, V1, 5
+        // 206: JP 206
+        let linter = lint(&[0xA300, 0xF155, 0xD015, 0x1206]);
+        let finding = &linter.findings()[0];
+        assert_eq!(
+            (finding.address, finding.quirk, finding.expects),
+            (0x202, Some(Quirk::Memory), Some(false))
+        );
+        assert!(finding.message.starts_with("I is used at 204"));
+        // Loading again right after counts as expecting I past the registers
+        let linter = lint(&[0xA300, 0xF155, 0xF165, 0x1206]);
+        assert_eq!(linter.findings()[0].expects, Some(true));
+        assert_eq!(linter.findings().len(), 1);
+
+        // LD I, 300 before the sprite makes the quirk irrelevant
+        let linter = lint(&[0xA300, 0xF155, 0xA300, 0xD015, 0x1208]);
+        assert!(linter.findings().is_empty());
+    }
+
+    #[test]
+    fn bnnn_with_a_register_needs_the_jumping_quirk() {
+        // 200: LD V3, 5
+        // 202: JP V0, 30A
+        let linter = lint(&[0x6305, 0xB30A]);
+        let finding = &linter.findings()[0];
+        assert_eq!(
+            (finding.address, finding.quirk, finding.expects),
+            (0x202, Some(Quirk::Jumping), Some(true))
+        );
+        assert_eq!(linter.recommend(), Some(Platform::Schip));
+        // Setting V0 instead means the plain Bnnn
+        let linter = lint(&[0x6005, 0xB30A]);
+        assert_eq!(linter.findings()[0].expects, Some(false));
+        assert_eq!(linter.recommend(), Some(Platform::CosmacVip));
+
+        // A target below 100 reads the same either way
+        let linter = lint(&[0x6005, 0xB0FA]);
+        assert!(linter.findings().is_empty());
+    }
+
+    #[test]
+    fn sprites_across_an_edge_need_the_clipping_quirk() {
+        // 200: LD V0, 57
+        // 202: LD V1, 0
+        // 204: DRW V0, V1, 5
+        // 206: JP 206
+        let linter = lint(&[0x6039, 0x6100, 0xD015, 0x1206]);
+        let finding = &linter.findings()[0];
+        assert_eq!(
+            (finding.address, finding.quirk),
+            (0x204, Some(Quirk::Clipping))
+        );
+        assert!(finding.message.contains(" at 57,0,"));
+        // Five rows from row 28 run past the bottom
+        let linter = lint(&[0x6000, 0x611C, 0xD015, 0x1206]);
+        assert!(linter.findings()[0].message.contains(" at 0,28,"));
+
+        // 56,27 fits exactly, and unknown coordinates are not flagged
+        assert!(lint(&[0x6038, 0x611B, 0xD015, 0x1206])
+            .findings()
+            .is_empty());
+        assert!(lint(&[0xC03F, 0xC11F, 0xD015, 0x1206])
+            .findings()
+            .is_empty());
+    }
+
+    #[test]
+    fn vf_read_after_logic_needs_the_vf_reset_quirk() {
+        // 200: OR V1, V2
+        // 202: SNE VF, 1
+        // 204: JP 204
+        let linter = lint(&[0x8121, 0x4F01, 0x1204]);
+        let finding = &linter.findings()[0];
+        assert_eq!(
+            (finding.address, finding.quirk),
+            (0x200, Some(Quirk::VfReset))
+        );
+        assert!(finding.message.starts_with("VF is read at 202"));
+
+        // LD VF, 0 before the read sets VF the same way everywhere
+        let linter = lint(&[0x8121, 0x6F00, 0x4F01, 0x1206]);
+        assert!(linter.findings().is_empty());
+        // Another logic instruction leaves VF to the quirk, so the read still counts
+        let linter = lint(&[0x8121, 0x8232, 0x4F01, 0x1206]);
+        assert_eq!(
+            linter.findings()[0].message,
+            "VF is read at 204, after the COSMAC VIP resets it to 0 here"
+        );
+    }
+
+    #[test]
+    fn vf_as_a_source_is_not_flagged() {
+        // ADD V1, VF, SUB V1, VF and SUBN V1, VF read VF before setting it everywhere
+        let linter = lint(&[0x81F4, 0x81F5, 0x81F7, 0x1206]);
+        assert!(linter.findings().is_empty());
+    }
+}
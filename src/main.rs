@@ -1,36 +1,633 @@
-use chippers::{chip8::Chip8, frontend::App};
-use clap::Parser;
-use std::path::PathBuf;
-use winit::event_loop::{ControlFlow, EventLoop};
+use chippers::coverage::{Coverage, CoverageFormat};
+use chippers::disasm::OpClass;
+use chippers::movie::{Movie, MovieHeader, MovieRecorder};
+use chippers::trace::{AddressRange, TraceFilter, TraceFormat, Tracer};
+use chippers::{chip8::Chip8, emulator::Emulator, gamepad::Gamepad, keymap::Keymap};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(required = true)]
+    rom_path: Option<PathBuf>,
+
+    /// Run in the terminal instead of opening a window
+    #[cfg(feature = "tui")]
+    #[arg(long)]
+    tui: bool,
+
+    #[cfg(feature = "gui")]
+    #[command(flatten)]
+    gui: gui::GuiArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    frames_per_second: u32,
+
+    #[arg(short, long, default_value_t = 800)]
+    cycles_per_second: u32,
+
+    /// Key binding config, by default chippers/keymap.toml in the user config directory
+    #[arg(long, value_name = "FILE")]
+    keymap: Option<PathBuf>,
+
+    /// Seed for the random number generator, random by default
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Record all input to a movie file that can be played back deterministically
+    #[arg(long, value_name = "FILE")]
+    record_movie: Option<PathBuf>,
+
+    /// Store a state hash in the movie every N frames to detect desyncs, 0 for none
+    #[arg(long, value_name = "N", default_value_t = 60)]
+    movie_hash_interval: u64,
+
+    /// Play back a movie file, using its seed, quirks and speed
+    #[arg(long, value_name = "FILE", conflicts_with = "record_movie")]
+    play_movie: Option<PathBuf>,
+
+    /// Play the movie to its end without any display and report whether it desynced
+    #[arg(long, requires = "play_movie")]
+    headless: bool,
+
+    /// Log every executed instruction with the registers and memory it changed
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Trace file format: text or binary
+    #[arg(long, default_value = "text", requires = "trace")]
+    trace_format: TraceFormat,
+
+    /// Only trace instructions at these addresses, e.g. 200-2FF (repeatable)
+    #[arg(long, value_name = "RANGE", value_delimiter = ',', requires = "trace")]
+    trace_range: Vec<AddressRange>,
+
+    /// Only trace these opcode classes: display, flow, skip, load, arithmetic, keypad,
+    /// timer, memory or unknown
+    #[arg(long, value_name = "CLASS", value_delimiter = ',', requires = "trace")]
+    trace_class: Vec<OpClass>,
+
+    /// Write which ROM bytes were executed, read or written: an annotated disassembly, or
+    /// an HTML page or lcov tracefile for .html and .info files
+    #[arg(long, value_name = "FILE")]
+    coverage: Option<PathBuf>,
+
+    /// Compile the ROM to native code as it runs instead of interpreting it
+    #[cfg(feature = "jit")]
+    #[arg(long, conflicts_with_all = ["trace", "coverage"])]
+    jit: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two instruction traces and report where they first diverge
+    ///
+    /// Traces can be chippers traces in either format, or logs of other emulators with
+    /// one instruction per line: register dumps such as `PC:0200 OP:00E0 I:0000 V0:00`, or
+    /// listings starting with the address and opcode such as `0200: 00E0`.
+    TraceDiff { a: PathBuf, b: PathBuf },
+
+    /// Run a ROM without a display and report where it spends its instructions
+    Profile(ProfileArgs),
+
+    /// Translate a ROM into a Rust module that runs it natively
+    ///
+    /// The module runs on the `Chip8` of an emulator that loaded the ROM, in place of the
+    /// interpreter: hand its `Recompiled` backend to `Emulator::set_backend`.
+    Recompile {
+        rom_path: PathBuf,
+
+        /// File to write the module to, standard output by default
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Decompile a ROM into structured code for the Octo assembler
+    ///
+    /// Loops, ifs and subroutines are recovered from the jumps and skips of the ROM, and the
+    /// output assembles back into the same bytes.
+    Decompile {
+        rom_path: PathBuf,
+
+        /// File to write the code to, standard output by default
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Scan a ROM for code that behaves differently across CHIP-8 platforms
+    ///
+    /// Reports the quirks the ROM depends on and the platform it seems written for, without
+    /// running it.
+    Lint { rom_path: PathBuf },
+
+    /// Write the control-flow graph of a ROM as Graphviz DOT to standard output
+    ///
+    /// Blocks are grouped by subroutine, with a node for each range of data. Render it with
+    /// `chippers cfg rom.ch8 | dot -Tsvg > rom.svg`.
+    Cfg {
+        rom_path: PathBuf,
+
+        /// Write the call graph between subroutines instead
+        #[arg(long)]
+        calls: bool,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct ProfileArgs {
     rom_path: PathBuf,
 
-    #[arg(short, long, default_value_t = 16)]
-    scale: u32,
+    /// Number of frames to run
+    #[arg(long, default_value_t = 3600)]
+    frames: u64,
 
-    #[arg(short, long, default_value_t = 60)]
+    #[arg(
+        short,
+        long,
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
     frames_per_second: u32,
 
     #[arg(short, long, default_value_t = 800)]
     cycles_per_second: u32,
+
+    /// Seed for the random number generator
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Play back the input of a movie, using its seed, quirks and speed
+    #[arg(long, value_name = "FILE")]
+    movie: Option<PathBuf>,
+
+    /// Write the call stacks in the folded format of flamegraph tools
+    #[arg(long, value_name = "FILE")]
+    folded: Option<PathBuf>,
+}
+
+/// The emulator as run by the command line, tracing and tracking coverage when asked to.
+type CliEmulator = Emulator<(Option<Tracer>, Option<Coverage>)>;
+
+/// Where the command line writes what it collected while emulating.
+struct Reports {
+    coverage: Option<PathBuf>,
+    rom_name: String,
+}
+
+impl Reports {
+    /// Writes the trace and coverage, if any. Done before reporting other errors, as both
+    /// are most useful when something went wrong.
+    fn finish(&self, emulator: CliEmulator) {
+        let (tracer, coverage) = emulator.into_observer();
+        if let Some(Err(err)) = tracer.map(Tracer::finish) {
+            eprintln!("Could not write trace: {}", err);
+        }
+        if let (Some(coverage), Some(path)) = (coverage, &self.coverage) {
+            let format = CoverageFormat::from_path(path);
+            let result = File::create(path).and_then(|file| {
+                let mut out = BufWriter::new(file);
+                coverage.write(&mut out, format, &self.rom_name)?;
+                out.flush()
+            });
+            if let Err(err) = result {
+                eprintln!("Could not write coverage {}: {}", path.display(), err);
+            }
+        }
+    }
 }
 
 fn main() {
     let args = Cli::parse();
+    match &args.command {
+        Some(Command::TraceDiff { a, b }) => trace_diff(a, b),
+        Some(Command::Profile(args)) => profile(args),
+        Some(Command::Recompile { rom_path, output }) => recompile(rom_path, output.as_deref()),
+        Some(Command::Cfg { rom_path, calls }) => cfg(rom_path, *calls),
+        Some(Command::Decompile { rom_path, output }) => decompile(rom_path, output.as_deref()),
+        Some(Command::Lint { rom_path }) => lint(rom_path),
+        None => (),
+    }
+    let rom_path = args.rom_path.clone().expect("clap requires a ROM");
+
+    let keymap = match &args.keymap {
+        Some(path) => Keymap::load(path, &rom_path),
+        None => Keymap::load_default(&rom_path),
+    };
+    let keymap =
+        keymap.unwrap_or_else(|err| Cli::command().error(ErrorKind::InvalidValue, err).exit());
+
+    let (rom, _) = read_rom(&rom_path);
+
+    let tracer = args.trace.as_ref().map(|path| {
+        let filter = TraceFilter {
+            ranges: args.trace_range.clone(),
+            classes: args.trace_class.clone(),
+        };
+        Tracer::create(path, args.trace_format, filter).unwrap_or_else(|err| {
+            fail(format!(
+                "Could not create trace {}: {}",
+                path.display(),
+                err
+            ))
+        })
+    });
+
+    let coverage = args.coverage.as_ref().map(|_| Coverage::new(&rom));
+    let observers = (tracer, coverage);
+    let reports = Reports {
+        coverage: args.coverage.clone(),
+        rom_name: rom_path.display().to_string(),
+    };
+
+    let mut movie_frames = 0;
+    let mut emulator = match &args.play_movie {
+        Some(path) => {
+            let (movie, chip8) = Movie::load(path)
+                .and_then(|movie| {
+                    let chip8 = movie.header.chip8(&rom)?;
+                    Ok((movie, chip8))
+                })
+                .unwrap_or_else(|err| {
+                    fail(format!("Could not play movie {}: {}", path.display(), err))
+                });
+            let header = &movie.header;
+            let mut emulator = Emulator::with_observer(
+                chip8,
+                header.frames_per_second,
+                header.cycles_per_second,
+                observers,
+            );
+            movie_frames = movie.end_frame();
+            emulator.play_movie(movie);
+            emulator
+        }
+        None => {
+            let seed = args.seed.unwrap_or_else(rand::random);
+            let mut chip8 = Chip8::with_seed(seed);
+            chip8.load_bytes(&rom);
+            let mut emulator = Emulator::with_observer(
+                chip8,
+                args.frames_per_second,
+                args.cycles_per_second,
+                observers,
+            );
+            if let Some(path) = &args.record_movie {
+                let header = MovieHeader::new(
+                    &rom,
+                    seed,
+                    emulator.chip8(),
+                    args.frames_per_second,
+                    args.cycles_per_second,
+                    args.movie_hash_interval,
+                );
+                match MovieRecorder::create(path, &header) {
+                    Ok(recorder) => emulator.record_movie(recorder),
+                    Err(err) => fail(format!(
+                        "Could not record movie {}: {}",
+                        path.display(),
+                        err
+                    )),
+                }
+            }
+            emulator
+        }
+    };
+
+    #[cfg(feature = "jit")]
+    if args.jit {
+        match chippers::jit::Jit::new() {
+            Ok(jit) => emulator.set_backend(Box::new(jit)),
+            Err(err) => fail(format!("Could not start the JIT: {}", err)),
+        }
+    }
 
-    let mut chip8 = Chip8::new();
-    chip8.load_rom(args.rom_path);
+    if args.headless {
+        run_headless(emulator, movie_frames, &reports);
+        return;
+    }
 
-    let event_loop = EventLoop::new().unwrap();
-    event_loop.set_control_flow(ControlFlow::Poll);
-    let mut app = App::new(
-        &mut chip8,
-        args.scale,
-        args.frames_per_second,
-        args.cycles_per_second,
+    if let Some(gamepad) = Gamepad::connect(keymap.gamepad().clone()) {
+        emulator.attach_gamepad(gamepad);
+    }
+
+    #[cfg(feature = "tui")]
+    if args.tui {
+        run_tui(emulator, keymap, &reports);
+        return;
+    }
+
+    #[cfg(feature = "gui")]
+    gui::run(emulator, keymap, args.gui, &reports);
+
+    #[cfg(not(feature = "gui"))]
+    {
+        let _ = (emulator, keymap, reports);
+        eprintln!("chippers was built without the gui feature, use --tui to run in the terminal");
+        std::process::exit(1);
+    }
+}
+
+fn fail(message: impl Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// Reads the ROM at `path`, exiting if it cannot be read, along with its file name for
+/// reports.
+fn read_rom(path: &Path) -> (Vec<u8>, String) {
+    let rom = std::fs::read(path)
+        .unwrap_or_else(|err| fail(format!("Could not read ROM {}: {}", path.display(), err)));
+    let name = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into(),
+    );
+    (rom, name)
+}
+
+/// Compares two traces, exiting with status 1 if they diverge.
+fn trace_diff(a: &Path, b: &Path) -> ! {
+    use chippers::trace_diff::{self, StepReader};
+
+    let open = |path: &Path| {
+        StepReader::open(path)
+            .unwrap_or_else(|err| fail(format!("Could not read trace {}: {}", path.display(), err)))
+    };
+    let diff = trace_diff::diff(open(a), open(b))
+        .unwrap_or_else(|err| fail(format!("Could not compare traces: {}", err)));
+    let names = [a.to_string_lossy(), b.to_string_lossy()];
+    diff.write_report(&mut std::io::stdout(), [&names[0], &names[1]])
+        .unwrap_or_else(|err| fail(err));
+    std::process::exit(if diff.divergence.is_some() { 1 } else { 0 });
+}
+
+/// Runs a ROM headless under the profiler and prints the report.
+fn profile(args: &ProfileArgs) -> ! {
+    use chippers::emulator::Headless;
+    use chippers::profile::Profiler;
+
+    let (rom, name) = read_rom(&args.rom_path);
+    let mut emulator = match &args.movie {
+        Some(path) => {
+            let (movie, chip8) = Movie::load(path)
+                .and_then(|movie| {
+                    let chip8 = movie.header.chip8(&rom)?;
+                    Ok((movie, chip8))
+                })
+                .unwrap_or_else(|err| {
+                    fail(format!("Could not play movie {}: {}", path.display(), err))
+                });
+            let header = &movie.header;
+            let mut emulator = Emulator::with_observer(
+                chip8,
+                header.frames_per_second,
+                header.cycles_per_second,
+                Profiler::new(),
+            );
+            emulator.play_movie(movie);
+            emulator
+        }
+        None => {
+            let mut chip8 = Chip8::with_seed(args.seed);
+            chip8.load_bytes(&rom);
+            Emulator::with_observer(
+                chip8,
+                args.frames_per_second,
+                args.cycles_per_second,
+                Profiler::new(),
+            )
+        }
+    };
+    if let Err(err) = emulator.run(&mut Headless::new(args.frames)) {
+        fail(format!("Emulation stopped: {}", err));
+    }
+
+    let frames = emulator.frame();
+    let profiler = emulator.into_observer();
+    println!(
+        "Profile of {} over {} frames, {} instructions",
+        name,
+        frames,
+        profiler.instructions()
+    );
+    println!();
+    profiler
+        .write_report(&mut std::io::stdout())
+        .unwrap_or_else(|err| fail(err));
+    if let Some(path) = &args.folded {
+        let result = std::fs::File::create(path).and_then(|file| {
+            let mut out = std::io::BufWriter::new(file);
+            profiler.write_folded(&mut out)?;
+            std::io::Write::flush(&mut out)
+        });
+        if let Err(err) = result {
+            fail(format!("Could not write {}: {}", path.display(), err));
+        }
+    }
+    std::process::exit(0);
+}
+
+/// Writes the Rust module of a ROM to `output`, or standard output.
+fn recompile(rom_path: &Path, output: Option<&Path>) -> ! {
+    use chippers::recompile::Recompiler;
+
+    let (rom, name) = read_rom(rom_path);
+    let recompiler = Recompiler::new(&rom);
+    let result = match output {
+        Some(path) => File::create(path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            recompiler.write_rust(&mut out, &name)?;
+            out.flush()
+        }),
+        None => recompiler.write_rust(&mut std::io::stdout().lock(), &name),
+    };
+    if let Err(err) = result {
+        fail(format!("Could not write the module: {}", err));
+    }
+    eprintln!(
+        "Recompiled {} instructions in {} blocks",
+        recompiler.instructions(),
+        recompiler.blocks()
+    );
+    std::process::exit(0);
+}
+
+/// Writes the control-flow graph or call graph of a ROM to standard output.
+fn cfg(rom_path: &Path, calls: bool) -> ! {
+    use chippers::cfg::Cfg;
+
+    let (rom, name) = read_rom(rom_path);
+    let cfg = Cfg::new(&rom);
+    let mut out = std::io::stdout().lock();
+    let result = if calls {
+        cfg.write_call_graph_dot(&mut out, &name)
+    } else {
+        cfg.write_dot(&mut out, &name)
+    };
+    if let Err(err) = result {
+        fail(format!("Could not write the graph: {}", err));
+    }
+    eprintln!(
+        "{} instructions in {} blocks, {} subroutines, {} ranges of data",
+        cfg.instructions(),
+        cfg.blocks().len(),
+        cfg.subroutines().len(),
+        cfg.data().len()
     );
+    for address in cfg.unresolved_jumps() {
+        eprintln!("Could not find the targets of the jump at {:03X}", address);
+    }
+    std::process::exit(0);
+}
+
+/// Writes the Octo source of a ROM to `output`, or standard output.
+fn decompile(rom_path: &Path, output: Option<&Path>) -> ! {
+    use chippers::decompile::Decompiler;
+
+    let (rom, name) = read_rom(rom_path);
+    let decompiler = Decompiler::new(&rom);
+    let result = match output {
+        Some(path) => File::create(path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            decompiler.write_octo(&mut out, &name)?;
+            out.flush()
+        }),
+        None => decompiler.write_octo(&mut std::io::stdout().lock(), &name),
+    };
+    if let Err(err) = result {
+        fail(format!("Could not write the code: {}", err));
+    }
+    let (loops, ifs) = decompiler.structures();
+    eprintln!(
+        "Decompiled {} instructions with {} loops and {} ifs",
+        decompiler.instructions(),
+        loops,
+        ifs
+    );
+    std::process::exit(0);
+}
+
+/// Reports the quirk-dependent code of a ROM on standard output.
+fn lint(rom_path: &Path) -> ! {
+    use chippers::lint::Linter;
+
+    let (rom, name) = read_rom(rom_path);
+    let linter = Linter::new(&rom);
+    if let Err(err) = linter.write_report(&mut std::io::stdout().lock(), &name) {
+        fail(format!("Could not write the report: {}", err));
+    }
+    std::process::exit(0);
+}
+
+/// Runs `frames` frames as fast as possible, to play back a movie to its end.
+fn run_headless(mut emulator: CliEmulator, frames: u64, reports: &Reports) {
+    use chippers::emulator::Headless;
+
+    let result = emulator.run(&mut Headless::new(frames));
+    reports.finish(emulator);
+    if let Err(err) = result {
+        fail(err);
+    }
+    println!("Movie played back without desync over {} frames", frames);
+}
+
+#[cfg(feature = "tui")]
+fn run_tui(mut emulator: CliEmulator, keymap: Keymap, reports: &Reports) {
+    use chippers::tui::TerminalApp;
+
+    let result = TerminalApp::new(keymap).and_then(|mut tui| emulator.run(&mut tui));
+    // The terminal is restored by now, so errors are readable
+    let result = result.and_then(|()| emulator.finish_movie());
+    reports.finish(emulator);
+    if let Err(err) = result {
+        fail(format!("Emulation stopped: {}", err));
+    }
+}
+
+#[cfg(feature = "gui")]
+mod gui {
+    use chippers::frontend::{App, Options};
+    use chippers::keymap::Keymap;
+    use chippers::render::Filter;
+    use clap::error::ErrorKind;
+    use clap::{Args, CommandFactory};
+    use std::path::PathBuf;
+    use winit::event_loop::{ControlFlow, EventLoop};
+
+    use super::{Cli, CliEmulator, Reports};
+
+    #[derive(Args, Debug)]
+    pub struct GuiArgs {
+        #[arg(
+            short,
+            long,
+            default_value_t = 16,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        scale: u32,
+
+        /// Post-processing filter: crt, lcd, scanlines or none
+        #[arg(long, default_value_t = Filter::None)]
+        filter: Filter,
+
+        /// Only scale the image by whole multiples of the native resolution
+        #[arg(long)]
+        integer_scaling: bool,
+
+        /// Save a PNG of frame N to OUT, using the current scale and filter
+        #[arg(long, num_args = 2, value_names = ["N", "OUT"])]
+        screenshot_at_frame: Vec<String>,
+
+        /// Record every emulated frame to a .gif or .y4m file (the record hotkey toggles it)
+        #[arg(long, value_name = "OUT")]
+        record: Option<PathBuf>,
+    }
+
+    pub fn run(emulator: CliEmulator, keymap: Keymap, args: GuiArgs, reports: &Reports) {
+        let screenshot_at = match args.screenshot_at_frame.as_slice() {
+            [frame, path] => match frame.parse::<u64>() {
+                Ok(frame) => Some((frame, PathBuf::from(path))),
+                Err(err) => Cli::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!("invalid frame number '{frame}' for --screenshot-at-frame: {err}"),
+                    )
+                    .exit(),
+            },
+            _ => None,
+        };
+
+        let event_loop = EventLoop::new().unwrap();
+        event_loop.set_control_flow(ControlFlow::Poll);
+        let mut app = App::new(
+            emulator,
+            Options {
+                scale: args.scale,
+                filter: args.filter,
+                integer_scaling: args.integer_scaling,
+                screenshot_at,
+                record: args.record,
+                keymap,
+            },
+        );
 
-    event_loop.run_app(&mut app).unwrap();
+        event_loop.run_app(&mut app).unwrap();
+        let error = app.error().map(ToString::to_string);
+        reports.finish(app.into_emulator());
+        if let Some(err) = error {
+            super::fail(format!("Emulation stopped: {}", err));
+        }
+    }
 }
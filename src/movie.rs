@@ -0,0 +1,425 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::chip8::{Chip8, Fnv1a};
+use crate::keypad::KeyWait;
+
+/// First line of every movie file.
+const MAGIC: &str = "chippers-movie 1";
+
+/// Everything needed to recreate the machine a movie starts from.
+///
+/// Movies always start from power-on: the ROM freshly loaded into a machine seeded with
+/// `seed`. `start_hash` is the state hash of that machine, so a playback that would start
+/// from anything else is caught before the first frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub key_wait: KeyWait,
+    pub frames_per_second: u32,
+    pub cycles_per_second: u32,
+    pub start_hash: u64,
+    /// Frames between two state hashes, 0 for none.
+    pub hash_interval: u64,
+}
+
+impl MovieHeader {
+    /// Describes `chip8`, which must have just been created with `seed` and loaded with
+    /// `rom`.
+    pub fn new(
+        rom: &[u8],
+        seed: u64,
+        chip8: &Chip8,
+        frames_per_second: u32,
+        cycles_per_second: u32,
+        hash_interval: u64,
+    ) -> Self {
+        Self {
+            rom_hash: rom_hash(rom),
+            seed,
+            key_wait: chip8.keypad().mode(),
+            frames_per_second,
+            cycles_per_second,
+            start_hash: chip8.state_hash(),
+            hash_interval,
+        }
+    }
+
+    /// Recreates the machine the movie starts from, failing if `rom` is not the ROM the
+    /// movie was recorded with.
+    pub fn chip8(&self, rom: &[u8]) -> io::Result<Chip8> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(invalid_data(format!(
+                "the movie was recorded with another ROM (hash {:016x}, this one is {:016x})",
+                self.rom_hash,
+                rom_hash(rom)
+            )));
+        }
+        let mut chip8 = Chip8::with_seed(self.seed);
+        chip8.load_bytes(rom);
+        chip8.set_key_wait(self.key_wait);
+        if chip8.state_hash() != self.start_hash {
+            return Err(invalid_data(format!(
+                "the movie starts from state {:016x} but power-on is {:016x}",
+                self.start_hash,
+                chip8.state_hash()
+            )));
+        }
+        Ok(chip8)
+    }
+}
+
+/// Something that happened during a movie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    Reset,
+    /// The state hash the machine must have at this point.
+    Hash(u64),
+}
+
+/// Identifies a ROM in movie headers.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write(rom);
+    hasher.finish()
+}
+
+/// Writes a movie file while it is being recorded.
+///
+/// The file is text: the header, then one line per event prefixed with the number of
+/// frames run before it happened, then an `end` line with the length of the movie. Events
+/// are written as they happen, so a crash still leaves a playable movie up to that point.
+pub struct MovieRecorder {
+    out: BufWriter<File>,
+    hash_interval: u64,
+    /// First write error, reported by [`MovieRecorder::finish`].
+    error: Option<io::Error>,
+}
+
+impl MovieRecorder {
+    pub fn create<P: AsRef<Path>>(path: P, header: &MovieHeader) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let key_wait = match header.key_wait {
+            KeyWait::Release => "release",
+            KeyWait::Press => "press",
+        };
+        writeln!(out, "{}", MAGIC)?;
+        writeln!(out, "rom {:016x}", header.rom_hash)?;
+        writeln!(out, "seed {}", header.seed)?;
+        writeln!(out, "quirks key-wait={}", key_wait)?;
+        writeln!(out, "frames-per-second {}", header.frames_per_second)?;
+        writeln!(out, "cycles-per-second {}", header.cycles_per_second)?;
+        writeln!(out, "start power-on {:016x}", header.start_hash)?;
+        writeln!(out, "hash-interval {}", header.hash_interval)?;
+        Ok(Self {
+            out,
+            hash_interval: header.hash_interval,
+            error: None,
+        })
+    }
+
+    /// Frames between two state hashes, 0 for none.
+    pub fn hash_interval(&self) -> u64 {
+        self.hash_interval
+    }
+
+    pub fn record(&mut self, frame: u64, event: MovieEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = match event {
+            MovieEvent::KeyDown(key) => writeln!(self.out, "{} down {:X}", frame, key),
+            MovieEvent::KeyUp(key) => writeln!(self.out, "{} up {:X}", frame, key),
+            MovieEvent::Reset => writeln!(self.out, "{} reset", frame),
+            MovieEvent::Hash(hash) => writeln!(self.out, "{} hash {:016x}", frame, hash),
+        };
+        // Keep the file current in case the emulator crashes
+        if let Err(err) = result.and_then(|()| self.out.flush()) {
+            self.error = Some(err);
+        }
+    }
+
+    /// Ends the movie after `frames` frames.
+    pub fn finish(mut self, frames: u64) -> io::Result<()> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        writeln!(self.out, "end {}", frames)?;
+        self.out.flush()
+    }
+}
+
+/// A movie read back from a file.
+#[derive(Debug, Clone)]
+pub struct Movie {
+    pub header: MovieHeader,
+    events: VecDeque<(u64, MovieEvent)>,
+    end: u64,
+}
+
+impl Movie {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read(input: impl BufRead) -> io::Result<Self> {
+        let mut lines = input.lines().enumerate();
+        match lines.next() {
+            Some((_, Ok(line))) if line == MAGIC => (),
+            Some((_, Err(err))) => return Err(err),
+            _ => return Err(invalid_data("not a chippers movie file".into())),
+        }
+
+        let mut rom_hash = None;
+        let mut seed = None;
+        let mut key_wait = KeyWait::default();
+        let mut frames_per_second = None;
+        let mut cycles_per_second = None;
+        let mut start_hash = None;
+        let mut hash_interval = 0;
+        let mut events = VecDeque::new();
+        let mut end = None;
+
+        for (number, line) in lines {
+            let line = line?;
+            let error = |message: &str| invalid_data(format!("line {}: {}", number + 1, message));
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [] => continue,
+                ["rom", hash] => {
+                    rom_hash = Some(parse_hash(hash).ok_or_else(|| error("bad ROM hash"))?)
+                }
+                ["seed", value] => seed = Some(value.parse().map_err(|_| error("bad seed"))?),
+                ["quirks", quirks @ ..] => {
+                    for quirk in quirks {
+                        key_wait = match *quirk {
+                            "key-wait=release" => KeyWait::Release,
+                            "key-wait=press" => KeyWait::Press,
+                            _ => return Err(error(&format!("unknown quirk '{}'", quirk))),
+                        };
+                    }
+                }
+                ["frames-per-second", value] => {
+                    let value = value.parse().ok().filter(|&value| value > 0);
+                    frames_per_second = Some(value.ok_or_else(|| error("bad frame rate"))?)
+                }
+                ["cycles-per-second", value] => {
+                    cycles_per_second = Some(value.parse().map_err(|_| error("bad clock rate"))?)
+                }
+                ["start", "power-on", hash] => {
+                    start_hash = Some(parse_hash(hash).ok_or_else(|| error("bad start hash"))?)
+                }
+                ["hash-interval", value] => {
+                    hash_interval = value.parse().map_err(|_| error("bad hash interval"))?
+                }
+                ["end", frames] => end = Some(frames.parse().map_err(|_| error("bad end frame"))?),
+                [frame, event @ ..] => {
+                    let frame: u64 = frame.parse().map_err(|_| error("unknown line"))?;
+                    let event = match event {
+                        ["down", key] => {
+                            MovieEvent::KeyDown(parse_key(key).ok_or_else(|| error("bad key"))?)
+                        }
+                        ["up", key] => {
+                            MovieEvent::KeyUp(parse_key(key).ok_or_else(|| error("bad key"))?)
+                        }
+                        ["reset"] => MovieEvent::Reset,
+                        ["hash", hash] => {
+                            MovieEvent::Hash(parse_hash(hash).ok_or_else(|| error("bad hash"))?)
+                        }
+                        _ => return Err(error("unknown event")),
+                    };
+                    if events.back().is_some_and(|(last, _)| *last > frame) {
+                        return Err(error("events out of order"));
+                    }
+                    events.push_back((frame, event));
+                }
+            }
+        }
+
+        let missing = |field| invalid_data(format!("movie header is missing '{}'", field));
+        let header = MovieHeader {
+            rom_hash: rom_hash.ok_or_else(|| missing("rom"))?,
+            seed: seed.ok_or_else(|| missing("seed"))?,
+            key_wait,
+            frames_per_second: frames_per_second.ok_or_else(|| missing("frames-per-second"))?,
+            cycles_per_second: cycles_per_second.ok_or_else(|| missing("cycles-per-second"))?,
+            start_hash: start_hash.ok_or_else(|| missing("start"))?,
+            hash_interval,
+        };
+        // A movie cut short by a crash ends with its last event
+        let end = end.unwrap_or_else(|| events.back().map_or(0, |(frame, _)| *frame));
+        Ok(Self {
+            header,
+            events,
+            end,
+        })
+    }
+
+    /// Number of frames the movie lasts.
+    pub fn end_frame(&self) -> u64 {
+        self.end
+    }
+
+    /// Removes the next event if it happens at `frame`.
+    pub fn next_event(&mut self, frame: u64) -> Option<MovieEvent> {
+        match self.events.front() {
+            Some(&(event_frame, event)) if event_frame == frame => {
+                self.events.pop_front();
+                Some(event)
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes the next event if it is the state hash for `frame`.
+    pub fn next_hash(&mut self, frame: u64) -> Option<u64> {
+        match self.events.front() {
+            Some(&(event_frame, MovieEvent::Hash(hash))) if event_frame == frame => {
+                self.events.pop_front();
+                Some(hash)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether every event has been played back by `frame`.
+    pub fn is_finished(&self, frame: u64) -> bool {
+        self.events.is_empty() && frame >= self.end
+    }
+}
+
+fn parse_hash(hash: &str) -> Option<u64> {
+    u64::from_str_radix(hash, 16).ok()
+}
+
+fn parse_key(key: &str) -> Option<u8> {
+    u8::from_str_radix(key, 16).ok().filter(|key| *key < 16)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "\
+chippers-movie 1
+rom 0123456789abcdef
+seed 7
+quirks key-wait=press
+frames-per-second 60
+cycles-per-second 700
+start power-on 00000000deadbeef
+hash-interval 10
+";
+
+    fn parse(events: &str) -> io::Result<Movie> {
+        Movie::read(format!("{}{}", HEADER, events).as_bytes())
+    }
+
+    fn parse_error(events: &str) -> String {
+        parse(events).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn recorded_movies_read_back() {
+        let header = MovieHeader {
+            rom_hash: 0x0123_4567_89ab_cdef,
+            seed: 7,
+            key_wait: KeyWait::Press,
+            frames_per_second: 60,
+            cycles_per_second: 700,
+            start_hash: 0xdead_beef,
+            hash_interval: 10,
+        };
+        let events = [
+            (0, MovieEvent::KeyDown(0x5)),
+            (3, MovieEvent::KeyDown(0xF)),
+            (3, MovieEvent::KeyUp(0x5)),
+            (10, MovieEvent::Hash(0x1234_5678_9abc_def0)),
+            (12, MovieEvent::Reset),
+        ];
+        let path = std::env::temp_dir().join(format!("chippers-{}-movie.txt", std::process::id()));
+        let mut recorder = MovieRecorder::create(&path, &header).unwrap();
+        for (frame, event) in events {
+            recorder.record(frame, event);
+        }
+        recorder.finish(20).unwrap();
+        let movie = Movie::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut movie = movie.unwrap();
+        assert_eq!(movie.header, header);
+        assert_eq!(movie.end_frame(), 20);
+        for (frame, event) in events {
+            assert!(!movie.is_finished(frame));
+            assert_eq!(movie.next_event(frame), Some(event));
+        }
+        assert!(!movie.is_finished(19));
+        assert!(movie.is_finished(20));
+    }
+
+    #[test]
+    fn events_wait_for_their_frame() {
+        let mut movie = parse("2 down A\n2 hash 00000000000000ff\nend 4\n").unwrap();
+        assert_eq!(movie.next_event(1), None);
+        assert_eq!(movie.next_hash(2), None);
+        assert_eq!(movie.next_event(2), Some(MovieEvent::KeyDown(0xA)));
+        assert_eq!(movie.next_hash(2), Some(0xff));
+    }
+
+    #[test]
+    fn movies_cut_short_end_with_their_last_event() {
+        let movie = parse("5 down 1\n8 up 1\n").unwrap();
+        assert_eq!(movie.end_frame(), 8);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        assert_eq!(parse_error("4 hash xyz\n"), "line 9: bad hash");
+        assert_eq!(
+            parse_error("4 hash 1ffffffffffffffff\n"),
+            "line 9: bad hash"
+        );
+        assert_eq!(parse_error("4 down G\n"), "line 9: bad key");
+        assert_eq!(parse_error("4 up 10\n"), "line 9: bad key");
+        assert_eq!(parse_error("4 down\n"), "line 9: unknown event");
+        assert_eq!(parse_error("4 jump 2\n"), "line 9: unknown event");
+        assert_eq!(parse_error("soon down 2\n"), "line 9: unknown line");
+        assert_eq!(
+            parse_error("5 down 2\n4 up 2\n"),
+            "line 10: events out of order"
+        );
+        assert_eq!(parse_error("end never\n"), "line 9: bad end frame");
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        let error = |text: &str| Movie::read(text.as_bytes()).unwrap_err().to_string();
+        assert_eq!(error("chippers-movie 2\n"), "not a chippers movie file");
+        assert_eq!(
+            error(&HEADER.replace("rom 0123456789abcdef", "rom 0123-4567")),
+            "line 2: bad ROM hash"
+        );
+        assert_eq!(
+            error(&HEADER.replace("key-wait=press", "key-wait=never")),
+            "line 4: unknown quirk 'key-wait=never'"
+        );
+        assert_eq!(
+            error(&HEADER.replace("frames-per-second 60", "frames-per-second 0")),
+            "line 5: bad frame rate"
+        );
+        assert_eq!(
+            error(&HEADER.replace("seed 7\n", "")),
+            "movie header is missing 'seed'"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::chip8::{Observer, Registers};
+use crate::disasm::{Instruction, OpClass};
+
+/// Addresses listed in the hotspot table of a report.
+const HOTSPOTS: usize = 20;
+
+/// Time spent in one subroutine, in executed instructions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubroutineStats {
+    pub calls: u64,
+    /// Instructions executed by the subroutine itself.
+    pub own: u64,
+    /// Instructions executed by the subroutine and everything it called.
+    pub total: u64,
+}
+
+/// Counts where a program spends its instructions.
+///
+/// Subroutines are told apart by following 2nnn and 00EE, so a subroutine is identified by
+/// its entry address. The main program is the subroutine at 200.
+pub struct Profiler {
+    counts: Vec<u64>,
+    opcodes: Vec<u16>,
+    classes: BTreeMap<OpClass, u64>,
+    instructions: u64,
+    /// Entry address of each active call and the instruction count it started at, the main
+    /// program first.
+    calls: Vec<(u16, u64)>,
+    /// Entry addresses of `calls`, for looking up `stacks`.
+    path: Vec<u16>,
+    subroutines: BTreeMap<u16, SubroutineStats>,
+    /// Own instructions of every call stack seen.
+    stacks: HashMap<Vec<u16>, u64>,
+    /// Sprites drawn in each frame.
+    draws: Vec<u32>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let mut subroutines = BTreeMap::new();
+        subroutines.insert(
+            0x200,
+            SubroutineStats {
+                calls: 1,
+                ..SubroutineStats::default()
+            },
+        );
+        Self {
+            counts: vec![0; 0x1000],
+            opcodes: vec![0; 0x1000],
+            classes: BTreeMap::new(),
+            instructions: 0,
+            calls: vec![(0x200, 0)],
+            path: vec![0x200],
+            subroutines,
+            stacks: HashMap::new(),
+            draws: Vec::new(),
+        }
+    }
+
+    /// Number of instructions executed.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Times the instruction at `address` was executed.
+    pub fn count(&self, address: u16) -> u64 {
+        self.counts[address as usize & 0xFFF]
+    }
+
+    pub fn class_counts(&self) -> &BTreeMap<OpClass, u64> {
+        &self.classes
+    }
+
+    /// Per-subroutine statistics by entry address, including calls still running.
+    pub fn subroutines(&self) -> BTreeMap<u16, SubroutineStats> {
+        let mut subroutines = self.subroutines.clone();
+        for (depth, &(entry, start)) in self.calls.iter().enumerate() {
+            if !self.path[..depth].contains(&entry) {
+                subroutines.entry(entry).or_default().total += self.instructions - start;
+            }
+        }
+        subroutines
+    }
+
+    /// Sprites drawn in each frame.
+    pub fn draws_per_frame(&self) -> &[u32] {
+        &self.draws
+    }
+
+    /// Writes the call stacks in the folded format read by flamegraph tools: one line per
+    /// stack, such as `main;sub_2A0;sub_310 1234`, with the instructions executed there.
+    pub fn write_folded(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        for (stack, count) in stacks {
+            let names: Vec<_> = stack
+                .iter()
+                .enumerate()
+                .map(|(depth, &entry)| subroutine_name(depth, entry))
+                .collect();
+            writeln!(out, "{} {}", names.join(";"), count)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a report for people: instructions by class, the hottest addresses, time per
+    /// subroutine and draw calls per frame.
+    pub fn write_report(&self, out: &mut impl Write) -> io::Result<()> {
+        let percent = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+
+        writeln!(out, "Instructions by class:")?;
+        for (class, &count) in &self.classes {
+            writeln!(
+                out,
+                "  {:<10} {:>10} {:>6.2}%",
+                class.name(),
+                count,
+                percent(count)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "Hottest addresses:")?;
+        writeln!(out, "  addr      count       %  instruction")?;
+        let mut addresses: Vec<u16> = (0..0x1000).filter(|&a| self.count(a) > 0).collect();
+        addresses.sort_by_key(|&address| (std::cmp::Reverse(self.count(address)), address));
+        for &address in addresses.iter().take(HOTSPOTS) {
+            let count = self.count(address);
+            let opcode = self.opcodes[address as usize];
+            writeln!(
+                out,
+                "  {:03X}  {:>10} {:>6.2}%  {:04X}  {}",
+                address,
+                count,
+                percent(count),
+                opcode,
+                Instruction::decode(opcode)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "Subroutines:")?;
+        writeln!(
+            out,
+            "  entry     calls         own       %       total       %"
+        )?;
+        let mut subroutines: Vec<_> = self.subroutines().into_iter().collect();
+        subroutines.sort_by_key(|(entry, stats)| (std::cmp::Reverse(stats.total), *entry));
+        for (entry, stats) in subroutines {
+            let name = if entry == 0x200 {
+                "main".to_string()
+            } else {
+                format!("{:03X}", entry)
+            };
+            writeln!(
+                out,
+                "  {:<5} {:>9} {:>11} {:>6.2}% {:>11} {:>6.2}%",
+                name,
+                stats.calls,
+                stats.own,
+                percent(stats.own),
+                stats.total,
+                percent(stats.total)
+            )?;
+        }
+
+        writeln!(out)?;
+        if self.draws.is_empty() {
+            return writeln!(out, "No frames were run");
+        }
+        let total: u64 = self.draws.iter().map(|&draws| draws as u64).sum();
+        let (busiest, most) = self
+            .draws
+            .iter()
+            .enumerate()
+            .max_by_key(|&(frame, draws)| (draws, std::cmp::Reverse(frame)))
+            .unwrap();
+        writeln!(
+            out,
+            "Draw calls per frame: min {}, mean {:.2}, max {} (first in frame {})",
+            self.draws.iter().min().unwrap(),
+            total as f64 / self.draws.len() as f64,
+            most,
+            busiest
+        )?;
+        let without = self.draws.iter().filter(|&&draws| draws == 0).count();
+        writeln!(out, "Frames without any drawing: {}", without)
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Observer for Profiler {
+    fn instruction(&mut self, opcode: u16, before: &Registers) {
+        let pc = before.pc as usize & 0xFFF;
+        self.counts[pc] += 1;
+        self.opcodes[pc] = opcode;
+        let instruction = Instruction::decode(opcode);
+        *self.classes.entry(instruction.class()).or_default() += 1;
+        self.instructions += 1;
+
+        let (entry, _) = *self.calls.last().unwrap();
+        self.subroutines.entry(entry).or_default().own += 1;
+        match self.stacks.get_mut(self.path.as_slice()) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.path.clone(), 1);
+            }
+        }
+
+        match instruction {
+            Instruction::Call { nnn } => {
+                self.calls.push((nnn, self.instructions));
+                self.path.push(nnn);
+                self.subroutines.entry(nnn).or_default().calls += 1;
+            }
+            // A return from the main program is a bug in the ROM, nothing to track
+            Instruction::Return if self.calls.len() > 1 => {
+                let (entry, start) = self.calls.pop().unwrap();
+                self.path.pop();
+                // Recursive calls are already counted by the outermost one
+                if !self.path.contains(&entry) {
+                    self.subroutines.entry(entry).or_default().total += self.instructions - start;
+                }
+            }
+            Instruction::Draw { .. } => match self.draws.last_mut() {
+                Some(draws) => *draws += 1,
+                None => self.draws.push(1),
+            },
+            _ => (),
+        }
+    }
+
+    fn frame(&mut self, _frame: u64) {
+        self.draws.push(0);
+    }
+}
+
+/// How a subroutine appears in folded stacks.
+fn subroutine_name(depth: usize, entry: u16) -> String {
+    if depth == 0 {
+        "main".to_string()
+    } else {
+        format!("sub_{:03X}", entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+
+    /// Runs `cycles` instructions of a ROM where main calls 206, which calls 20C, then
+    /// calls 210, which calls itself until V2 is 3, then loops at 204.
+    fn profile(cycles: u64) -> Profiler {
+        let opcodes: [u16; 12] = [
+            0x2206, // 200: CALL 206
+            0x2210, // 202: CALL 210
+            0x1204, // 204: JP 204
+            0x220C, // 206: CALL 20C
+            0x6001, // 208: LD V0, 1
+            0x00EE, // 20A: RET
+            0x6102, // 20C: LD V1, 2
+            0x00EE, // 20E: RET
+            0x7201, // 210: ADD V2, 1
+            0x3203, // 212: SE V2, 3
+            0x2210, // 214: CALL 210
+            0x00EE, // 216: RET
+        ];
+        let mut chip8 = Chip8::new();
+        chip8.load_raw(&opcodes);
+        let mut profiler = Profiler::new();
+        chip8.run_with(cycles, &mut profiler);
+        profiler
+    }
+
+    fn stats(calls: u64, own: u64, total: u64) -> SubroutineStats {
+        SubroutineStats { calls, own, total }
+    }
+
+    #[test]
+    fn nested_and_recursive_calls_are_timed() {
+        let profiler = profile(22);
+        assert_eq!(profiler.instructions(), 22);
+        assert_eq!(profiler.count(0x204), 4);
+        assert_eq!(profiler.count(0x216), 3);
+        assert_eq!(
+            profiler.subroutines().into_iter().collect::<Vec<_>>(),
+            [
+                (0x200, stats(1, 6, 22)),
+                (0x206, stats(1, 3, 5)),
+                (0x20C, stats(1, 2, 2)),
+                // The recursive calls count towards the outermost one only
+                (0x210, stats(3, 11, 11)),
+            ]
+        );
+        assert_eq!(profiler.class_counts()[&OpClass::Flow], 14);
+        assert_eq!(profiler.class_counts()[&OpClass::Arithmetic], 3);
+    }
+
+    #[test]
+    fn calls_still_running_count_up_to_the_end() {
+        // Stopped in the second call of 210 by itself
+        let profiler = profile(12);
+        assert_eq!(
+            profiler.subroutines().into_iter().collect::<Vec<_>>(),
+            [
+                (0x200, stats(1, 2, 12)),
+                (0x206, stats(1, 3, 5)),
+                (0x20C, stats(1, 2, 2)),
+                (0x210, stats(2, 5, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn returns_from_main_are_ignored() {
+        let mut profiler = Profiler::new();
+        for (pc, opcode) in [(0x200, 0x00EE), (0x202, 0x6001), (0x204, 0x00EE)] {
+            let before = Registers {
+                pc,
+                ..Registers::default()
+            };
+            profiler.instruction(opcode, &before);
+        }
+        assert_eq!(
+            profiler.subroutines().into_iter().collect::<Vec<_>>(),
+            [(0x200, stats(1, 3, 3))]
+        );
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(String::from_utf8(folded).unwrap(), "main 3\n");
+    }
+
+    #[test]
+    fn folded_stacks_count_own_instructions() {
+        let mut folded = Vec::new();
+        profile(22).write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main 6\n\
+             main;sub_206 3\n\
+             main;sub_206;sub_20C 2\n\
+             main;sub_210 4\n\
+             main;sub_210;sub_210 4\n\
+             main;sub_210;sub_210;sub_210 3\n"
+        );
+    }
+
+    #[test]
+    fn draws_are_counted_per_frame() {
+        // 200: DRW V0, V0, 1
+        // 202: DRW V0, V0, 1
+        // 204: JP 200
+        let mut chip8 = Chip8::new();
+        chip8.load_raw(&[0xD001, 0xD001, 0x1200]);
+        let mut profiler = Profiler::new();
+        for (frame, cycles) in [3, 1, 0, 4].into_iter().enumerate() {
+            profiler.frame(frame as u64);
+            chip8.run_with(cycles, &mut profiler);
+        }
+        assert_eq!(profiler.draws_per_frame(), [2, 1, 0, 3]);
+
+        let mut report = Vec::new();
+        profiler.write_report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.ends_with(
+            "Draw calls per frame: min 0, mean 1.50, max 3 (first in frame 3)\n\
+             Frames without any drawing: 1\n"
+        ));
+        assert!(report.contains("  200           3  37.50%  D001  DRW V0, V0, 1\n"));
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::cfg::{self, Cfg, Line};
+use crate::disasm::Instruction;
+
+/// A ROM split into blocks of straight-line code, to be written out as a Rust module.
+///
+/// The blocks are those of the ROM's [`Cfg`], split again after Fx0A, Fx33 and Fx55: Fx0A
+/// may not move on, and Fx33 and Fx55 may overwrite the code that follows. The generated
+/// code leaves everything the CFG does not find, and every block whose bytes the ROM
+/// overwrote, to the interpreter.
+pub struct Recompiler {
+    rom: Vec<u8>,
+    blocks: BTreeMap<u16, Vec<Line>>,
+}
+
+impl Recompiler {
+    /// Finds the blocks of `rom`, loaded at 200.
+    pub fn new(rom: &[u8]) -> Self {
+        let mut blocks = BTreeMap::new();
+        for block in Cfg::new(rom).blocks().values() {
+            let mut start = block.start;
+            let mut lines = Vec::new();
+            for &line in &block.lines {
+                lines.push(line);
+                if matches!(
+                    line.instruction,
+                    Instruction::WaitKey { .. }
+                        | Instruction::Bcd { .. }
+                        | Instruction::Store { .. }
+                ) {
+                    blocks.insert(start, std::mem::take(&mut lines));
+                    start = line.address + 2;
+                }
+            }
+            if !lines.is_empty() {
+                blocks.insert(start, lines);
+            }
+        }
+
+        Self {
+            rom: rom.to_vec(),
+            blocks,
+        }
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Number of instructions in all blocks.
+    pub fn instructions(&self) -> usize {
+        self.blocks.values().map(Vec::len).sum()
+    }
+
+    /// Writes the Rust module, with `name` for the ROM in its documentation.
+    ///
+    /// The module depends on nothing but `chippers` with the `std` feature. It has the ROM
+    /// as `ROM`, a `run` function to call in place of `Chip8::run`, and a `Recompiled`
+    /// backend to hand to `Emulator::set_backend`.
+    pub fn write_rust(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        writeln!(
+            out,
+            "//! {} recompiled to Rust by `chippers recompile`.",
+            name
+        )?;
+        writeln!(out, "//!")?;
+        writeln!(
+            out,
+            "//! Runs on a `Chip8` that loaded [`ROM`]: call [`run`] in place of `Chip8::run`, or"
+        )?;
+        writeln!(
+            out,
+            "//! hand [`Recompiled`] to `Emulator::set_backend`. Code not found by following the jumps"
+        )?;
+        writeln!(
+            out,
+            "//! of the ROM, and code the ROM overwrites, run in the interpreter."
+        )?;
+        writeln!(out)?;
+        writeln!(out, "use chippers::chip8::{{Chip8, Cpu}};")?;
+        writeln!(out, "use chippers::emulator::Backend;")?;
+        writeln!(out)?;
+
+        writeln!(out, "/// The ROM, loaded at 200.")?;
+        writeln!(out, "pub const ROM: [u8; {}] = [", self.rom.len())?;
+        for row in self.rom.chunks(16) {
+            let bytes: Vec<_> = row.iter().map(|byte| format!("0x{:02X},", byte)).collect();
+            writeln!(out, "    {}", bytes.join(" "))?;
+        }
+        writeln!(out, "];")?;
+        writeln!(out)?;
+
+        write!(
+            out,
+            "\
+/// Runs the recompiled code in place of the interpreter.
+pub struct Recompiled;
+
+impl Backend for Recompiled {{
+    fn run(&mut self, chip8: &mut Chip8, cycles: u64) {{
+        run(chip8, cycles);
+    }}
+}}
+
+/// Runs `cycles` instructions like `Chip8::run`.
+pub fn run(chip8: &mut Chip8, cycles: u64) {{
+    let mut cpu = chip8.cpu();
+    let mut remaining = cycles;
+    while remaining > 0 {{
+        let ran = match cpu.pc {{
+"
+        )?;
+        for (&start, lines) in &self.blocks {
+            writeln!(
+                out,
+                "            0x{:03X} if remaining >= {} && intact(chip8, 0x{:03X}, 0x{:03X}) => {{",
+                start,
+                lines.len(),
+                start,
+                start as usize + 2 * lines.len()
+            )?;
+            writeln!(out, "                block_{:03x}(chip8, &mut cpu)", start)?;
+            writeln!(out, "            }}")?;
+        }
+        write!(
+            out,
+            "\
+            _ => 0,
+        }};
+        if ran > 0 {{
+            remaining -= ran;
+            continue;
+        }}
+        let pc = cpu.pc as usize;
+        let memory = chip8.memory();
+        if pc >= 0x1000 || (pc < 0xFFF && memory[pc] == 0 && memory[pc + 1] == 0) {{
+            // Every cycle from here on does nothing
+            break;
+        }}
+        interpret(chip8, &mut cpu);
+        remaining -= 1;
+    }}
+    chip8.set_cpu(&cpu);
+}}
+
+/// Whether memory from `start` to `end` still holds the code of the ROM.
+fn intact(chip8: &Chip8, start: usize, end: usize) -> bool {{
+    chip8.memory()[start..end] == ROM[start - 0x200..end - 0x200]
+}}
+
+/// Runs the instruction at `cpu.pc` in the interpreter.
+fn interpret(chip8: &mut Chip8, cpu: &mut Cpu) {{
+    chip8.set_cpu(cpu);
+    chip8.cycle();
+    *cpu = chip8.cpu();
+}}
+"
+        )?;
+
+        for (&start, lines) in &self.blocks {
+            writeln!(out)?;
+            write_block(out, start, lines)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the function running the block at `start`, which returns the instructions it ran.
+fn write_block(out: &mut impl Write, start: u16, lines: &[Line]) -> io::Result<()> {
+    let len = lines.len();
+    let code: Vec<_> = lines
+        .iter()
+        .map(|line| rust(line.instruction, line.address, len))
+        .collect();
+    writeln!(
+        out,
+        "fn block_{:03x}({}: &mut Chip8, cpu: &mut Cpu) -> u64 {{",
+        start,
+        if code.iter().any(Option::is_none) {
+            "chip8"
+        } else {
+            "_chip8"
+        }
+    )?;
+    // Control flow and the interpreter set the PC themselves
+    let last = lines.last().unwrap();
+    let sets_pc = cfg::ends_block(last.instruction) || code.last().unwrap().is_none();
+    for (line, code) in lines.iter().zip(code) {
+        writeln!(
+            out,
+            "    // {:03X}: {:04X}  {}",
+            line.address, line.opcode, line.instruction
+        )?;
+        let code = code
+            .unwrap_or_else(|| format!("cpu.pc = 0x{:03X};\ninterpret(chip8, cpu);", line.address));
+        for line in code.lines() {
+            writeln!(out, "    {}", line)?;
+        }
+    }
+    if !sets_pc {
+        writeln!(out, "    cpu.pc = 0x{:03X};", last.address + 2)?;
+    }
+    writeln!(out, "    {}", len)?;
+    writeln!(out, "}}")
+}
+
+/// The Rust code of `instruction` at `address` in a block of `len` instructions, or `None`
+/// if the interpreter has to run it.
+fn rust(instruction: Instruction, address: u16, len: usize) -> Option<String> {
+    use Instruction::*;
+    let skip = |condition: String| {
+        format!(
+            "cpu.pc = if {} {{ 0x{:03X} }} else {{ 0x{:03X} }};",
+            condition,
+            address + 4,
+            address + 2
+        )
+    };
+    let code = match instruction {
+        Jump { nnn } => format!("cpu.pc = 0x{:03X};", nnn),
+        Call { nnn } => format!(
+            "if cpu.sp >= 16 {{\n    \
+             // Overflows the stack in the interpreter\n    \
+             cpu.pc = 0x{:03X};\n    \
+             return {};\n\
+             }}\n\
+             cpu.stack[cpu.sp as usize] = 0x{:03X};\n\
+             cpu.sp += 1;\n\
+             cpu.pc = 0x{:03X};",
+            address,
+            len - 1,
+            address + 2,
+            nnn
+        ),
+        Return => format!(
+            "if cpu.sp == 0 {{\n    \
+             // Underflows the stack in the interpreter\n    \
+             cpu.pc = 0x{:03X};\n    \
+             return {};\n\
+             }}\n\
+             cpu.sp -= 1;\n\
+             cpu.pc = cpu.stack[cpu.sp as usize];",
+            address,
+            len - 1
+        ),
+        JumpV0 { nnn } => format!("cpu.pc = 0x{:03X}_u16.wrapping_add(cpu.v[0] as u16);", nnn),
+        SkipEqImm { x, nn } => skip(format!("cpu.v[{}] == 0x{:02X}", x, nn)),
+        SkipNeImm { x, nn } => skip(format!("cpu.v[{}] != 0x{:02X}", x, nn)),
+        SkipEq { x, y } => skip(format!("cpu.v[{}] == cpu.v[{}]", x, y)),
+        SkipNe { x, y } => skip(format!("cpu.v[{}] != cpu.v[{}]", x, y)),
+        SkipKey { x } => skip(format!("cpu.keys & (1 << (cpu.v[{}] & 0xF)) != 0", x)),
+        SkipNotKey { x } => skip(format!("cpu.keys & (1 << (cpu.v[{}] & 0xF)) == 0", x)),
+        LoadImm { x, nn } => format!("cpu.v[{}] = 0x{:02X};", x, nn),
+        AddImm { x, nn } => format!("cpu.v[{0}] = cpu.v[{0}].wrapping_add(0x{1:02X});", x, nn),
+        Move { x, y } => format!("cpu.v[{}] = cpu.v[{}];", x, y),
+        Or { x, y } => format!("cpu.v[{}] |= cpu.v[{}];\ncpu.v[15] = 0;", x, y),
+        And { x, y } => format!("cpu.v[{}] &= cpu.v[{}];\ncpu.v[15] = 0;", x, y),
+        Xor { x, y } => format!("cpu.v[{}] ^= cpu.v[{}];\ncpu.v[15] = 0;", x, y),
+        Add { x, y } => format!(
+            "let (sum, carry) = cpu.v[{0}].overflowing_add(cpu.v[{1}]);\n\
+             cpu.v[{0}] = sum;\n\
+             cpu.v[15] = carry as u8;",
+            x, y
+        ),
+        Sub { x, y } | SubReverse { x, y } => {
+            let (a, b) = match instruction {
+                Sub { .. } => (x, y),
+                _ => (y, x),
+            };
+            format!(
+                "let no_borrow = cpu.v[{1}] >= cpu.v[{2}];\n\
+                 cpu.v[{0}] = cpu.v[{1}].wrapping_sub(cpu.v[{2}]);\n\
+                 cpu.v[15] = no_borrow as u8;",
+                x, a, b
+            )
+        }
+        ShiftRight { x, y } => format!(
+            "let lsb = cpu.v[{}] & 1;\n{}\ncpu.v[15] = lsb;",
+            y,
+            shift(x, y, ">>")
+        ),
+        ShiftLeft { x, y } => format!(
+            "let msb = cpu.v[{}] >> 7;\n{}\ncpu.v[15] = msb;",
+            y,
+            shift(x, y, "<<")
+        ),
+        LoadI { nnn } => format!("cpu.i = 0x{:03X};", nnn),
+        AddI { x } => format!("cpu.i = cpu.i.wrapping_add(cpu.v[{}] as u16);", x),
+        Font { x } => format!("cpu.i = 5 * cpu.v[{}] as u16;", x),
+        GetDelay { x } => format!("cpu.v[{}] = cpu.delay_timer;", x),
+        SetDelay { x } => format!("cpu.delay_timer = cpu.v[{}];", x),
+        SetSound { x } => format!("cpu.sound_timer = cpu.v[{}];", x),
+        // Drawing, random numbers, waiting for keys, memory and anything unknown
+        Clear
+        | Random { .. }
+        | Draw { .. }
+        | WaitKey { .. }
+        | Bcd { .. }
+        | Store { .. }
+        | Load { .. }
+        | Sys { .. }
+        | Unknown(_) => return None,
+    };
+    Some(code)
+}
+
+/// Shifts vy by one with `operator` into vx, in place when they are the same register.
+fn shift(x: u8, y: u8, operator: &str) -> String {
+    if x == y {
+        format!("cpu.v[{}] {}= 1;", x, operator)
+    } else {
+        format!("cpu.v[{}] = cpu.v[{}] {} 1;", x, y, operator)
+    }
+}
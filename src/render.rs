@@ -0,0 +1,274 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::chip8::{HEIGHT, WIDTH};
+
+/// Colors used to draw lit and unlit CHIP-8 pixels, as RGBA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub on: [u8; 4],
+    pub off: [u8; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            on: [0xFF, 0xFF, 0xFF, 0xFF],
+            off: [0x00, 0x00, 0x00, 0xFF],
+        }
+    }
+}
+
+/// Post-processing applied on top of the upscaled image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    #[default]
+    None,
+    Scanlines,
+    Lcd,
+    Crt,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Filter::None),
+            "scanlines" => Ok(Filter::Scanlines),
+            "lcd" => Ok(Filter::Lcd),
+            "crt" => Ok(Filter::Crt),
+            _ => Err(format!(
+                "unknown filter '{s}', expected one of: crt, lcd, scanlines, none"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Filter::None => "none",
+            Filter::Scanlines => "scanlines",
+            Filter::Lcd => "lcd",
+            Filter::Crt => "crt",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Strength of the barrel distortion used by the CRT filter.
+const CRT_CURVATURE: f32 = 0.06;
+/// How much light a lit pixel bleeds into its neighbours with the CRT filter.
+const CRT_BLOOM: f32 = 0.18;
+
+/// Rasterizes `gfx` into an RGBA `frame` of `scale * WIDTH` by `scale * HEIGHT` pixels.
+pub fn rasterize(gfx: &[u8], scale: u32, palette: &Palette, filter: Filter, frame: &mut [u8]) {
+    let out_width = (scale * WIDTH) as usize;
+    let out_height = (scale * HEIGHT) as usize;
+    debug_assert_eq!(frame.len(), out_width * out_height * 4);
+
+    for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
+        let ox = i % out_width;
+        let oy = i / out_width;
+
+        let rgba = match filter {
+            Filter::None => {
+                let x = ox / scale as usize;
+                let y = oy / scale as usize;
+                if gfx[x + y * WIDTH as usize] == 1 {
+                    palette.on
+                } else {
+                    palette.off
+                }
+            }
+            Filter::Scanlines | Filter::Lcd => {
+                let x = ox / scale as usize;
+                let y = oy / scale as usize;
+                let lit = if gfx[x + y * WIDTH as usize] == 1 {
+                    1.0
+                } else {
+                    0.0
+                };
+                let cell_x = (ox % scale as usize) as f32 / scale as f32;
+                let cell_y = (oy % scale as usize) as f32 / scale as f32;
+                let mask = if filter == Filter::Scanlines {
+                    scanline_mask(cell_y, scale)
+                } else {
+                    grid_mask(cell_x, cell_y, scale)
+                };
+                shade(palette, lit, mask)
+            }
+            Filter::Crt => {
+                let u = (ox as f32 + 0.5) / out_width as f32 * 2.0 - 1.0;
+                let v = (oy as f32 + 0.5) / out_height as f32 * 2.0 - 1.0;
+                let cu = u * (1.0 + CRT_CURVATURE * v * v);
+                let cv = v * (1.0 + CRT_CURVATURE * u * u);
+                if cu.abs() >= 1.0 || cv.abs() >= 1.0 {
+                    [0x00, 0x00, 0x00, 0xFF]
+                } else {
+                    let sx = (cu + 1.0) / 2.0 * WIDTH as f32;
+                    let sy = (cv + 1.0) / 2.0 * HEIGHT as f32;
+                    let x = sx as usize;
+                    let y = sy as usize;
+                    let lit = if gfx[x + y * WIDTH as usize] == 1 {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                    let light = (lit + CRT_BLOOM * neighbourhood(gfx, x, y)).min(1.0);
+                    let vignette = (1.0 - 0.2 * (u * u + v * v)).clamp(0.0, 1.0);
+                    shade(
+                        palette,
+                        light,
+                        scanline_mask(sy - y as f32, scale) * vignette,
+                    )
+                }
+            }
+        };
+
+        pixel.copy_from_slice(&rgba);
+    }
+}
+
+/// Rasterizes the rows of `gfx` flagged in the `rows` bitmask into a native-resolution RGBA
+/// `frame`, leaving the other rows untouched.
+pub fn rasterize_rows(gfx: &[u8], rows: u32, palette: &Palette, frame: &mut [u8]) {
+    let width = WIDTH as usize;
+    for (y, line) in frame.chunks_exact_mut(width * 4).enumerate() {
+        if rows & (1 << y) == 0 {
+            continue;
+        }
+        for (pixel, &lit) in line
+            .chunks_exact_mut(4)
+            .zip(&gfx[y * width..(y + 1) * width])
+        {
+            let rgba = if lit == 1 { palette.on } else { palette.off };
+            pixel.copy_from_slice(&rgba);
+        }
+    }
+}
+
+/// Darkens the lower part of every pixel row.
+fn scanline_mask(cell_y: f32, scale: u32) -> f32 {
+    if scale >= 2 && cell_y >= 0.7 {
+        0.55
+    } else {
+        1.0
+    }
+}
+
+/// Darkens the right and bottom edge of every pixel to show an LCD grid.
+fn grid_mask(cell_x: f32, cell_y: f32, scale: u32) -> f32 {
+    let edge = 1.0 - 1.0 / scale as f32;
+    if scale >= 3 && (cell_x >= edge || cell_y >= edge) {
+        0.6
+    } else {
+        1.0
+    }
+}
+
+/// Fraction of lit pixels around (`x`, `y`), wrapping at the screen edges.
+fn neighbourhood(gfx: &[u8], x: usize, y: usize) -> f32 {
+    let (w, h) = (WIDTH as usize, HEIGHT as usize);
+    let mut lit = 0;
+    for dy in [h - 1, 0, 1] {
+        for dx in [w - 1, 0, 1] {
+            if (dx, dy) != (0, 0) && gfx[(x + dx) % w + ((y + dy) % h) * w] == 1 {
+                lit += 1;
+            }
+        }
+    }
+    lit as f32 / 8.0
+}
+
+fn shade(palette: &Palette, light: f32, mask: f32) -> [u8; 4] {
+    let mut rgba = [0xFF; 4];
+    for ((out, &on), &off) in rgba.iter_mut().zip(&palette.on).zip(&palette.off).take(3) {
+        let (on, off) = (on as f32, off as f32);
+        *out = ((off + (on - off) * light) * mask + 0.5) as u8;
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+    const BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+    fn render(gfx: &[u8], scale: u32, filter: Filter) -> Vec<u8> {
+        let mut frame = vec![0; (scale * WIDTH * scale * HEIGHT * 4) as usize];
+        rasterize(gfx, scale, &Palette::default(), filter, &mut frame);
+        frame
+    }
+
+    fn pixel(frame: &[u8], scale: u32, x: u32, y: u32) -> [u8; 4] {
+        let i = ((x + y * scale * WIDTH) * 4) as usize;
+        frame[i..i + 4].try_into().unwrap()
+    }
+
+    /// A screen with only the pixel at (`x`, `y`) lit.
+    fn one_pixel(x: usize, y: usize) -> Vec<u8> {
+        let mut gfx = vec![0; (WIDTH * HEIGHT) as usize];
+        gfx[x + y * WIDTH as usize] = 1;
+        gfx
+    }
+
+    #[test]
+    fn none_scales_pixels_into_squares() {
+        let frame = render(&one_pixel(1, 0), 2, Filter::None);
+        for (x, y) in [(2, 0), (3, 0), (2, 1), (3, 1)] {
+            assert_eq!(pixel(&frame, 2, x, y), WHITE);
+        }
+        for (x, y) in [(0, 0), (1, 1), (4, 0), (2, 2)] {
+            assert_eq!(pixel(&frame, 2, x, y), BLACK);
+        }
+    }
+
+    #[test]
+    fn scanlines_darken_the_bottom_of_each_row() {
+        let frame = render(&one_pixel(0, 0), 4, Filter::Scanlines);
+        assert_eq!(pixel(&frame, 4, 1, 0), WHITE);
+        assert_eq!(pixel(&frame, 4, 1, 2), WHITE);
+        assert_eq!(pixel(&frame, 4, 1, 3), [140, 140, 140, 0xFF]);
+        assert_eq!(pixel(&frame, 4, 4, 0), BLACK);
+    }
+
+    #[test]
+    fn lcd_darkens_the_right_and_bottom_edge_of_each_pixel() {
+        let frame = render(&one_pixel(0, 0), 4, Filter::Lcd);
+        assert_eq!(pixel(&frame, 4, 0, 0), WHITE);
+        assert_eq!(pixel(&frame, 4, 3, 0), [153, 153, 153, 0xFF]);
+        assert_eq!(pixel(&frame, 4, 0, 3), [153, 153, 153, 0xFF]);
+        assert_eq!(pixel(&frame, 4, 4, 4), BLACK);
+    }
+
+    #[test]
+    fn crt_curves_and_darkens_towards_the_corners() {
+        let gfx = vec![1; (WIDTH * HEIGHT) as usize];
+        let scale = 4;
+        let frame = render(&gfx, scale, Filter::Crt);
+        let (width, height) = (scale * WIDTH, scale * HEIGHT);
+        // Bent off the screen
+        assert_eq!(pixel(&frame, scale, 0, 0), BLACK);
+        let center = pixel(&frame, scale, width / 2, height / 2);
+        let edge = pixel(&frame, scale, 8, height / 2);
+        assert!(
+            center[0] > edge[0],
+            "{:?} should be brighter than {:?}",
+            center,
+            edge
+        );
+        assert!(edge[0] > 0);
+        // Blank screens stay black
+        let frame = render(&vec![0; gfx.len()], scale, Filter::Crt);
+        assert!(frame.chunks_exact(4).all(|pixel| pixel == BLACK));
+    }
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the unit tests. Integration tests include this file with `#[path]`.
+
+use alloc::vec::Vec;
+
+/// The bytes of a ROM hand-assembled from `opcodes`, to load at 200.
+pub(crate) fn assemble(opcodes: &[u16]) -> Vec<u8> {
+    opcodes
+        .iter()
+        .flat_map(|opcode| opcode.to_be_bytes())
+        .collect()
+}
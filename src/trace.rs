@@ -0,0 +1,794 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chip8::{Observer, Registers};
+use crate::disasm::{Instruction, OpClass};
+
+/// First line of every text trace.
+const TEXT_MAGIC: &str = "# chippers trace 1";
+
+/// First bytes of every binary trace.
+const BINARY_MAGIC: &[u8; 8] = b"C8TRACE\x01";
+
+/// Record tag of a new frame in binary traces. Any other tag is the number of changes of
+/// an instruction record.
+const FRAME_TAG: u8 = 0xFF;
+
+/// A register or memory byte changed by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    V(u8, u8),
+    I(u16),
+    Sp(u16),
+    DelayTimer(u8),
+    SoundTimer(u8),
+    Memory(u16, u8),
+}
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub frame: u64,
+    /// Number of instructions executed before this one.
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    /// Everything the instruction changed except the PC, which the next record shows.
+    pub changes: Vec<Change>,
+}
+
+impl TraceRecord {
+    /// Writes the record as a line of a text trace.
+    pub fn write_text(&self, out: &mut impl Write) -> io::Result<()> {
+        let disasm = Instruction::decode(self.opcode).to_string();
+        write!(
+            out,
+            "{:>6} {:>10} {:03X} {:04X}  ",
+            self.frame, self.cycle, self.pc, self.opcode
+        )?;
+        if self.changes.is_empty() {
+            write!(out, "{}", disasm)?;
+        } else {
+            write!(out, "{:<16}", disasm)?;
+        }
+        for change in &self.changes {
+            match *change {
+                Change::V(x, value) => write!(out, " V{:X}={:02X}", x, value)?,
+                Change::I(value) => write!(out, " I={:03X}", value)?,
+                Change::Sp(value) => write!(out, " SP={:X}", value)?,
+                Change::DelayTimer(value) => write!(out, " DT={:02X}", value)?,
+                Change::SoundTimer(value) => write!(out, " ST={:02X}", value)?,
+                Change::Memory(address, value) => write!(out, " [{:03X}]={:02X}", address, value)?,
+            }
+        }
+        writeln!(out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One line per instruction, with its disassembly.
+    #[default]
+    Text,
+    /// A few bytes per instruction, for long runs.
+    Binary,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(TraceFormat::Text),
+            "binary" => Ok(TraceFormat::Binary),
+            _ => Err(format!(
+                "unknown trace format '{}', expected text or binary",
+                s
+            )),
+        }
+    }
+}
+
+/// An inclusive range of addresses, written `200-2FF` in hexadecimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl AddressRange {
+    pub fn contains(&self, address: u16) -> bool {
+        (self.start..=self.end).contains(&address)
+    }
+}
+
+impl FromStr for AddressRange {
+    type Err = String;
+
+    /// Parses `START-END` or a single address, both in hexadecimal with an optional `0x`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |address: &str| {
+            let digits = address
+                .trim()
+                .trim_start_matches("0x")
+                .trim_start_matches("0X");
+            u16::from_str_radix(digits, 16)
+                .ok()
+                .filter(|address| *address < 0x1000)
+                .ok_or_else(|| format!("invalid address '{}'", address.trim()))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if start > end {
+            return Err(format!("address range '{}' ends before it starts", s));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Which instructions make it into a trace. Empty lists let everything through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub ranges: Vec<AddressRange>,
+    pub classes: Vec<OpClass>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: u16, opcode: u16) -> bool {
+        (self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(pc)))
+            && (self.classes.is_empty()
+                || self.classes.contains(&Instruction::decode(opcode).class()))
+    }
+}
+
+/// Writes every executed instruction to a trace file.
+///
+/// Text traces start with a comment line, then have one line per instruction: frame,
+/// cycle, PC, opcode, disassembly and what it changed, such as `VA=02`, `I=2A0` or
+/// `[2A0]=01` for a memory write.
+///
+/// Binary traces start with 8 magic bytes. Frame records are `FF` followed by the number
+/// of frames since the previous frame record as a LEB128 varint. Instruction records are
+/// the number of changes, the PC and opcode as big-endian words, the number of cycles
+/// since the previous instruction record as a varint, then each change: a register
+/// number 0-F, 10 for I, 11 for SP, 12 for DT, 13 for ST or 20 for memory, followed by
+/// the value, with the address first for memory. Words are big-endian.
+pub struct Tracer {
+    out: BufWriter<File>,
+    format: TraceFormat,
+    filter: TraceFilter,
+    frame: u64,
+    cycle: u64,
+    /// Frame and cycle of the last record written, for the deltas of binary traces.
+    written_frame: Option<u64>,
+    written_cycle: u64,
+    current: Option<(u16, u16, Registers)>,
+    writes: Vec<(u16, u8)>,
+    /// First write error, reported by [`Tracer::finish`].
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        format: TraceFormat,
+        filter: TraceFilter,
+    ) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            TraceFormat::Text => writeln!(out, "{}", TEXT_MAGIC)?,
+            TraceFormat::Binary => out.write_all(BINARY_MAGIC)?,
+        }
+        Ok(Self {
+            out,
+            format,
+            filter,
+            frame: 0,
+            cycle: 0,
+            written_frame: None,
+            written_cycle: 0,
+            current: None,
+            writes: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Flushes the trace, reporting the first error that happened while writing it.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.out.flush()
+    }
+
+    fn write(&mut self, record: &TraceRecord) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Text => record.write_text(&mut self.out),
+            TraceFormat::Binary => {
+                if self.written_frame != Some(record.frame) {
+                    let delta = record.frame - self.written_frame.unwrap_or(0);
+                    self.out.write_all(&[FRAME_TAG])?;
+                    write_varint(&mut self.out, delta)?;
+                    self.written_frame = Some(record.frame);
+                }
+                self.out.write_all(&[record.changes.len() as u8])?;
+                self.out.write_all(&record.pc.to_be_bytes())?;
+                self.out.write_all(&record.opcode.to_be_bytes())?;
+                write_varint(&mut self.out, record.cycle - self.written_cycle)?;
+                self.written_cycle = record.cycle;
+                for change in &record.changes {
+                    match *change {
+                        Change::V(x, value) => self.out.write_all(&[x, value])?,
+                        Change::I(value) => {
+                            self.out.write_all(&[0x10])?;
+                            self.out.write_all(&value.to_be_bytes())?;
+                        }
+                        Change::Sp(value) => {
+                            self.out.write_all(&[0x11])?;
+                            self.out.write_all(&value.to_be_bytes())?;
+                        }
+                        Change::DelayTimer(value) => self.out.write_all(&[0x12, value])?,
+                        Change::SoundTimer(value) => self.out.write_all(&[0x13, value])?,
+                        Change::Memory(address, value) => {
+                            self.out.write_all(&[0x20])?;
+                            self.out.write_all(&address.to_be_bytes())?;
+                            self.out.write_all(&[value])?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Observer for Tracer {
+    fn instruction(&mut self, opcode: u16, before: &Registers) {
+        if self.filter.matches(before.pc, opcode) {
+            self.current = Some((before.pc, opcode, *before));
+        }
+    }
+
+    fn memory_write(&mut self, address: u16, value: u8) {
+        if self.current.is_some() {
+            self.writes.push((address, value));
+        }
+    }
+
+    fn executed(&mut self, after: &Registers) {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        let Some((pc, opcode, before)) = self.current.take() else {
+            return;
+        };
+        if self.error.is_some() {
+            return;
+        }
+
+        let mut changes = Vec::new();
+        for x in 0..16 {
+            if before.v[x] != after.v[x] {
+                changes.push(Change::V(x as u8, after.v[x]));
+            }
+        }
+        if before.i != after.i {
+            changes.push(Change::I(after.i));
+        }
+        if before.sp != after.sp {
+            changes.push(Change::Sp(after.sp));
+        }
+        if before.delay_timer != after.delay_timer {
+            changes.push(Change::DelayTimer(after.delay_timer));
+        }
+        if before.sound_timer != after.sound_timer {
+            changes.push(Change::SoundTimer(after.sound_timer));
+        }
+        changes.extend(
+            self.writes
+                .drain(..)
+                .map(|(address, value)| Change::Memory(address, value)),
+        );
+
+        let record = TraceRecord {
+            frame: self.frame,
+            cycle,
+            pc,
+            opcode,
+            changes,
+        };
+        if let Err(err) = self.write(&record) {
+            self.error = Some(err);
+        }
+    }
+
+    fn frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+}
+
+/// The format of a trace starting with `prefix`, if it is a chippers trace.
+pub fn detect_format(prefix: &[u8]) -> Option<TraceFormat> {
+    if prefix.starts_with(BINARY_MAGIC) {
+        Some(TraceFormat::Binary)
+    } else if prefix.starts_with(TEXT_MAGIC.as_bytes()) {
+        Some(TraceFormat::Text)
+    } else {
+        None
+    }
+}
+
+/// Reads back the records of a trace written by [`Tracer`], in either format.
+pub struct TraceReader<R> {
+    input: R,
+    format: TraceFormat,
+    /// Line number in text traces.
+    line: usize,
+    frame: u64,
+    cycle: u64,
+}
+
+impl<R: BufRead> TraceReader<R> {
+    /// Starts reading a trace, failing if it is not one written by [`Tracer`].
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let format = detect_format(input.fill_buf()?)
+            .ok_or_else(|| invalid_data("not a chippers trace".into()))?;
+        let mut reader = Self {
+            input,
+            format,
+            line: 1,
+            frame: 0,
+            cycle: 0,
+        };
+        match format {
+            TraceFormat::Text => {
+                let mut magic = String::new();
+                reader.input.read_line(&mut magic)?;
+            }
+            TraceFormat::Binary => reader.input.read_exact(&mut [0; BINARY_MAGIC.len()])?,
+        }
+        Ok(reader)
+    }
+
+    fn read_text(&mut self) -> io::Result<Option<TraceRecord>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            if !line.trim().is_empty() && !line.starts_with('#') {
+                break;
+            }
+        }
+        let error = |message: &str| invalid_data(format!("line {}: {}", self.line, message));
+        let mut fields = line.split_whitespace();
+        let mut number = |radix| {
+            fields
+                .next()
+                .and_then(|field| u64::from_str_radix(field, radix).ok())
+                .ok_or_else(|| error("bad instruction"))
+        };
+        let frame = number(10)?;
+        let cycle = number(10)?;
+        let pc = number(16)? as u16;
+        let opcode = number(16)? as u16;
+        let mut changes = Vec::new();
+        for field in fields.filter(|field| field.contains('=')) {
+            let (name, value) = field.split_once('=').unwrap();
+            let value = u16::from_str_radix(value, 16).map_err(|_| error("bad value"))?;
+            let change = match name {
+                "I" => Change::I(value),
+                "SP" => Change::Sp(value),
+                "DT" => Change::DelayTimer(value as u8),
+                "ST" => Change::SoundTimer(value as u8),
+                _ if name.starts_with('V') => {
+                    let x = u8::from_str_radix(&name[1..], 16)
+                        .ok()
+                        .filter(|x| *x < 16)
+                        .ok_or_else(|| error("bad register"))?;
+                    Change::V(x, value as u8)
+                }
+                _ if name.starts_with('[') && name.ends_with(']') => {
+                    let address = u16::from_str_radix(&name[1..name.len() - 1], 16)
+                        .map_err(|_| error("bad address"))?;
+                    Change::Memory(address, value as u8)
+                }
+                _ => return Err(error("unknown change")),
+            };
+            changes.push(change);
+        }
+        Ok(Some(TraceRecord {
+            frame,
+            cycle,
+            pc,
+            opcode,
+            changes,
+        }))
+    }
+
+    fn read_binary(&mut self) -> io::Result<Option<TraceRecord>> {
+        let mut tag = [0];
+        loop {
+            if self.input.read(&mut tag)? == 0 {
+                return Ok(None);
+            }
+            if tag[0] != FRAME_TAG {
+                break;
+            }
+            self.frame = self
+                .frame
+                .checked_add(read_varint(&mut self.input)?)
+                .ok_or_else(|| invalid_data("frame number overflows".into()))?;
+        }
+        let pc = read_word(&mut self.input)?;
+        let opcode = read_word(&mut self.input)?;
+        self.cycle = self
+            .cycle
+            .checked_add(read_varint(&mut self.input)?)
+            .ok_or_else(|| invalid_data("cycle number overflows".into()))?;
+        let mut changes = Vec::with_capacity(tag[0] as usize);
+        for _ in 0..tag[0] {
+            let change = match read_byte(&mut self.input)? {
+                x @ 0x00..=0x0F => Change::V(x, read_byte(&mut self.input)?),
+                0x10 => Change::I(read_word(&mut self.input)?),
+                0x11 => Change::Sp(read_word(&mut self.input)?),
+                0x12 => Change::DelayTimer(read_byte(&mut self.input)?),
+                0x13 => Change::SoundTimer(read_byte(&mut self.input)?),
+                0x20 => {
+                    let address = read_word(&mut self.input)?;
+                    Change::Memory(address, read_byte(&mut self.input)?)
+                }
+                kind => return Err(invalid_data(format!("unknown change kind {:02X}", kind))),
+            };
+            changes.push(change);
+        }
+        Ok(Some(TraceRecord {
+            frame: self.frame,
+            cycle: self.cycle,
+            pc,
+            opcode,
+            changes,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for TraceReader<R> {
+    type Item = io::Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.format {
+            TraceFormat::Text => self.read_text(),
+            TraceFormat::Binary => self.read_binary(),
+        };
+        record.transpose()
+    }
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_word(input: &mut impl Read) -> io::Result<u16> {
+    let mut word = [0; 2];
+    input.read_exact(&mut word)?;
+    Ok(u16::from_be_bytes(word))
+}
+
+fn read_varint(input: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_byte(input)?;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("varint too long".into()))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_varint(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+
+    // 200: LD V0, 5
+    // 202: LD I, 300
+    // 204: LD B, V0
+    // 206: ADD V0, 1
+    // 208: JP 206
+    const PROGRAM: &[u16] = &[0x6005, 0xA300, 0xF033, 0x7001, 0x1206];
+
+    /// Runs [`PROGRAM`] for 3 cycles in frame 0 and 4 in frame 3, returning the trace.
+    fn trace(format: TraceFormat, filter: TraceFilter) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "chippers-{}-trace-{:?}-{}",
+            std::process::id(),
+            format,
+            filter.ranges.len() + filter.classes.len()
+        ));
+        let mut tracer = Tracer::create(&path, format, filter).unwrap();
+        let mut chip8 = Chip8::new();
+        chip8.load_raw(PROGRAM);
+        tracer.frame(0);
+        chip8.run_with(3, &mut tracer);
+        tracer.frame(3);
+        chip8.run_with(4, &mut tracer);
+        tracer.finish().unwrap();
+        let trace = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        trace
+    }
+
+    fn read(trace: &[u8]) -> io::Result<Vec<TraceRecord>> {
+        TraceReader::new(trace)?.collect()
+    }
+
+    fn record(frame: u64, cycle: u64, pc: u16, opcode: u16, changes: &[Change]) -> TraceRecord {
+        TraceRecord {
+            frame,
+            cycle,
+            pc,
+            opcode,
+            changes: changes.to_vec(),
+        }
+    }
+
+    fn program_records() -> Vec<TraceRecord> {
+        vec![
+            record(0, 0, 0x200, 0x6005, &[Change::V(0, 5)]),
+            record(0, 1, 0x202, 0xA300, &[Change::I(0x300)]),
+            record(
+                0,
+                2,
+                0x204,
+                0xF033,
+                &[
+                    Change::Memory(0x300, 0),
+                    Change::Memory(0x301, 0),
+                    Change::Memory(0x302, 5),
+                ],
+            ),
+            record(3, 3, 0x206, 0x7001, &[Change::V(0, 6)]),
+            record(3, 4, 0x208, 0x1206, &[]),
+            record(3, 5, 0x206, 0x7001, &[Change::V(0, 7)]),
+            record(3, 6, 0x208, 0x1206, &[]),
+        ]
+    }
+
+    #[test]
+    fn traces_read_back_in_both_formats() {
+        for format in [TraceFormat::Text, TraceFormat::Binary] {
+            let trace = trace(format, TraceFilter::default());
+            assert_eq!(detect_format(&trace), Some(format));
+            assert_eq!(read(&trace).unwrap(), program_records(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn text_traces_show_disassembly_and_changes() {
+        let trace = String::from_utf8(trace(TraceFormat::Text, TraceFilter::default())).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines[0], TEXT_MAGIC);
+        assert!(lines[1].ends_with(" V0=05"), "{}", lines[1]);
+        assert!(
+            lines[3].ends_with(" [300]=00 [301]=00 [302]=05"),
+            "{}",
+            lines[3]
+        );
+        assert!(lines[5].ends_with("JP 0x206"), "{}", lines[5]);
+    }
+
+    #[test]
+    fn binary_traces_delta_encode_frames_and_cycles() {
+        let trace = trace(TraceFormat::Binary, TraceFilter::default());
+        let records = &trace[BINARY_MAGIC.len()..];
+        // Frame 0 is written as a delta from 0, then the first instruction
+        assert_eq!(&records[..8], &[FRAME_TAG, 0, 1, 0x02, 0x00, 0x60, 0x05, 0]);
+        assert_eq!(&records[8..10], &[0x00, 0x05]);
+        // The second frame record is 3 frames after the first
+        let frame = records[10..]
+            .iter()
+            .position(|&byte| byte == FRAME_TAG)
+            .unwrap();
+        assert_eq!(records[10 + frame + 1], 3);
+    }
+
+    #[test]
+    fn filtered_records_keep_their_cycle_numbers() {
+        let filter = TraceFilter {
+            ranges: vec!["206".parse().unwrap()],
+            classes: Vec::new(),
+        };
+        let expected: Vec<_> = program_records()
+            .into_iter()
+            .filter(|record| record.pc == 0x206)
+            .collect();
+        for format in [TraceFormat::Text, TraceFormat::Binary] {
+            let trace = trace(format, filter.clone());
+            assert_eq!(read(&trace).unwrap(), expected, "{:?}", format);
+        }
+
+        let filter = TraceFilter {
+            ranges: Vec::new(),
+            classes: vec!["flow".parse().unwrap(), "memory".parse().unwrap()],
+        };
+        let opcodes: Vec<u16> = read(&trace(TraceFormat::Binary, filter))
+            .unwrap()
+            .iter()
+            .map(|record| record.opcode)
+            .collect();
+        assert_eq!(opcodes, [0xF033, 0x1206, 0x1206]);
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 0x7F, 0x80, 300, 0x3FFF, 0x4000, u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value).unwrap();
+            assert_eq!(
+                bytes.len(),
+                (64 - value.leading_zeros() as usize).max(1).div_ceil(7)
+            );
+            assert_eq!(read_varint(&mut &bytes[..]).unwrap(), value);
+        }
+        assert!(read_varint(&mut &[0x80; 10][..]).is_err());
+        assert!(read_varint(&mut &[0x80, 0x80][..]).is_err());
+    }
+
+    #[test]
+    fn truncated_binary_traces_fail() {
+        let trace = trace(TraceFormat::Binary, TraceFilter::default());
+        let records = program_records();
+        for len in 0..trace.len() {
+            match read(&trace[..len]) {
+                // Cutting between records loses the records after the cut
+                Ok(read) => assert_eq!(read, records[..read.len()], "cut at {}", len),
+                Err(err) => assert!(
+                    matches!(
+                        err.kind(),
+                        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+                    ),
+                    "cut at {}: {}",
+                    len,
+                    err
+                ),
+            }
+        }
+        // Cutting inside a record is always an error
+        assert!(read(&trace[..BINARY_MAGIC.len() + 5]).is_err());
+    }
+
+    #[test]
+    fn corrupt_binary_traces_fail() {
+        let corrupt = |records: &[u8]| {
+            let mut trace = BINARY_MAGIC.to_vec();
+            trace.extend(records);
+            read(&trace).unwrap_err().to_string()
+        };
+        assert_eq!(
+            corrupt(&[1, 0x02, 0x00, 0x60, 0x05, 0, 0x14, 0x05]),
+            "unknown change kind 14"
+        );
+        assert_eq!(
+            corrupt(&[FRAME_TAG, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+            "varint too long"
+        );
+        let max = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+        let mut frames = vec![FRAME_TAG];
+        frames.extend(max);
+        frames.push(FRAME_TAG);
+        frames.extend(max);
+        assert_eq!(corrupt(&frames), "frame number overflows");
+        let mut cycles = vec![0, 0x02, 0x00, 0x60, 0x05];
+        cycles.extend(max);
+        cycles.extend([0, 0x02, 0x00, 0x60, 0x05]);
+        cycles.extend(max);
+        assert_eq!(corrupt(&cycles), "cycle number overflows");
+        assert!(read(b"C8TRACE\x02").is_err());
+    }
+
+    #[test]
+    fn malformed_text_traces_fail() {
+        let error = |line: &str| {
+            let trace = format!("{}\n# a comment\n\n{}\n", TEXT_MAGIC, line);
+            read(trace.as_bytes()).unwrap_err().to_string()
+        };
+        assert_eq!(error("0 0 200"), "line 4: bad instruction");
+        assert_eq!(error("0 x 200 6005"), "line 4: bad instruction");
+        assert_eq!(error("0 0 200 6005  LD V0, 5 V0=XY"), "line 4: bad value");
+        assert_eq!(
+            error("0 0 200 6005  LD V0, 5 V10=05"),
+            "line 4: bad register"
+        );
+        assert_eq!(error("0 0 200 6005  LD V0, 5 V=05"), "line 4: bad register");
+        assert_eq!(
+            error("0 0 204 F033  LD B, V0 [3G0]=00"),
+            "line 4: bad address"
+        );
+        assert_eq!(
+            error("0 0 200 6005  LD V0, 5 PC=202"),
+            "line 4: unknown change"
+        );
+        assert!(read(b"0 0 200 6005").is_err());
+    }
+
+    #[test]
+    fn address_ranges_parse() {
+        let range = |s: &str| s.parse::<AddressRange>();
+        assert_eq!(
+            range("200-2FF"),
+            Ok(AddressRange {
+                start: 0x200,
+                end: 0x2FF
+            })
+        );
+        assert_eq!(
+            range("0x200 - 0x2ff"),
+            Ok(AddressRange {
+                start: 0x200,
+                end: 0x2FF
+            })
+        );
+        assert_eq!(
+            range("2A0"),
+            Ok(AddressRange {
+                start: 0x2A0,
+                end: 0x2A0
+            })
+        );
+        assert_eq!(
+            range("FFF"),
+            Ok(AddressRange {
+                start: 0xFFF,
+                end: 0xFFF
+            })
+        );
+        assert_eq!(range("1000"), Err("invalid address '1000'".to_string()));
+        assert_eq!(range("200-"), Err("invalid address ''".to_string()));
+        assert_eq!(range("2G0"), Err("invalid address '2G0'".to_string()));
+        assert_eq!(
+            range("300-200"),
+            Err("address range '300-200' ends before it starts".to_string())
+        );
+        assert!(range("200-2FF").unwrap().contains(0x2FF));
+        assert!(!range("200-2FF").unwrap().contains(0x300));
+    }
+
+    #[test]
+    fn filters_match_ranges_and_classes() {
+        let filter = TraceFilter {
+            ranges: vec!["200-20F".parse().unwrap(), "300".parse().unwrap()],
+            classes: vec!["Display".parse().unwrap()],
+        };
+        assert!(filter.matches(0x200, 0x00E0));
+        assert!(filter.matches(0x300, 0xD015));
+        assert!(!filter.matches(0x210, 0x00E0));
+        assert!(!filter.matches(0x200, 0x6005));
+        assert!(TraceFilter::default().matches(0xFFE, 0x0000));
+        assert!("sound".parse::<OpClass>().is_err());
+        assert_eq!("BINARY".parse(), Ok(TraceFormat::Binary));
+        assert!("json".parse::<TraceFormat>().is_err());
+    }
+}
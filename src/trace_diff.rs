@@ -0,0 +1,783 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines, Write};
+use std::path::Path;
+
+use crate::disasm::Instruction;
+use crate::trace::{self, Change, TraceReader};
+
+/// Instructions searched at the start of both traces for a common starting point.
+const ALIGN_WINDOW: usize = 1000;
+
+/// Agreeing instructions shown before a divergence.
+const CONTEXT: usize = 5;
+
+/// Registers as far as a trace tells them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KnownRegisters {
+    pub v: [Option<u8>; 16],
+    pub i: Option<u16>,
+    pub sp: Option<u16>,
+    pub delay_timer: Option<u8>,
+    pub sound_timer: Option<u8>,
+}
+
+impl KnownRegisters {
+    /// The registers at power-on.
+    fn power_on() -> Self {
+        Self {
+            v: [Some(0); 16],
+            i: Some(0),
+            sp: Some(0),
+            delay_timer: Some(0),
+            sound_timer: Some(0),
+        }
+    }
+}
+
+/// An executed instruction read from any supported trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub frame: Option<u64>,
+    pub cycle: Option<u64>,
+    pub pc: u16,
+    pub opcode: Option<u16>,
+    /// The registers the instruction started from.
+    pub before: KnownRegisters,
+    /// The memory the instruction wrote, if the trace records writes.
+    pub writes: Option<Vec<(u16, u8)>>,
+}
+
+impl Step {
+    /// Whether two steps are the same instruction, as far as both traces tell.
+    fn same_instruction(&self, other: &Step) -> bool {
+        self.pc == other.pc
+            && match (self.opcode, other.opcode) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+    }
+}
+
+/// Reads the instructions of a trace, in any of the supported formats.
+///
+/// Besides chippers traces, this imports the line-based logs of other emulators, guessing
+/// the format line by line:
+///
+/// - register dumps, with `KEY:VALUE` or `KEY=VALUE` pairs such as
+///   `PC:0200 OP:00E0 I:0000 V0:00 ... VF:00 SP:0 DT:00 ST:00`, all in hexadecimal. `PC` is
+///   required, the others are compared when present;
+/// - address listings, starting with the PC and the opcode in hexadecimal, such as
+///   `0200: 00E0 CLS`.
+///
+/// Other lines are skipped. An instruction that runs again without moving the PC, such as
+/// Fx0A waiting for a key, is reported only once, since emulators log waits differently.
+pub struct StepReader {
+    source: Source,
+    last: Option<(u16, Option<u16>)>,
+}
+
+enum Source {
+    Chippers {
+        records: TraceReader<BufReader<File>>,
+        registers: KnownRegisters,
+        frame: Option<u64>,
+        cycle: Option<u64>,
+    },
+    Log {
+        lines: Lines<BufReader<File>>,
+    },
+}
+
+impl StepReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut input = BufReader::new(File::open(path)?);
+        let source = if trace::detect_format(input.fill_buf()?).is_some() {
+            Source::Chippers {
+                records: TraceReader::new(input)?,
+                registers: KnownRegisters::default(),
+                frame: None,
+                cycle: None,
+            }
+        } else {
+            Source::Log {
+                lines: input.lines(),
+            }
+        };
+        Ok(Self { source, last: None })
+    }
+
+    fn read(&mut self) -> io::Result<Option<Step>> {
+        match &mut self.source {
+            Source::Chippers {
+                records,
+                registers,
+                frame,
+                cycle,
+            } => {
+                let Some(record) = records.next().transpose()? else {
+                    return Ok(None);
+                };
+                match *cycle {
+                    // Chippers traces begin at power-on unless they are filtered
+                    None if record.cycle == 0 => *registers = KnownRegisters::power_on(),
+                    // Filtered out instructions changed who knows what
+                    Some(previous) if record.cycle != previous + 1 => {
+                        *registers = KnownRegisters::default()
+                    }
+                    _ => (),
+                }
+                if frame.is_some_and(|frame| frame != record.frame) {
+                    // The timers ticked between frames, which the trace does not show
+                    registers.delay_timer = None;
+                    registers.sound_timer = None;
+                }
+                *frame = Some(record.frame);
+                *cycle = Some(record.cycle);
+
+                let before = *registers;
+                let mut writes = Vec::new();
+                for change in record.changes {
+                    match change {
+                        Change::V(x, value) => registers.v[x as usize] = Some(value),
+                        Change::I(value) => registers.i = Some(value),
+                        Change::Sp(value) => registers.sp = Some(value),
+                        Change::DelayTimer(value) => registers.delay_timer = Some(value),
+                        Change::SoundTimer(value) => registers.sound_timer = Some(value),
+                        Change::Memory(address, value) => writes.push((address, value)),
+                    }
+                }
+                Ok(Some(Step {
+                    frame: Some(record.frame),
+                    cycle: Some(record.cycle),
+                    pc: record.pc,
+                    opcode: Some(record.opcode),
+                    before,
+                    writes: Some(writes),
+                }))
+            }
+            Source::Log { lines } => {
+                for line in lines {
+                    if let Some(step) = parse_log_line(&line?) {
+                        return Ok(Some(step));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Iterator for StepReader {
+    type Item = io::Result<Step>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let step = match self.read() {
+                Ok(Some(step)) => step,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+            let key = (step.pc, step.opcode);
+            if self.last.replace(key) != Some(key) {
+                return Some(Ok(step));
+            }
+        }
+    }
+}
+
+/// Parses a line of another emulator's log, if it describes an instruction.
+fn parse_log_line(line: &str) -> Option<Step> {
+    let tokens: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '|')
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    // Pair up keys and values, which may be split across tokens as in `PC: 0200`
+    let mut pairs = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = tokens[index];
+        match token.find([':', '=']) {
+            Some(at) if at + 1 == token.len() && index + 1 < tokens.len() => {
+                pairs.push((&token[..at], tokens[index + 1]));
+                index += 1;
+            }
+            Some(at) if at > 0 => pairs.push((&token[..at], &token[at + 1..])),
+            _ => (),
+        }
+        index += 1;
+    }
+    let value = |key: &str| {
+        pairs
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .and_then(|(_, value)| parse_hex(value))
+    };
+
+    if let Some(pc) = value("PC") {
+        let mut before = KnownRegisters::default();
+        for x in 0..16 {
+            before.v[x] = value(&format!("V{:X}", x)).map(|value| value as u8);
+        }
+        before.i = value("I");
+        before.sp = value("SP");
+        before.delay_timer = value("DT").or_else(|| value("DELAY")).map(|t| t as u8);
+        before.sound_timer = value("ST").or_else(|| value("SOUND")).map(|t| t as u8);
+        return Some(Step {
+            frame: value("FRAME").map(u64::from),
+            cycle: value("CYCLE").map(u64::from),
+            pc,
+            opcode: value("OP").or_else(|| value("OPCODE")),
+            before,
+            writes: None,
+        });
+    }
+
+    let pc = parse_hex(tokens.first()?.trim_end_matches(':')).filter(|pc| *pc < 0x1000)?;
+    let opcode = tokens.get(1).filter(|token| token.len() >= 4)?;
+    Some(Step {
+        frame: None,
+        cycle: None,
+        pc,
+        opcode: Some(parse_hex(opcode)?),
+        before: KnownRegisters::default(),
+        writes: None,
+    })
+}
+
+fn parse_hex(value: &str) -> Option<u16> {
+    let digits = value
+        .trim_start_matches("0x")
+        .trim_start_matches("0X")
+        .trim_start_matches('$');
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// Where two traces first disagree.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub a: Step,
+    pub b: Step,
+    /// Every field that differs, such as `V3: 05 vs 06`.
+    pub differences: Vec<String>,
+    /// Whether the registers already differed before the instruction ran, which points at
+    /// the instruction before it.
+    pub before_differs: bool,
+    /// The last agreeing instructions of the first trace, oldest first.
+    pub context: Vec<Step>,
+}
+
+/// The outcome of comparing two traces.
+#[derive(Debug, Clone)]
+pub struct TraceDiff {
+    /// Leading instructions of each trace skipped to line them up.
+    pub skipped: [usize; 2],
+    /// Instructions that agreed.
+    pub agreed: u64,
+    pub divergence: Option<Divergence>,
+    /// Which trace still had instructions when the other one ended, if they did not end
+    /// together.
+    pub longer: Option<usize>,
+}
+
+/// Compares two traces instruction by instruction.
+pub fn diff(a: StepReader, b: StepReader) -> io::Result<TraceDiff> {
+    let mut a = a.buffered()?;
+    let mut b = b.buffered()?;
+    let skipped = align(&a.buffer, &b.buffer);
+    a.buffer.drain(..skipped[0]);
+    b.buffer.drain(..skipped[1]);
+
+    let mut agreed = 0;
+    let mut context = VecDeque::new();
+    loop {
+        let (step_a, step_b) = match (a.next()?, b.next()?) {
+            (Some(step_a), Some(step_b)) => (step_a, step_b),
+            (None, None) => return Ok(finish(skipped, agreed, None, None)),
+            (Some(_), None) => return Ok(finish(skipped, agreed, None, Some(0))),
+            (None, Some(_)) => return Ok(finish(skipped, agreed, None, Some(1))),
+        };
+        let (differences, before_differs) = compare(&step_a, &step_b);
+        if !differences.is_empty() {
+            let divergence = Divergence {
+                a: step_a,
+                b: step_b,
+                differences,
+                before_differs,
+                context: context.into(),
+            };
+            return Ok(finish(skipped, agreed, Some(divergence), None));
+        }
+        agreed += 1;
+        if context.len() == CONTEXT {
+            context.pop_front();
+        }
+        context.push_back(step_a);
+    }
+}
+
+fn finish(
+    skipped: [usize; 2],
+    agreed: u64,
+    divergence: Option<Divergence>,
+    longer: Option<usize>,
+) -> TraceDiff {
+    TraceDiff {
+        skipped,
+        agreed,
+        divergence,
+        longer,
+    }
+}
+
+/// Finds how many leading instructions to skip in each trace so they start with the same
+/// instruction, preferring to skip as little as possible.
+fn align(a: &VecDeque<Step>, b: &VecDeque<Step>) -> [usize; 2] {
+    let mut best: Option<[usize; 2]> = None;
+    for (i, step_a) in a.iter().enumerate() {
+        if best.is_some_and(|[best_a, best_b]| i >= best_a + best_b) {
+            break;
+        }
+        if let Some(j) = b.iter().position(|step_b| step_a.same_instruction(step_b)) {
+            if best.is_none_or(|[best_a, best_b]| i + j < best_a + best_b) {
+                best = Some([i, j]);
+            }
+        }
+    }
+    best.unwrap_or([0, 0])
+}
+
+/// Lists the differences between two steps, and whether any of them is in the registers
+/// the instructions started from.
+fn compare(a: &Step, b: &Step) -> (Vec<String>, bool) {
+    let mut differences = Vec::new();
+    let (ra, rb) = (&a.before, &b.before);
+    for x in 0..16 {
+        let value = |registers: &KnownRegisters| registers.v[x].map(u16::from);
+        differ(
+            &mut differences,
+            &format!("V{:X}", x),
+            value(ra),
+            value(rb),
+            2,
+        );
+    }
+    differ(&mut differences, "I", ra.i, rb.i, 3);
+    differ(&mut differences, "SP", ra.sp, rb.sp, 1);
+    let timer = |value: Option<u8>| value.map(u16::from);
+    let (dt_a, dt_b) = (timer(ra.delay_timer), timer(rb.delay_timer));
+    differ(&mut differences, "DT", dt_a, dt_b, 2);
+    let (st_a, st_b) = (timer(ra.sound_timer), timer(rb.sound_timer));
+    differ(&mut differences, "ST", st_a, st_b, 2);
+    let before_differs = !differences.is_empty();
+
+    differ(&mut differences, "PC", Some(a.pc), Some(b.pc), 3);
+    differ(&mut differences, "opcode", a.opcode, b.opcode, 4);
+    if let (Some(writes_a), Some(writes_b)) = (&a.writes, &b.writes) {
+        if writes_a != writes_b {
+            differences.push(format!(
+                "memory writes: {} vs {}",
+                format_writes(writes_a),
+                format_writes(writes_b)
+            ));
+        }
+    }
+    (differences, before_differs)
+}
+
+/// Notes that `name` differs, if both traces know its value.
+fn differ(
+    differences: &mut Vec<String>,
+    name: &str,
+    a: Option<u16>,
+    b: Option<u16>,
+    digits: usize,
+) {
+    if let (Some(a), Some(b)) = (a, b) {
+        if a != b {
+            differences.push(format!("{}: {:0digits$X} vs {:0digits$X}", name, a, b));
+        }
+    }
+}
+
+fn format_writes(writes: &[(u16, u8)]) -> String {
+    if writes.is_empty() {
+        return "none".into();
+    }
+    let writes: Vec<_> = writes
+        .iter()
+        .map(|(address, value)| format!("[{:03X}]={:02X}", address, value))
+        .collect();
+    writes.join(" ")
+}
+
+/// A [`StepReader`] with the first instructions read ahead for alignment.
+struct Buffered {
+    reader: StepReader,
+    buffer: VecDeque<Step>,
+}
+
+impl StepReader {
+    fn buffered(mut self) -> io::Result<Buffered> {
+        let mut buffer = VecDeque::new();
+        while buffer.len() < ALIGN_WINDOW {
+            match self.next().transpose()? {
+                Some(step) => buffer.push_back(step),
+                None => break,
+            }
+        }
+        Ok(Buffered {
+            reader: self,
+            buffer,
+        })
+    }
+}
+
+impl Buffered {
+    fn next(&mut self) -> io::Result<Option<Step>> {
+        match self.buffer.pop_front() {
+            Some(step) => Ok(Some(step)),
+            None => self.reader.next().transpose(),
+        }
+    }
+}
+
+impl TraceDiff {
+    /// Writes a report for people, calling the traces by `names`.
+    pub fn write_report(&self, out: &mut impl Write, names: [&str; 2]) -> io::Result<()> {
+        let width = names[0].len().max(names[1].len());
+        for (name, skipped) in names.iter().zip(self.skipped) {
+            if skipped > 0 {
+                writeln!(
+                    out,
+                    "Skipped {} leading instructions of {} to line the traces up",
+                    skipped, name
+                )?;
+            }
+        }
+
+        let Some(divergence) = &self.divergence else {
+            write!(out, "The traces agree for {} instructions", self.agreed)?;
+            match self.longer {
+                Some(longer) => writeln!(out, ", then {} ends first", names[1 - longer])?,
+                None => writeln!(out)?,
+            }
+            return Ok(());
+        };
+
+        writeln!(
+            out,
+            "The traces diverge after {} agreeing instructions:",
+            self.agreed
+        )?;
+        writeln!(out)?;
+        for (name, step) in names.iter().zip([&divergence.a, &divergence.b]) {
+            writeln!(out, "  {:width$}  {}", name, describe(step))?;
+        }
+        writeln!(out)?;
+        for difference in &divergence.differences {
+            writeln!(out, "  {}", difference)?;
+        }
+        if divergence.before_differs {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "The registers already differed before this instruction ran, so the one\n\
+                 before it is the likely culprit."
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "Registers before the instruction:")?;
+        write!(out, "  {:width$}  PC  I   SP DT ST", "")?;
+        for x in 0..16 {
+            write!(out, " V{:X}", x)?;
+        }
+        writeln!(out)?;
+        for (name, step) in names.iter().zip([&divergence.a, &divergence.b]) {
+            let registers = &step.before;
+            write!(
+                out,
+                "  {:width$}  {:03X} {}",
+                name,
+                step.pc,
+                known(registers.i, 3)
+            )?;
+            write!(out, " {} ", known(registers.sp, 2))?;
+            write!(out, "{} ", known(registers.delay_timer.map(u16::from), 2))?;
+            write!(out, "{}", known(registers.sound_timer.map(u16::from), 2))?;
+            for value in registers.v {
+                write!(out, " {}", known(value.map(u16::from), 2))?;
+            }
+            writeln!(out)?;
+        }
+
+        if !divergence.context.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "Last agreeing instructions in {}:", names[0])?;
+            for step in &divergence.context {
+                writeln!(out, "  {}", describe(step))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Frame, cycle, address, opcode and disassembly of a step, as far as they are known.
+fn describe(step: &Step) -> String {
+    let mut text = String::new();
+    if let Some(frame) = step.frame {
+        text += &format!("frame {}  ", frame);
+    }
+    if let Some(cycle) = step.cycle {
+        text += &format!("cycle {}  ", cycle);
+    }
+    text += &format!("{:03X}", step.pc);
+    if let Some(opcode) = step.opcode {
+        text += &format!(" {:04X}  {}", opcode, Instruction::decode(opcode));
+    }
+    text
+}
+
+fn known(value: Option<u16>, digits: usize) -> String {
+    match value {
+        Some(value) => format!("{:0digits$X}", value),
+        None => "?".repeat(digits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Writes `contents` to a temporary trace file called `name`.
+    fn write_log(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("chippers-{}-{}.log", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn steps(name: &str, contents: &str) -> Vec<Step> {
+        let path = write_log(name, contents);
+        let steps = StepReader::open(&path).unwrap().collect::<io::Result<_>>();
+        std::fs::remove_file(&path).unwrap();
+        steps.unwrap()
+    }
+
+    fn diff_logs(name: &str, a: &str, b: &str) -> TraceDiff {
+        let paths = [
+            write_log(&format!("{}-a", name), a),
+            write_log(&format!("{}-b", name), b),
+        ];
+        let diff = diff(
+            StepReader::open(&paths[0]).unwrap(),
+            StepReader::open(&paths[1]).unwrap(),
+        );
+        for path in paths {
+            std::fs::remove_file(path).unwrap();
+        }
+        diff.unwrap()
+    }
+
+    /// A register dump log of a loop adding 1 to V0 at 200 and jumping back from 202,
+    /// `steps` instructions long.
+    fn counting_log(steps: usize) -> String {
+        (0..steps)
+            .map(|step| {
+                let (pc, opcode) = [(0x200, 0x7001), (0x202, 0x1200)][step % 2];
+                let v0 = (step / 2 + step % 2) as u8;
+                format!("PC:{:04X} OP:{:04X} V0:{:02X}\n", pc, opcode, v0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn register_dumps_are_imported() {
+        let line = "PC:0200 OP:00E0 I:02A0 V0:01 V1:02 VF:0F SP:1 DT:3C ST:00";
+        let step = parse_log_line(line).unwrap();
+        assert_eq!((step.pc, step.opcode), (0x200, Some(0x00E0)));
+        assert_eq!(step.before.v[..3], [Some(1), Some(2), None]);
+        assert_eq!(step.before.v[0xF], Some(0xF));
+        assert_eq!(step.before.i, Some(0x2A0));
+        assert_eq!(step.before.sp, Some(1));
+        assert_eq!(step.before.delay_timer, Some(0x3C));
+        assert_eq!(step.before.sound_timer, Some(0));
+        assert_eq!(step.writes, None);
+
+        // Keys and values split across tokens, `=` pairs and prefixed numbers
+        let step =
+            parse_log_line("frame=2 | cycle=0x10 | PC: 0x0204, opcode=$A2A0 DELAY=5").unwrap();
+        assert_eq!((step.frame, step.cycle), (Some(2), Some(0x10)));
+        assert_eq!((step.pc, step.opcode), (0x204, Some(0xA2A0)));
+        assert_eq!(step.before.delay_timer, Some(5));
+        assert_eq!(step.before.i, None);
+    }
+
+    #[test]
+    fn address_listings_are_imported() {
+        let step = parse_log_line("0200: 00E0 CLS").unwrap();
+        assert_eq!((step.pc, step.opcode), (0x200, Some(0x00E0)));
+        assert_eq!(step.before, KnownRegisters::default());
+        let step = parse_log_line("  0x2A4  D015").unwrap();
+        assert_eq!((step.pc, step.opcode), (0x2A4, Some(0xD015)));
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        for line in [
+            "",
+            "Loading ROM breakout.ch8",
+            "PC:XYZ OP:00E0",
+            "0200: 0E",
+            "0200: ZZZZ CLS",
+            "1200: 00E0",
+            "0200:",
+        ] {
+            assert_eq!(parse_log_line(line), None, "{:?}", line);
+        }
+        let steps = steps(
+            "malformed",
+            "Starting\n0200: 6005\nnot an instruction\n0202: 12\n0202: 1202\n",
+        );
+        let pcs: Vec<u16> = steps.iter().map(|step| step.pc).collect();
+        assert_eq!(pcs, [0x200, 0x202]);
+    }
+
+    #[test]
+    fn repeated_instructions_are_read_once() {
+        let steps = steps(
+            "repeated",
+            "0200: F00A\n0200: F00A\n0200: F00A\n0202: 1202\n0202: 1202\n0200: F00A\n",
+        );
+        let pcs: Vec<u16> = steps.iter().map(|step| step.pc).collect();
+        assert_eq!(pcs, [0x200, 0x202, 0x200]);
+    }
+
+    #[test]
+    fn chippers_traces_track_registers() {
+        let trace = format!(
+            "{}\n{}\n{}\n{}\n",
+            "# chippers trace 1",
+            "     0          0 200 6005  LD V0, 0x05      V0=05",
+            "     0          1 202 A2A0  LD I, 0x2A0      I=2A0",
+            "     1          3 206 F033  LD B, V0         [2A0]=00 [2A1]=00 [2A2]=05",
+        );
+        let steps = steps("chippers", &trace);
+        assert_eq!(steps[0].before, KnownRegisters::power_on());
+        assert_eq!(steps[1].before.v[0], Some(5));
+        assert_eq!(steps[1].before.delay_timer, Some(0));
+        // Cycle 2 was filtered out, so nothing is known after it
+        assert_eq!(steps[2].before, KnownRegisters::default());
+        assert_eq!(
+            steps[2].writes,
+            Some(vec![(0x2A0, 0), (0x2A1, 0), (0x2A2, 5)])
+        );
+    }
+
+    #[test]
+    fn identical_traces_agree() {
+        let log = counting_log(20);
+        let diff = diff_logs("identical", &log, &log);
+        assert_eq!(diff.skipped, [0, 0]);
+        assert_eq!(diff.agreed, 20);
+        assert!(diff.divergence.is_none());
+        assert_eq!(diff.longer, None);
+
+        let diff = diff_logs("shorter", &log, &counting_log(15));
+        assert_eq!(diff.agreed, 15);
+        assert!(diff.divergence.is_none());
+        assert_eq!(diff.longer, Some(0));
+        let mut report = Vec::new();
+        diff.write_report(&mut report, ["a.log", "b.log"]).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "The traces agree for 15 instructions, then b.log ends first\n"
+        );
+    }
+
+    #[test]
+    fn divergences_are_found_at_their_step() {
+        let a = counting_log(20);
+        let b = a.replace("PC:0200 OP:7001 V0:04", "PC:0200 OP:7001 V0:05");
+        let diff = diff_logs("divergence", &a, &b);
+        let divergence = diff.divergence.unwrap();
+        assert_eq!(diff.agreed, 8);
+        assert_eq!(divergence.a.pc, 0x200);
+        assert_eq!(divergence.differences, ["V0: 04 vs 05"]);
+        assert!(divergence.before_differs);
+        assert_eq!(divergence.context.len(), CONTEXT);
+        assert_eq!(divergence.context[CONTEXT - 1].pc, 0x202);
+
+        // A different opcode at the same address is not a register difference
+        let b = a.replacen("PC:0202 OP:1200", "PC:0202 OP:1202", 1);
+        let divergence = diff_logs("opcode", &a, &b).divergence.unwrap();
+        assert_eq!(divergence.differences, ["opcode: 1200 vs 1202"]);
+        assert!(!divergence.before_differs);
+        assert_eq!(divergence.context.len(), 1);
+    }
+
+    #[test]
+    fn traces_resync_after_skipped_instructions() {
+        let a = counting_log(20);
+        // Another emulator logging its own start-up code first
+        let b = format!("PC:0000 OP:00E0\nPC:0002 OP:1004\nPC:0004 OP:1200\n{}", a);
+        let diff = diff_logs("resync", &a, &b);
+        assert_eq!(diff.skipped, [0, 3]);
+        assert_eq!(diff.agreed, 20);
+        assert!(diff.divergence.is_none());
+
+        let diff = diff_logs("resync-swapped", &b, &a);
+        assert_eq!(diff.skipped, [3, 0]);
+        assert_eq!(diff.agreed, 20);
+        let mut report = Vec::new();
+        diff.write_report(&mut report, ["b.log", "a.log"]).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.starts_with("Skipped 3 leading instructions of b.log"));
+    }
+
+    #[test]
+    fn divergences_past_the_alignment_window_are_found() {
+        let a = counting_log(2 * ALIGN_WINDOW);
+        let step = ALIGN_WINDOW + 500;
+        let b: String = a
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                if index == step {
+                    format!("{}\n", line.replace("OP:7001", "OP:7002"))
+                } else {
+                    format!("{}\n", line)
+                }
+            })
+            .collect();
+        let diff = diff_logs("late", &a, &b);
+        assert_eq!(diff.skipped, [0, 0]);
+        assert_eq!(diff.agreed, step as u64);
+        assert_eq!(
+            diff.divergence.unwrap().differences,
+            ["opcode: 7001 vs 7002"]
+        );
+    }
+
+    #[test]
+    fn traces_without_a_common_start_in_the_window_are_not_aligned() {
+        let a = counting_log(20);
+        let b: String = (0..ALIGN_WINDOW)
+            .map(|step| format!("PC:{:04X} OP:00E0\n", 0x300 + step % 2 * 2))
+            .chain(std::iter::once(a.clone()))
+            .collect();
+        let diff = diff_logs("unaligned", &a, &b);
+        assert_eq!(diff.skipped, [0, 0]);
+        assert_eq!(diff.agreed, 0);
+        let divergence = diff.divergence.unwrap();
+        assert_eq!(
+            divergence.differences,
+            ["PC: 200 vs 300", "opcode: 7001 vs 00E0"]
+        );
+        assert!(divergence.context.is_empty());
+    }
+}
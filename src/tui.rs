@@ -0,0 +1,285 @@
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{
+    self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+
+use crate::chip8::{Chip8, HEIGHT, WIDTH};
+use crate::emulator::{Frontend, InputEvent};
+use crate::keymap::{self, Action, Hotkey, Keymap};
+
+/// Most terminals only report key presses, so a key counts as released once it has not
+/// been pressed or auto-repeated for this long. The same goes for the fast forward hotkey.
+///
+/// Auto-repeat starts only after a delay, typically 250 to 500ms, so a shorter timeout
+/// would release a held key before its first repeat arrives. The cost of a longer one is
+/// that every tap holds its key down for this long, which makes quick taps in games that
+/// react to each press sluggish. Terminals reporting releases are not affected.
+const KEY_RELEASE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Column where the register panel starts.
+const PANEL_COLUMN: u16 = WIDTH as u16 + 2;
+
+/// Runs the emulator inside a terminal.
+///
+/// The screen is drawn with Unicode half blocks, two CHIP-8 pixels per character cell,
+/// next to a panel showing the registers. The terminal is restored when this is dropped.
+pub struct TerminalApp {
+    stdout: Stdout,
+    keymap: Keymap,
+    key_pressed_at: [Option<Instant>; 16],
+    fast_forward_at: Option<Instant>,
+    release_events: bool,
+    paused: bool,
+}
+
+impl TerminalApp {
+    /// Puts the terminal into raw mode on the alternate screen.
+    ///
+    /// Hotkeys for screenshots, recording and fullscreen are ignored in the terminal.
+    pub fn new(keymap: Keymap) -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+        // Terminals implementing the kitty keyboard protocol can report key releases
+        let release_events = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if release_events {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Self {
+            stdout,
+            keymap,
+            key_pressed_at: [None; 16],
+            fast_forward_at: None,
+            release_events,
+            paused: false,
+        })
+    }
+
+    fn translate(&mut self, key_event: KeyEvent) -> Option<InputEvent> {
+        let KeyEvent {
+            code,
+            modifiers,
+            kind,
+            ..
+        } = key_event;
+
+        if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(InputEvent::Quit);
+        }
+
+        let action = self.keymap.action(key_name(code)?)?;
+        let released = kind == KeyEventKind::Release;
+        match action {
+            Action::Key(key) if released => {
+                self.key_pressed_at[key as usize] = None;
+                Some(InputEvent::KeyUp(key))
+            }
+            Action::Key(key) => {
+                self.key_pressed_at[key as usize] = Some(Instant::now());
+                Some(InputEvent::KeyDown(key))
+            }
+            Action::Hotkey(Hotkey::FastForward) if released => {
+                self.fast_forward_at = None;
+                Some(InputEvent::FastForward(false))
+            }
+            Action::Hotkey(Hotkey::FastForward) => {
+                self.fast_forward_at = Some(Instant::now());
+                Some(InputEvent::FastForward(true))
+            }
+            Action::Hotkey(_) if kind != KeyEventKind::Press => None,
+            Action::Hotkey(Hotkey::Quit) => Some(InputEvent::Quit),
+            Action::Hotkey(Hotkey::Pause) => Some(InputEvent::Pause),
+            Action::Hotkey(Hotkey::Reset) => Some(InputEvent::Reset),
+            Action::Hotkey(Hotkey::SaveState) => Some(InputEvent::SaveState),
+            Action::Hotkey(Hotkey::LoadState) => Some(InputEvent::LoadState),
+            Action::Hotkey(Hotkey::Screenshot | Hotkey::Record | Hotkey::Fullscreen) => None,
+        }
+    }
+
+    /// Releases the first key that has not been repeated within the timeout.
+    fn release_stale_key(&mut self) -> Option<InputEvent> {
+        if self.release_events {
+            return None;
+        }
+        let is_stale = |pressed_at: &Option<Instant>| {
+            pressed_at.is_some_and(|pressed_at| pressed_at.elapsed() >= KEY_RELEASE_TIMEOUT)
+        };
+        if is_stale(&self.fast_forward_at) {
+            self.fast_forward_at = None;
+            return Some(InputEvent::FastForward(false));
+        }
+        let key = self.key_pressed_at.iter().position(is_stale)?;
+        self.key_pressed_at[key] = None;
+        Some(InputEvent::KeyUp(key as u8))
+    }
+
+    /// Redraws the screen rows flagged in `rows` and the register panel.
+    fn draw(&mut self, chip8: &Chip8, rows: u32) -> io::Result<()> {
+        let out = &mut self.stdout;
+        let width = WIDTH as usize;
+        for cell_row in 0..HEIGHT as usize / 2 {
+            if rows & (0b11 << (cell_row * 2)) == 0 {
+                continue;
+            }
+            let top = &chip8.gfx[cell_row * 2 * width..][..width];
+            let bottom = &chip8.gfx[(cell_row * 2 + 1) * width..][..width];
+            let line: String = top
+                .iter()
+                .zip(bottom)
+                .map(|pixels| match pixels {
+                    (1, 1) => '█',
+                    (1, _) => '▀',
+                    (_, 1) => '▄',
+                    _ => ' ',
+                })
+                .collect();
+            queue!(out, MoveTo(0, cell_row as u16), Print(line))?;
+        }
+
+        let mut panel = vec![
+            format!("PC {:04X}", chip8.pc()),
+            format!("I  {:04X}", chip8.i()),
+            format!("SP {:02X}", chip8.sp()),
+            format!(
+                "DT {:02X}  ST {:02X}",
+                chip8.delay_timer(),
+                chip8.sound_timer()
+            ),
+            String::new(),
+        ];
+        let v = chip8.v();
+        for r in 0..8 {
+            panel.push(format!(
+                "V{:X} {:02X}  V{:X} {:02X}",
+                r,
+                v[r],
+                r + 8,
+                v[r + 8]
+            ));
+        }
+        for (row, text) in panel.iter().enumerate() {
+            queue!(out, MoveTo(PANEL_COLUMN, row as u16), Print(text))?;
+        }
+
+        self.draw_status()
+    }
+
+    /// Draws the help line below the screen.
+    fn draw_status(&mut self) -> io::Result<()> {
+        let hotkey = |hotkey| self.keymap.keys_for(Action::Hotkey(hotkey)).join("/");
+        let status = format!(
+            "{}{}: quit  {}: pause  {}: reset  {}/{}: save/load state  {}: fast forward",
+            if self.paused { "PAUSED  " } else { "" },
+            hotkey(Hotkey::Quit),
+            hotkey(Hotkey::Pause),
+            hotkey(Hotkey::Reset),
+            hotkey(Hotkey::SaveState),
+            hotkey(Hotkey::LoadState),
+            hotkey(Hotkey::FastForward),
+        );
+        queue!(
+            self.stdout,
+            MoveTo(0, HEIGHT as u16 / 2 + 1),
+            Clear(ClearType::CurrentLine),
+            Print(status)
+        )?;
+        self.stdout.flush()
+    }
+}
+
+impl Frontend for TerminalApp {
+    fn present(&mut self, chip8: &Chip8, dirty_rows: u32) -> io::Result<()> {
+        self.draw(chip8, dirty_rows)
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        let _ = self.draw_status();
+    }
+
+    fn poll_input(&mut self, deadline: Instant) -> io::Result<Option<InputEvent>> {
+        loop {
+            if let Some(event) = self.release_stale_key() {
+                return Ok(Some(event));
+            }
+            let mut timeout = deadline.saturating_duration_since(Instant::now());
+            if !self.release_events {
+                timeout = timeout.min(KEY_RELEASE_TIMEOUT);
+            }
+            if !event::poll(timeout)? {
+                if Instant::now() >= deadline {
+                    return Ok(None);
+                }
+                continue;
+            }
+            if let Event::Key(key_event) = event::read()? {
+                if let Some(event) = self.translate(key_event) {
+                    return Ok(Some(event));
+                }
+            }
+        }
+    }
+}
+
+/// The [`Keymap`] name of a terminal key.
+fn key_name(code: KeyCode) -> Option<&'static str> {
+    let name = match code {
+        KeyCode::Char(' ') => "Space",
+        KeyCode::Char('-') => "Minus",
+        KeyCode::Char('=') => "Equal",
+        KeyCode::Char(',') => "Comma",
+        KeyCode::Char('.') => "Period",
+        KeyCode::Char('/') => "Slash",
+        KeyCode::Char(';') => "Semicolon",
+        KeyCode::Char('\'') => "Quote",
+        KeyCode::Char('[') => "BracketLeft",
+        KeyCode::Char(']') => "BracketRight",
+        KeyCode::Char('\\') => "Backslash",
+        KeyCode::Char('`') => "Backquote",
+        KeyCode::Char(c) if c.is_ascii_alphanumeric() => {
+            return keymap::key_name(c.encode_utf8(&mut [0; 4]))
+        }
+        KeyCode::F(n) => return keymap::key_name(&format!("F{}", n)),
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Enter => "Enter",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Esc => "Escape",
+        KeyCode::Insert => "Insert",
+        KeyCode::Delete => "Delete",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        _ => return None,
+    };
+    Some(name)
+}
+
+impl Drop for TerminalApp {
+    fn drop(&mut self) {
+        if self.release_events {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.stdout, Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
@@ -0,0 +1,98 @@
+//! Compares screenshots against the golden images in `tests/golden`:
+//! `cargo test --features capture`. Run with `UPDATE_GOLDEN=1` to write them again after an
+//! intended change to the output.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use chippers::capture;
+use chippers::chip8::Chip8;
+use chippers::render::{Filter, Palette};
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// The framebuffer after running `rom` long enough to draw its picture.
+fn screen(rom: &str) -> [u8; 64 * 32] {
+    let rom = std::fs::read(manifest_dir().join("roms").join(rom)).unwrap();
+    let mut chip8 = Chip8::with_seed(0);
+    chip8.load_bytes(&rom);
+    chip8.run(1000);
+    chip8.gfx
+}
+
+/// Size and RGBA pixels of the PNG at `path`.
+fn decode(path: &Path) -> (u32, u32, Vec<u8>) {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    pixels.truncate(info.buffer_size());
+    (info.width, info.height, pixels)
+}
+
+fn check(name: &str, gfx: &[u8], scale: u32, filter: Filter) {
+    let golden = manifest_dir().join("tests/golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        capture::save_png(&golden, gfx, scale, &Palette::default(), filter).unwrap();
+        return;
+    }
+    let actual: PathBuf =
+        std::env::temp_dir().join(format!("chippers-{}-{}", std::process::id(), name));
+    capture::save_png(&actual, gfx, scale, &Palette::default(), filter).unwrap();
+    let decoded = decode(&actual);
+    std::fs::remove_file(&actual).unwrap();
+    assert!(
+        decoded == decode(&golden),
+        "{} differs from {}",
+        name,
+        golden.display()
+    );
+}
+
+#[test]
+fn native_screenshot_matches_the_golden_image() {
+    check("ibm-logo.png", &screen("2-ibm-logo.ch8"), 1, Filter::None);
+}
+
+#[test]
+fn filtered_screenshots_match_the_golden_images() {
+    let gfx = screen("1-chip8-logo.ch8");
+    check("chip8-logo-scanlines.png", &gfx, 4, Filter::Scanlines);
+    check("chip8-logo-lcd.png", &gfx, 4, Filter::Lcd);
+    check("chip8-logo-crt.png", &gfx, 4, Filter::Crt);
+}
+
+#[test]
+fn recordings_reject_scales_out_of_range() {
+    let palette = Palette::default();
+    let path = std::env::temp_dir().join(format!("chippers-{}-scale.gif", std::process::id()));
+    // 64 * 1024 pixels does not fit in the 16-bit size of a GIF
+    for scale in [0, 1024, u32::MAX] {
+        let err = capture::Recorder::create(&path, scale, &palette, 60)
+            .err()
+            .unwrap_or_else(|| panic!("scale {} should be rejected", scale));
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+    capture::Recorder::create(&path, 1023, &palette, 60)
+        .unwrap()
+        .finish()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(capture::save_png(&path, &[0; 64 * 32], 0, &palette, Filter::None).is_err());
+}
+
+#[test]
+fn screenshots_reject_scales_out_of_range() {
+    let palette = Palette::default();
+    let path = std::env::temp_dir().join(format!("chippers-{}-scale.png", std::process::id()));
+    for scale in [0, 363, 1 << 16, u32::MAX / 64, u32::MAX] {
+        let err = capture::save_png(&path, &[0; 64 * 32], scale, &palette, Filter::None)
+            .err()
+            .unwrap_or_else(|| panic!("scale {} should be rejected", scale));
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
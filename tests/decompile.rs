@@ -0,0 +1,306 @@
+//! Checks that decompiled ROMs assemble back into the same bytes: `cargo test`.
+//!
+//! Octo is not at hand in a test, so this brings a small assembler for the part of its
+//! language the decompiler writes.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chippers::decompile::Decompiler;
+
+// The shared test helpers are written for the library, which may be built without std
+extern crate alloc;
+
+#[path = "../src/test_util.rs"]
+mod test_util;
+
+/// A structure waiting for the statement that closes it.
+enum Open {
+    /// A `loop`, with the jumps of its `while`s to point past the `again`.
+    Loop { start: u16, exits: Vec<usize> },
+    /// An `if begin` or `else`, with the jump to point at the code after it.
+    If { jump: usize },
+}
+
+#[derive(Default)]
+struct Assembler {
+    out: Vec<u8>,
+    labels: HashMap<String, u16>,
+    /// Instructions waiting for the address of a label: where they are, the label and the
+    /// rest of the opcode.
+    fixups: Vec<(usize, String, u16)>,
+    open: Vec<Open>,
+}
+
+impl Assembler {
+    fn here(&self) -> u16 {
+        0x200 + self.out.len() as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.out.extend(opcode.to_be_bytes());
+    }
+
+    /// Emits `opcode` with the address `target`, a number or a label.
+    fn emit_address(&mut self, opcode: u16, target: &str) {
+        if target.starts_with(|c: char| c.is_ascii_digit()) {
+            self.emit(opcode | number(target));
+        } else {
+            self.fixups
+                .push((self.out.len(), target.to_string(), opcode));
+            self.emit(opcode);
+        }
+    }
+
+    /// Points the jump at `jump` to the current address.
+    fn patch(&mut self, jump: usize) {
+        let opcode = 0x1000 | self.here();
+        self.out[jump..jump + 2].copy_from_slice(&opcode.to_be_bytes());
+    }
+
+    /// Assembles the statement at the start of `tokens`, returning how many tokens it took.
+    fn statement(&mut self, tokens: &[&str]) -> usize {
+        match tokens {
+            [":", label, ..] => {
+                self.labels.insert(label.to_string(), self.here());
+                2
+            }
+            ["loop", ..] => {
+                self.open.push(Open::Loop {
+                    start: self.here(),
+                    exits: Vec::new(),
+                });
+                1
+            }
+            ["again", ..] => {
+                let Some(Open::Loop { start, exits }) = self.open.pop() else {
+                    panic!("again without loop");
+                };
+                self.emit(0x1000 | start);
+                for exit in exits {
+                    self.patch(exit);
+                }
+                1
+            }
+            ["while", condition @ ..] => {
+                self.emit(skip(condition, true));
+                let exit = self.out.len();
+                self.emit(0x1000);
+                let Some(Open::Loop { exits, .. }) = self
+                    .open
+                    .iter_mut()
+                    .rev()
+                    .find(|open| matches!(open, Open::Loop { .. }))
+                else {
+                    panic!("while outside a loop");
+                };
+                exits.push(exit);
+                1 + condition_len(condition)
+            }
+            ["if", condition @ ..] => {
+                let len = condition_len(condition);
+                // `then` skips the statement unless the condition holds, `begin` skips the
+                // jump over the body if it does
+                let begin = condition[len] == "begin";
+                self.emit(skip(condition, begin));
+                if begin {
+                    self.open.push(Open::If {
+                        jump: self.out.len(),
+                    });
+                    self.emit(0x1000);
+                }
+                2 + len
+            }
+            ["else", ..] => {
+                let Some(Open::If { jump }) = self.open.pop() else {
+                    panic!("else without if");
+                };
+                self.open.push(Open::If {
+                    jump: self.out.len(),
+                });
+                self.emit(0x1000);
+                self.patch(jump);
+                1
+            }
+            ["end", ..] => {
+                let Some(Open::If { jump }) = self.open.pop() else {
+                    panic!("end without if");
+                };
+                self.patch(jump);
+                1
+            }
+            ["clear", ..] => self.op(0x00E0, 1),
+            ["return", ..] => self.op(0x00EE, 1),
+            ["native", target, ..] => self.address_op(0x0000, target, 2),
+            ["jump", target, ..] => self.address_op(0x1000, target, 2),
+            ["jump0", target, ..] => self.address_op(0xB000, target, 2),
+            ["sprite", x, y, n, ..] => {
+                self.op(0xD000 | register(x) << 8 | register(y) << 4 | number(n), 4)
+            }
+            ["bcd", x, ..] => self.op(0xF033 | register(x) << 8, 2),
+            ["save", x, ..] => self.op(0xF055 | register(x) << 8, 2),
+            ["load", x, ..] => self.op(0xF065 | register(x) << 8, 2),
+            ["delay", ":=", x, ..] => self.op(0xF015 | register(x) << 8, 3),
+            ["buzzer", ":=", x, ..] => self.op(0xF018 | register(x) << 8, 3),
+            ["i", "+=", x, ..] => self.op(0xF01E | register(x) << 8, 3),
+            ["i", ":=", "hex", x, ..] => self.op(0xF029 | register(x) << 8, 4),
+            ["i", ":=", target, ..] => self.address_op(0xA000, target, 3),
+            [x, ":=", "random", nn, ..] => self.op(0xC000 | register(x) << 8 | number(nn), 4),
+            [x, ":=", "delay", ..] => self.op(0xF007 | register(x) << 8, 3),
+            [x, ":=", "key", ..] => self.op(0xF00A | register(x) << 8, 3),
+            [x, ":=", y, ..] if is_register(y) => {
+                self.op(0x8000 | register(x) << 8 | register(y) << 4, 3)
+            }
+            [x, ":=", nn, ..] => self.op(0x6000 | register(x) << 8 | number(nn), 3),
+            [x, "+=", nn, ..] if !is_register(nn) => {
+                self.op(0x7000 | register(x) << 8 | number(nn), 3)
+            }
+            [x, operator, y, ..] if is_register(x) => {
+                let n = match *operator {
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    "+=" => 0x4,
+                    "-=" => 0x5,
+                    ">>=" => 0x6,
+                    "=-" => 0x7,
+                    "<<=" => 0xE,
+                    _ => panic!("unknown operator {}", operator),
+                };
+                self.op(0x8000 | register(x) << 8 | register(y) << 4 | n, 3)
+            }
+            [byte, ..] if byte.starts_with(|c: char| c.is_ascii_digit()) => {
+                self.out.push(u8::try_from(number(byte)).unwrap());
+                1
+            }
+            // Anything else calls a subroutine
+            [label, ..] => self.address_op(0x2000, label, 1),
+            [] => unreachable!(),
+        }
+    }
+
+    /// Emits the instruction of a statement `len` tokens long.
+    fn op(&mut self, opcode: u16, len: usize) -> usize {
+        self.emit(opcode);
+        len
+    }
+
+    /// Emits the instruction with the address `target` of a statement `len` tokens long.
+    fn address_op(&mut self, opcode: u16, target: &str, len: usize) -> usize {
+        self.emit_address(opcode, target);
+        len
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        assert!(self.open.is_empty(), "unclosed structures");
+        for (at, label, opcode) in std::mem::take(&mut self.fixups) {
+            let address = *self
+                .labels
+                .get(&label)
+                .unwrap_or_else(|| panic!("undefined label {}", label));
+            self.out[at..at + 2].copy_from_slice(&(opcode | address).to_be_bytes());
+        }
+        self.out
+    }
+}
+
+fn assemble(source: &str) -> Vec<u8> {
+    let tokens: Vec<&str> = source
+        .lines()
+        .flat_map(|line| line.split('#').next().unwrap().split_whitespace())
+        .collect();
+    let mut assembler = Assembler::default();
+    let mut index = 0;
+    while index < tokens.len() {
+        index += assembler.statement(&tokens[index..]);
+    }
+    assembler.finish()
+}
+
+/// Number of tokens of the condition at the start of `tokens`.
+fn condition_len(tokens: &[&str]) -> usize {
+    match tokens[1] {
+        "key" | "-key" => 2,
+        _ => 3,
+    }
+}
+
+/// The instruction skipping the next one when whether the condition at the start of
+/// `tokens` holds is `holds`.
+fn skip(tokens: &[&str], holds: bool) -> u16 {
+    let x = register(tokens[0]) << 8;
+    match tokens[1] {
+        "key" | "-key" if (tokens[1] == "key") == holds => 0xE09E | x,
+        "key" | "-key" => 0xE0A1 | x,
+        operator => {
+            let equal = (operator == "==") == holds;
+            match (equal, tokens[2]) {
+                (true, y) if is_register(y) => 0x5000 | x | register(y) << 4,
+                (false, y) if is_register(y) => 0x9000 | x | register(y) << 4,
+                (true, nn) => 0x3000 | x | number(nn),
+                (false, nn) => 0x4000 | x | number(nn),
+            }
+        }
+    }
+}
+
+fn number(token: &str) -> u16 {
+    match token.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => token.parse(),
+    }
+    .unwrap_or_else(|_| panic!("bad number {}", token))
+}
+
+fn is_register(token: &str) -> bool {
+    token.len() == 2 && token.starts_with('v') && token[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn register(token: &str) -> u16 {
+    assert!(is_register(token), "bad register {}", token);
+    u16::from_str_radix(&token[1..], 16).unwrap()
+}
+
+fn decompile(rom: &[u8]) -> String {
+    let mut source = Vec::new();
+    Decompiler::new(rom)
+        .write_octo(&mut source, "test.ch8")
+        .unwrap();
+    String::from_utf8(source).unwrap()
+}
+
+fn round_trip(rom: &[u8], name: &str) {
+    let source = decompile(rom);
+    assert!(
+        assemble(&source) == rom,
+        "{} does not assemble back into the ROM:\n{}",
+        name,
+        source
+    );
+}
+
+#[test]
+fn every_rom_assembles_back() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    for entry in std::fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        round_trip(&std::fs::read(&path).unwrap(), &path.display().to_string());
+    }
+}
+
+#[test]
+fn structured_code_assembles_back() {
+    let roms: [&[u16]; 4] = [
+        // if begin, else and end
+        &[0x3001, 0x1208, 0x7101, 0x120A, 0x7201, 0x120A],
+        // A while leaving a loop
+        &[0x7101, 0x3105, 0x1208, 0x1200, 0x1208],
+        // if then inside a loop, with a key
+        &[0xE19E, 0x7201, 0x1200],
+        // A jump back to the jump after a skip, which cannot be an if begin
+        &[0x3001, 0x1208, 0x7101, 0x1202, 0x1208],
+    ];
+    for opcodes in roms {
+        round_trip(&test_util::assemble(opcodes), &format!("{:04X?}", opcodes));
+    }
+}
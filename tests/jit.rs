@@ -0,0 +1,135 @@
+//! Cross-checks the JIT against the interpreter: `cargo test --features jit`.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use chippers::chip8::Chip8;
+use chippers::jit::Jit;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Runs `rom` on the interpreter and the JIT side by side with the same random input and
+/// frame lengths, checking the machines agree after every frame. Returns the blocks the JIT
+/// compiled.
+fn cross_check(rom: &[u8], seed: u64, frames: u32) -> usize {
+    let mut interpreted = Chip8::with_seed(seed);
+    interpreted.load_bytes(rom);
+    let mut compiled = interpreted.clone();
+    let mut jit = Jit::new().unwrap();
+    let mut input = SmallRng::seed_from_u64(seed);
+
+    for frame in 0..frames {
+        if frame % 5 == 0 {
+            let key = input.gen_range(0..16);
+            if input.gen() {
+                interpreted.press_key(key);
+                compiled.press_key(key);
+            } else {
+                interpreted.release_key(key);
+                compiled.release_key(key);
+            }
+        }
+        // Short frames end in the middle of blocks
+        let cycles = input.gen_range(0..48);
+        let expected = panic::catch_unwind(AssertUnwindSafe(|| interpreted.run(cycles)));
+        let actual = panic::catch_unwind(AssertUnwindSafe(|| jit.run(&mut compiled, cycles)));
+        assert_eq!(
+            expected.is_err(),
+            actual.is_err(),
+            "only one of them panicked in frame {}",
+            frame
+        );
+        if expected.is_err() {
+            break;
+        }
+        assert_eq!(
+            interpreted.registers(),
+            compiled.registers(),
+            "registers differ after frame {}",
+            frame
+        );
+        assert_eq!(interpreted.stack(), compiled.stack());
+        assert_eq!(
+            interpreted.state_hash(),
+            compiled.state_hash(),
+            "state differs after frame {}",
+            frame
+        );
+        interpreted.tick_timers();
+        compiled.tick_timers();
+    }
+    jit.blocks()
+}
+
+#[test]
+fn agrees_with_the_interpreter_on_every_rom() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    for entry in std::fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = std::fs::read(&path).unwrap();
+        for seed in 0..4 {
+            let blocks = cross_check(&rom, seed, 2000);
+            assert!(blocks > 0, "nothing of {} was compiled", path.display());
+        }
+    }
+}
+
+#[test]
+fn runs_self_modifying_code() {
+    #[rustfmt::skip]
+    let rom = [
+        0xA2, 0x09, // 200: LD I, 0x209
+        0x71, 0x01, // 202: ADD V1, 1
+        0x80, 0x10, // 204: LD V0, V1
+        0xF0, 0x55, // 206: LD [I], V0, rewriting the operand of the next instruction
+        0x72, 0x00, // 208: ADD V2, 0
+        0x12, 0x00, // 20A: JP 0x200
+    ];
+    cross_check(&rom, 0, 500);
+}
+
+#[test]
+fn stops_at_0000_and_the_end_of_memory() {
+    cross_check(&[0x60, 0x01, 0x00, 0x00], 0, 10);
+    // Jumps to the last instruction of memory and runs off its end
+    let mut chip8 = Chip8::with_seed(0);
+    chip8.load_bytes(&[0x1F, 0xFE]);
+    let mut jit = Jit::new().unwrap();
+    jit.run(&mut chip8, 1);
+    assert_eq!(chip8.pc(), 0xFFE);
+}
+
+#[test]
+fn panics_on_stack_overflow_like_the_interpreter() {
+    // 200: CALL 0x200
+    cross_check(&[0x22, 0x00], 0, 10);
+}
+
+#[test]
+fn shares_blocks_between_machines() {
+    let rom =
+        std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/breakout.ch8")).unwrap();
+    let mut interpreted: Vec<Chip8> = (0..4)
+        .map(|seed| {
+            let mut chip8 = Chip8::with_seed(seed);
+            chip8.load_bytes(&rom);
+            chip8
+        })
+        .collect();
+    let mut compiled = interpreted.clone();
+    let mut jit = Jit::new().unwrap();
+    for frame in 0..500 {
+        for (interpreted, compiled) in interpreted.iter_mut().zip(&mut compiled) {
+            interpreted.run(13);
+            jit.run(compiled, 13);
+            assert_eq!(
+                interpreted.state_hash(),
+                compiled.state_hash(),
+                "state differs after frame {}",
+                frame
+            );
+            interpreted.tick_timers();
+            compiled.tick_timers();
+        }
+    }
+}
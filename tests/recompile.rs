@@ -0,0 +1,132 @@
+//! Cross-checks recompiled ROMs against the interpreter: `cargo test`.
+//!
+//! The modules in `tests/recompiled` are the output of `chippers recompile`, compiled into
+//! this test. Run with `UPDATE_GOLDEN=1` to write them again after an intended change to
+//! the recompiler, then run the tests once more to check the new code.
+
+use std::path::Path;
+
+use chippers::chip8::Chip8;
+use chippers::emulator::{Backend, Emulator, Headless};
+use chippers::recompile::Recompiler;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+// Left as the recompiler wrote them
+#[rustfmt::skip]
+#[path = "recompiled/breakout.rs"]
+mod breakout;
+#[rustfmt::skip]
+#[path = "recompiled/corax.rs"]
+mod corax;
+
+/// The recompiled modules, with the ROM each was made from.
+const MODULES: [(&str, &str); 2] = [("breakout.ch8", "breakout"), ("3-corax+.ch8", "corax")];
+
+/// Runs `rom` on the interpreter and through `run` side by side with the same random input
+/// and frame lengths, checking the machines agree after every frame.
+fn cross_check(rom: &[u8], run: fn(&mut Chip8, u64), seed: u64, frames: u32) {
+    let mut interpreted = Chip8::with_seed(seed);
+    interpreted.load_bytes(rom);
+    let mut recompiled = interpreted.clone();
+    let mut input = SmallRng::seed_from_u64(seed);
+
+    for frame in 0..frames {
+        if frame % 5 == 0 {
+            let key = input.gen_range(0..16);
+            if input.gen() {
+                interpreted.press_key(key);
+                recompiled.press_key(key);
+            } else {
+                interpreted.release_key(key);
+                recompiled.release_key(key);
+            }
+        }
+        // Short frames end in the middle of blocks
+        let cycles = input.gen_range(0..48);
+        interpreted.run(cycles);
+        run(&mut recompiled, cycles);
+        assert_eq!(
+            interpreted.registers(),
+            recompiled.registers(),
+            "registers differ after frame {} with seed {}",
+            frame,
+            seed
+        );
+        assert_eq!(
+            interpreted.state_hash(),
+            recompiled.state_hash(),
+            "state differs after frame {} with seed {}",
+            frame,
+            seed
+        );
+        interpreted.tick_timers();
+        recompiled.tick_timers();
+    }
+}
+
+#[test]
+fn recompiled_modules_are_current() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for (rom, module) in MODULES {
+        let rom_path = manifest_dir.join("roms").join(rom);
+        let mut code = Vec::new();
+        Recompiler::new(&std::fs::read(rom_path).unwrap())
+            .write_rust(&mut code, rom)
+            .unwrap();
+        let path = manifest_dir.join(format!("tests/recompiled/{}.rs", module));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &code).unwrap();
+            continue;
+        }
+        assert!(
+            std::fs::read(&path).unwrap() == code,
+            "{} is out of date, run with UPDATE_GOLDEN=1",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn recompiled_code_agrees_with_the_interpreter() {
+    for seed in 0..20 {
+        cross_check(&breakout::ROM, breakout::run, seed, 600);
+        cross_check(&corax::ROM, corax::run, seed, 600);
+    }
+}
+
+/// State hash after 600 frames of `rom` in an emulator with `backend`, pressing keys
+/// every now and then.
+fn emulate(rom: &[u8], backend: Option<Box<dyn Backend>>) -> u64 {
+    let mut chip8 = Chip8::with_seed(7);
+    chip8.load_bytes(rom);
+    let mut emulator = Emulator::new(chip8, 60, 700);
+    if let Some(backend) = backend {
+        emulator.set_backend(backend);
+    }
+    let mut frontend = Headless::new(600);
+    for frame in 0..600 {
+        match frame % 100 {
+            0 => emulator.press_key(if frame % 200 == 0 { 0x4 } else { 0x6 }),
+            50 => {
+                emulator.release_key(0x4);
+                emulator.release_key(0x6);
+            }
+            _ => (),
+        }
+        emulator.run_frame(&mut frontend).unwrap();
+    }
+    emulator.chip8().state_hash()
+}
+
+#[test]
+fn recompiled_backends_run_in_the_emulator() {
+    assert_eq!(
+        emulate(&breakout::ROM, Some(Box::new(breakout::Recompiled))),
+        emulate(&breakout::ROM, None)
+    );
+    assert_eq!(
+        emulate(&corax::ROM, Some(Box::new(corax::Recompiled))),
+        emulate(&corax::ROM, None)
+    );
+}
@@ -0,0 +1,795 @@
+//! breakout.ch8 recompiled to Rust by `chippers recompile`.
+//!
+//! Runs on a `Chip8` that loaded [`ROM`]: call [`run`] in place of `Chip8::run`, or
+//! hand [`Recompiled`] to `Emulator::set_backend`. Code not found by following the jumps
+//! of the ROM, and code the ROM overwrites, run in the interpreter.
+
+use chippers::chip8::{Chip8, Cpu};
+use chippers::emulator::Backend;
+
+/// The ROM, loaded at 200.
+pub const ROM: [u8; 280] = [
+    0x6E, 0x05, 0x65, 0x00, 0x6B, 0x06, 0x6A, 0x00, 0xA3, 0x0C, 0xDA, 0xB1, 0x7A, 0x04, 0x3A, 0x40,
+    0x12, 0x08, 0x7B, 0x02, 0x3B, 0x12, 0x12, 0x06, 0x6C, 0x20, 0x6D, 0x1F, 0xA3, 0x10, 0xDC, 0xD1,
+    0x22, 0xF6, 0x60, 0x00, 0x61, 0x00, 0xA3, 0x12, 0xD0, 0x11, 0x70, 0x08, 0xA3, 0x0E, 0xD0, 0x11,
+    0x60, 0x40, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x34, 0xC6, 0x0F, 0x67, 0x1E, 0x68, 0x01,
+    0x69, 0xFF, 0xA3, 0x0E, 0xD6, 0x71, 0xA3, 0x10, 0xDC, 0xD1, 0x60, 0x04, 0xE0, 0xA1, 0x7C, 0xFE,
+    0x60, 0x06, 0xE0, 0xA1, 0x7C, 0x02, 0x60, 0x3F, 0x8C, 0x02, 0xDC, 0xD1, 0xA3, 0x0E, 0xD6, 0x71,
+    0x86, 0x84, 0x87, 0x94, 0x60, 0x3F, 0x86, 0x02, 0x61, 0x1F, 0x87, 0x12, 0x47, 0x1F, 0x12, 0xAC,
+    0x46, 0x00, 0x68, 0x01, 0x46, 0x3F, 0x68, 0xFF, 0x47, 0x00, 0x69, 0x01, 0xD6, 0x71, 0x3F, 0x01,
+    0x12, 0xAA, 0x47, 0x1F, 0x12, 0xAA, 0x60, 0x05, 0x80, 0x75, 0x3F, 0x00, 0x12, 0xAA, 0x60, 0x01,
+    0xF0, 0x18, 0x80, 0x60, 0x61, 0xFC, 0x80, 0x12, 0xA3, 0x0C, 0xD0, 0x71, 0x60, 0xFE, 0x89, 0x03,
+    0x22, 0xF6, 0x75, 0x01, 0x22, 0xF6, 0x45, 0x60, 0x12, 0xDE, 0x12, 0x46, 0x69, 0xFF, 0x80, 0x60,
+    0x80, 0xC5, 0x3F, 0x01, 0x12, 0xCA, 0x61, 0x02, 0x80, 0x15, 0x3F, 0x01, 0x12, 0xE0, 0x80, 0x15,
+    0x3F, 0x01, 0x12, 0xEE, 0x80, 0x15, 0x3F, 0x01, 0x12, 0xE8, 0x60, 0x20, 0xF0, 0x18, 0xA3, 0x0E,
+    0x7E, 0xFF, 0x80, 0xE0, 0x80, 0x04, 0x61, 0x00, 0xD0, 0x11, 0x3E, 0x00, 0x12, 0x30, 0x12, 0xDE,
+    0x78, 0xFF, 0x48, 0xFE, 0x68, 0xFF, 0x12, 0xEE, 0x78, 0x01, 0x48, 0x02, 0x68, 0x01, 0x60, 0x04,
+    0xF0, 0x18, 0x69, 0xFF, 0x12, 0x70, 0xA3, 0x14, 0xF5, 0x33, 0xF2, 0x65, 0xF1, 0x29, 0x63, 0x37,
+    0x64, 0x00, 0xD3, 0x45, 0x73, 0x05, 0xF2, 0x29, 0xD3, 0x45, 0x00, 0xEE, 0xF0, 0x00, 0x80, 0x00,
+    0xFC, 0x00, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Runs the recompiled code in place of the interpreter.
+pub struct Recompiled;
+
+impl Backend for Recompiled {
+    fn run(&mut self, chip8: &mut Chip8, cycles: u64) {
+        run(chip8, cycles);
+    }
+}
+
+/// Runs `cycles` instructions like `Chip8::run`.
+pub fn run(chip8: &mut Chip8, cycles: u64) {
+    let mut cpu = chip8.cpu();
+    let mut remaining = cycles;
+    while remaining > 0 {
+        let ran = match cpu.pc {
+            0x200 if remaining >= 3 && intact(chip8, 0x200, 0x206) => {
+                block_200(chip8, &mut cpu)
+            }
+            0x206 if remaining >= 1 && intact(chip8, 0x206, 0x208) => {
+                block_206(chip8, &mut cpu)
+            }
+            0x208 if remaining >= 4 && intact(chip8, 0x208, 0x210) => {
+                block_208(chip8, &mut cpu)
+            }
+            0x210 if remaining >= 1 && intact(chip8, 0x210, 0x212) => {
+                block_210(chip8, &mut cpu)
+            }
+            0x212 if remaining >= 2 && intact(chip8, 0x212, 0x216) => {
+                block_212(chip8, &mut cpu)
+            }
+            0x216 if remaining >= 1 && intact(chip8, 0x216, 0x218) => {
+                block_216(chip8, &mut cpu)
+            }
+            0x218 if remaining >= 5 && intact(chip8, 0x218, 0x222) => {
+                block_218(chip8, &mut cpu)
+            }
+            0x222 if remaining >= 7 && intact(chip8, 0x222, 0x230) => {
+                block_222(chip8, &mut cpu)
+            }
+            0x230 if remaining >= 2 && intact(chip8, 0x230, 0x234) => {
+                block_230(chip8, &mut cpu)
+            }
+            0x234 if remaining >= 2 && intact(chip8, 0x234, 0x238) => {
+                block_234(chip8, &mut cpu)
+            }
+            0x238 if remaining >= 1 && intact(chip8, 0x238, 0x23A) => {
+                block_238(chip8, &mut cpu)
+            }
+            0x23A if remaining >= 6 && intact(chip8, 0x23A, 0x246) => {
+                block_23a(chip8, &mut cpu)
+            }
+            0x246 if remaining >= 4 && intact(chip8, 0x246, 0x24E) => {
+                block_246(chip8, &mut cpu)
+            }
+            0x24E if remaining >= 1 && intact(chip8, 0x24E, 0x250) => {
+                block_24e(chip8, &mut cpu)
+            }
+            0x250 if remaining >= 2 && intact(chip8, 0x250, 0x254) => {
+                block_250(chip8, &mut cpu)
+            }
+            0x254 if remaining >= 1 && intact(chip8, 0x254, 0x256) => {
+                block_254(chip8, &mut cpu)
+            }
+            0x256 if remaining >= 12 && intact(chip8, 0x256, 0x26E) => {
+                block_256(chip8, &mut cpu)
+            }
+            0x26E if remaining >= 1 && intact(chip8, 0x26E, 0x270) => {
+                block_26e(chip8, &mut cpu)
+            }
+            0x270 if remaining >= 1 && intact(chip8, 0x270, 0x272) => {
+                block_270(chip8, &mut cpu)
+            }
+            0x272 if remaining >= 1 && intact(chip8, 0x272, 0x274) => {
+                block_272(chip8, &mut cpu)
+            }
+            0x274 if remaining >= 1 && intact(chip8, 0x274, 0x276) => {
+                block_274(chip8, &mut cpu)
+            }
+            0x276 if remaining >= 1 && intact(chip8, 0x276, 0x278) => {
+                block_276(chip8, &mut cpu)
+            }
+            0x278 if remaining >= 1 && intact(chip8, 0x278, 0x27A) => {
+                block_278(chip8, &mut cpu)
+            }
+            0x27A if remaining >= 1 && intact(chip8, 0x27A, 0x27C) => {
+                block_27a(chip8, &mut cpu)
+            }
+            0x27C if remaining >= 2 && intact(chip8, 0x27C, 0x280) => {
+                block_27c(chip8, &mut cpu)
+            }
+            0x280 if remaining >= 1 && intact(chip8, 0x280, 0x282) => {
+                block_280(chip8, &mut cpu)
+            }
+            0x282 if remaining >= 1 && intact(chip8, 0x282, 0x284) => {
+                block_282(chip8, &mut cpu)
+            }
+            0x284 if remaining >= 1 && intact(chip8, 0x284, 0x286) => {
+                block_284(chip8, &mut cpu)
+            }
+            0x286 if remaining >= 3 && intact(chip8, 0x286, 0x28C) => {
+                block_286(chip8, &mut cpu)
+            }
+            0x28C if remaining >= 1 && intact(chip8, 0x28C, 0x28E) => {
+                block_28c(chip8, &mut cpu)
+            }
+            0x28E if remaining >= 10 && intact(chip8, 0x28E, 0x2A2) => {
+                block_28e(chip8, &mut cpu)
+            }
+            0x2A2 if remaining >= 2 && intact(chip8, 0x2A2, 0x2A6) => {
+                block_2a2(chip8, &mut cpu)
+            }
+            0x2A6 if remaining >= 1 && intact(chip8, 0x2A6, 0x2A8) => {
+                block_2a6(chip8, &mut cpu)
+            }
+            0x2A8 if remaining >= 1 && intact(chip8, 0x2A8, 0x2AA) => {
+                block_2a8(chip8, &mut cpu)
+            }
+            0x2AA if remaining >= 1 && intact(chip8, 0x2AA, 0x2AC) => {
+                block_2aa(chip8, &mut cpu)
+            }
+            0x2AC if remaining >= 4 && intact(chip8, 0x2AC, 0x2B4) => {
+                block_2ac(chip8, &mut cpu)
+            }
+            0x2B4 if remaining >= 1 && intact(chip8, 0x2B4, 0x2B6) => {
+                block_2b4(chip8, &mut cpu)
+            }
+            0x2B6 if remaining >= 3 && intact(chip8, 0x2B6, 0x2BC) => {
+                block_2b6(chip8, &mut cpu)
+            }
+            0x2BC if remaining >= 1 && intact(chip8, 0x2BC, 0x2BE) => {
+                block_2bc(chip8, &mut cpu)
+            }
+            0x2BE if remaining >= 2 && intact(chip8, 0x2BE, 0x2C2) => {
+                block_2be(chip8, &mut cpu)
+            }
+            0x2C2 if remaining >= 1 && intact(chip8, 0x2C2, 0x2C4) => {
+                block_2c2(chip8, &mut cpu)
+            }
+            0x2C4 if remaining >= 2 && intact(chip8, 0x2C4, 0x2C8) => {
+                block_2c4(chip8, &mut cpu)
+            }
+            0x2C8 if remaining >= 1 && intact(chip8, 0x2C8, 0x2CA) => {
+                block_2c8(chip8, &mut cpu)
+            }
+            0x2CA if remaining >= 9 && intact(chip8, 0x2CA, 0x2DC) => {
+                block_2ca(chip8, &mut cpu)
+            }
+            0x2DC if remaining >= 1 && intact(chip8, 0x2DC, 0x2DE) => {
+                block_2dc(chip8, &mut cpu)
+            }
+            0x2DE if remaining >= 1 && intact(chip8, 0x2DE, 0x2E0) => {
+                block_2de(chip8, &mut cpu)
+            }
+            0x2E0 if remaining >= 2 && intact(chip8, 0x2E0, 0x2E4) => {
+                block_2e0(chip8, &mut cpu)
+            }
+            0x2E4 if remaining >= 1 && intact(chip8, 0x2E4, 0x2E6) => {
+                block_2e4(chip8, &mut cpu)
+            }
+            0x2E6 if remaining >= 1 && intact(chip8, 0x2E6, 0x2E8) => {
+                block_2e6(chip8, &mut cpu)
+            }
+            0x2E8 if remaining >= 2 && intact(chip8, 0x2E8, 0x2EC) => {
+                block_2e8(chip8, &mut cpu)
+            }
+            0x2EC if remaining >= 1 && intact(chip8, 0x2EC, 0x2EE) => {
+                block_2ec(chip8, &mut cpu)
+            }
+            0x2EE if remaining >= 4 && intact(chip8, 0x2EE, 0x2F6) => {
+                block_2ee(chip8, &mut cpu)
+            }
+            0x2F6 if remaining >= 2 && intact(chip8, 0x2F6, 0x2FA) => {
+                block_2f6(chip8, &mut cpu)
+            }
+            0x2FA if remaining >= 9 && intact(chip8, 0x2FA, 0x30C) => {
+                block_2fa(chip8, &mut cpu)
+            }
+_ => 0,
+        };
+        if ran > 0 {
+            remaining -= ran;
+            continue;
+        }
+        let pc = cpu.pc as usize;
+        let memory = chip8.memory();
+        if pc >= 0x1000 || (pc < 0xFFF && memory[pc] == 0 && memory[pc + 1] == 0) {
+            // Every cycle from here on does nothing
+            break;
+        }
+        interpret(chip8, &mut cpu);
+        remaining -= 1;
+    }
+    chip8.set_cpu(&cpu);
+}
+
+/// Whether memory from `start` to `end` still holds the code of the ROM.
+fn intact(chip8: &Chip8, start: usize, end: usize) -> bool {
+    chip8.memory()[start..end] == ROM[start - 0x200..end - 0x200]
+}
+
+/// Runs the instruction at `cpu.pc` in the interpreter.
+fn interpret(chip8: &mut Chip8, cpu: &mut Cpu) {
+    chip8.set_cpu(cpu);
+    chip8.cycle();
+    *cpu = chip8.cpu();
+}
+
+fn block_200(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 200: 6E05  LD VE, 0x05
+    cpu.v[14] = 0x05;
+    // 202: 6500  LD V5, 0x00
+    cpu.v[5] = 0x00;
+    // 204: 6B06  LD VB, 0x06
+    cpu.v[11] = 0x06;
+    cpu.pc = 0x206;
+    3
+}
+
+fn block_206(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 206: 6A00  LD VA, 0x00
+    cpu.v[10] = 0x00;
+    cpu.pc = 0x208;
+    1
+}
+
+fn block_208(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 208: A30C  LD I, 0x30C
+    cpu.i = 0x30C;
+    // 20A: DAB1  DRW VA, VB, 1
+    cpu.pc = 0x20A;
+    interpret(chip8, cpu);
+    // 20C: 7A04  ADD VA, 0x04
+    cpu.v[10] = cpu.v[10].wrapping_add(0x04);
+    // 20E: 3A40  SE VA, 0x40
+    cpu.pc = if cpu.v[10] == 0x40 { 0x212 } else { 0x210 };
+    4
+}
+
+fn block_210(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 210: 1208  JP 0x208
+    cpu.pc = 0x208;
+    1
+}
+
+fn block_212(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 212: 7B02  ADD VB, 0x02
+    cpu.v[11] = cpu.v[11].wrapping_add(0x02);
+    // 214: 3B12  SE VB, 0x12
+    cpu.pc = if cpu.v[11] == 0x12 { 0x218 } else { 0x216 };
+    2
+}
+
+fn block_216(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 216: 1206  JP 0x206
+    cpu.pc = 0x206;
+    1
+}
+
+fn block_218(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 218: 6C20  LD VC, 0x20
+    cpu.v[12] = 0x20;
+    // 21A: 6D1F  LD VD, 0x1F
+    cpu.v[13] = 0x1F;
+    // 21C: A310  LD I, 0x310
+    cpu.i = 0x310;
+    // 21E: DCD1  DRW VC, VD, 1
+    cpu.pc = 0x21E;
+    interpret(chip8, cpu);
+    // 220: 22F6  CALL 0x2F6
+    if cpu.sp >= 16 {
+        // Overflows the stack in the interpreter
+        cpu.pc = 0x220;
+        return 4;
+    }
+    cpu.stack[cpu.sp as usize] = 0x222;
+    cpu.sp += 1;
+    cpu.pc = 0x2F6;
+    5
+}
+
+fn block_222(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 222: 6000  LD V0, 0x00
+    cpu.v[0] = 0x00;
+    // 224: 6100  LD V1, 0x00
+    cpu.v[1] = 0x00;
+    // 226: A312  LD I, 0x312
+    cpu.i = 0x312;
+    // 228: D011  DRW V0, V1, 1
+    cpu.pc = 0x228;
+    interpret(chip8, cpu);
+    // 22A: 7008  ADD V0, 0x08
+    cpu.v[0] = cpu.v[0].wrapping_add(0x08);
+    // 22C: A30E  LD I, 0x30E
+    cpu.i = 0x30E;
+    // 22E: D011  DRW V0, V1, 1
+    cpu.pc = 0x22E;
+    interpret(chip8, cpu);
+    7
+}
+
+fn block_230(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 230: 6040  LD V0, 0x40
+    cpu.v[0] = 0x40;
+    // 232: F015  LD DT, V0
+    cpu.delay_timer = cpu.v[0];
+    cpu.pc = 0x234;
+    2
+}
+
+fn block_234(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 234: F007  LD V0, DT
+    cpu.v[0] = cpu.delay_timer;
+    // 236: 3000  SE V0, 0x00
+    cpu.pc = if cpu.v[0] == 0x00 { 0x23A } else { 0x238 };
+    2
+}
+
+fn block_238(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 238: 1234  JP 0x234
+    cpu.pc = 0x234;
+    1
+}
+
+fn block_23a(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 23A: C60F  RND V6, 0x0F
+    cpu.pc = 0x23A;
+    interpret(chip8, cpu);
+    // 23C: 671E  LD V7, 0x1E
+    cpu.v[7] = 0x1E;
+    // 23E: 6801  LD V8, 0x01
+    cpu.v[8] = 0x01;
+    // 240: 69FF  LD V9, 0xFF
+    cpu.v[9] = 0xFF;
+    // 242: A30E  LD I, 0x30E
+    cpu.i = 0x30E;
+    // 244: D671  DRW V6, V7, 1
+    cpu.pc = 0x244;
+    interpret(chip8, cpu);
+    6
+}
+
+fn block_246(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 246: A310  LD I, 0x310
+    cpu.i = 0x310;
+    // 248: DCD1  DRW VC, VD, 1
+    cpu.pc = 0x248;
+    interpret(chip8, cpu);
+    // 24A: 6004  LD V0, 0x04
+    cpu.v[0] = 0x04;
+    // 24C: E0A1  SKNP V0
+    cpu.pc = if cpu.keys & (1 << (cpu.v[0] & 0xF)) == 0 { 0x250 } else { 0x24E };
+    4
+}
+
+fn block_24e(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 24E: 7CFE  ADD VC, 0xFE
+    cpu.v[12] = cpu.v[12].wrapping_add(0xFE);
+    cpu.pc = 0x250;
+    1
+}
+
+fn block_250(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 250: 6006  LD V0, 0x06
+    cpu.v[0] = 0x06;
+    // 252: E0A1  SKNP V0
+    cpu.pc = if cpu.keys & (1 << (cpu.v[0] & 0xF)) == 0 { 0x256 } else { 0x254 };
+    2
+}
+
+fn block_254(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 254: 7C02  ADD VC, 0x02
+    cpu.v[12] = cpu.v[12].wrapping_add(0x02);
+    cpu.pc = 0x256;
+    1
+}
+
+fn block_256(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 256: 603F  LD V0, 0x3F
+    cpu.v[0] = 0x3F;
+    // 258: 8C02  AND VC, V0
+    cpu.v[12] &= cpu.v[0];
+    cpu.v[15] = 0;
+    // 25A: DCD1  DRW VC, VD, 1
+    cpu.pc = 0x25A;
+    interpret(chip8, cpu);
+    // 25C: A30E  LD I, 0x30E
+    cpu.i = 0x30E;
+    // 25E: D671  DRW V6, V7, 1
+    cpu.pc = 0x25E;
+    interpret(chip8, cpu);
+    // 260: 8684  ADD V6, V8
+    let (sum, carry) = cpu.v[6].overflowing_add(cpu.v[8]);
+    cpu.v[6] = sum;
+    cpu.v[15] = carry as u8;
+    // 262: 8794  ADD V7, V9
+    let (sum, carry) = cpu.v[7].overflowing_add(cpu.v[9]);
+    cpu.v[7] = sum;
+    cpu.v[15] = carry as u8;
+    // 264: 603F  LD V0, 0x3F
+    cpu.v[0] = 0x3F;
+    // 266: 8602  AND V6, V0
+    cpu.v[6] &= cpu.v[0];
+    cpu.v[15] = 0;
+    // 268: 611F  LD V1, 0x1F
+    cpu.v[1] = 0x1F;
+    // 26A: 8712  AND V7, V1
+    cpu.v[7] &= cpu.v[1];
+    cpu.v[15] = 0;
+    // 26C: 471F  SNE V7, 0x1F
+    cpu.pc = if cpu.v[7] != 0x1F { 0x270 } else { 0x26E };
+    12
+}
+
+fn block_26e(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 26E: 12AC  JP 0x2AC
+    cpu.pc = 0x2AC;
+    1
+}
+
+fn block_270(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 270: 4600  SNE V6, 0x00
+    cpu.pc = if cpu.v[6] != 0x00 { 0x274 } else { 0x272 };
+    1
+}
+
+fn block_272(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 272: 6801  LD V8, 0x01
+    cpu.v[8] = 0x01;
+    cpu.pc = 0x274;
+    1
+}
+
+fn block_274(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 274: 463F  SNE V6, 0x3F
+    cpu.pc = if cpu.v[6] != 0x3F { 0x278 } else { 0x276 };
+    1
+}
+
+fn block_276(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 276: 68FF  LD V8, 0xFF
+    cpu.v[8] = 0xFF;
+    cpu.pc = 0x278;
+    1
+}
+
+fn block_278(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 278: 4700  SNE V7, 0x00
+    cpu.pc = if cpu.v[7] != 0x00 { 0x27C } else { 0x27A };
+    1
+}
+
+fn block_27a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 27A: 6901  LD V9, 0x01
+    cpu.v[9] = 0x01;
+    cpu.pc = 0x27C;
+    1
+}
+
+fn block_27c(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 27C: D671  DRW V6, V7, 1
+    cpu.pc = 0x27C;
+    interpret(chip8, cpu);
+    // 27E: 3F01  SE VF, 0x01
+    cpu.pc = if cpu.v[15] == 0x01 { 0x282 } else { 0x280 };
+    2
+}
+
+fn block_280(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 280: 12AA  JP 0x2AA
+    cpu.pc = 0x2AA;
+    1
+}
+
+fn block_282(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 282: 471F  SNE V7, 0x1F
+    cpu.pc = if cpu.v[7] != 0x1F { 0x286 } else { 0x284 };
+    1
+}
+
+fn block_284(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 284: 12AA  JP 0x2AA
+    cpu.pc = 0x2AA;
+    1
+}
+
+fn block_286(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 286: 6005  LD V0, 0x05
+    cpu.v[0] = 0x05;
+    // 288: 8075  SUB V0, V7
+    let no_borrow = cpu.v[0] >= cpu.v[7];
+    cpu.v[0] = cpu.v[0].wrapping_sub(cpu.v[7]);
+    cpu.v[15] = no_borrow as u8;
+    // 28A: 3F00  SE VF, 0x00
+    cpu.pc = if cpu.v[15] == 0x00 { 0x28E } else { 0x28C };
+    3
+}
+
+fn block_28c(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 28C: 12AA  JP 0x2AA
+    cpu.pc = 0x2AA;
+    1
+}
+
+fn block_28e(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 28E: 6001  LD V0, 0x01
+    cpu.v[0] = 0x01;
+    // 290: F018  LD ST, V0
+    cpu.sound_timer = cpu.v[0];
+    // 292: 8060  LD V0, V6
+    cpu.v[0] = cpu.v[6];
+    // 294: 61FC  LD V1, 0xFC
+    cpu.v[1] = 0xFC;
+    // 296: 8012  AND V0, V1
+    cpu.v[0] &= cpu.v[1];
+    cpu.v[15] = 0;
+    // 298: A30C  LD I, 0x30C
+    cpu.i = 0x30C;
+    // 29A: D071  DRW V0, V7, 1
+    cpu.pc = 0x29A;
+    interpret(chip8, cpu);
+    // 29C: 60FE  LD V0, 0xFE
+    cpu.v[0] = 0xFE;
+    // 29E: 8903  XOR V9, V0
+    cpu.v[9] ^= cpu.v[0];
+    cpu.v[15] = 0;
+    // 2A0: 22F6  CALL 0x2F6
+    if cpu.sp >= 16 {
+        // Overflows the stack in the interpreter
+        cpu.pc = 0x2A0;
+        return 9;
+    }
+    cpu.stack[cpu.sp as usize] = 0x2A2;
+    cpu.sp += 1;
+    cpu.pc = 0x2F6;
+    10
+}
+
+fn block_2a2(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2A2: 7501  ADD V5, 0x01
+    cpu.v[5] = cpu.v[5].wrapping_add(0x01);
+    // 2A4: 22F6  CALL 0x2F6
+    if cpu.sp >= 16 {
+        // Overflows the stack in the interpreter
+        cpu.pc = 0x2A4;
+        return 1;
+    }
+    cpu.stack[cpu.sp as usize] = 0x2A6;
+    cpu.sp += 1;
+    cpu.pc = 0x2F6;
+    2
+}
+
+fn block_2a6(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2A6: 4560  SNE V5, 0x60
+    cpu.pc = if cpu.v[5] != 0x60 { 0x2AA } else { 0x2A8 };
+    1
+}
+
+fn block_2a8(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2A8: 12DE  JP 0x2DE
+    cpu.pc = 0x2DE;
+    1
+}
+
+fn block_2aa(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2AA: 1246  JP 0x246
+    cpu.pc = 0x246;
+    1
+}
+
+fn block_2ac(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2AC: 69FF  LD V9, 0xFF
+    cpu.v[9] = 0xFF;
+    // 2AE: 8060  LD V0, V6
+    cpu.v[0] = cpu.v[6];
+    // 2B0: 80C5  SUB V0, VC
+    let no_borrow = cpu.v[0] >= cpu.v[12];
+    cpu.v[0] = cpu.v[0].wrapping_sub(cpu.v[12]);
+    cpu.v[15] = no_borrow as u8;
+    // 2B2: 3F01  SE VF, 0x01
+    cpu.pc = if cpu.v[15] == 0x01 { 0x2B6 } else { 0x2B4 };
+    4
+}
+
+fn block_2b4(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2B4: 12CA  JP 0x2CA
+    cpu.pc = 0x2CA;
+    1
+}
+
+fn block_2b6(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2B6: 6102  LD V1, 0x02
+    cpu.v[1] = 0x02;
+    // 2B8: 8015  SUB V0, V1
+    let no_borrow = cpu.v[0] >= cpu.v[1];
+    cpu.v[0] = cpu.v[0].wrapping_sub(cpu.v[1]);
+    cpu.v[15] = no_borrow as u8;
+    // 2BA: 3F01  SE VF, 0x01
+    cpu.pc = if cpu.v[15] == 0x01 { 0x2BE } else { 0x2BC };
+    3
+}
+
+fn block_2bc(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2BC: 12E0  JP 0x2E0
+    cpu.pc = 0x2E0;
+    1
+}
+
+fn block_2be(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2BE: 8015  SUB V0, V1
+    let no_borrow = cpu.v[0] >= cpu.v[1];
+    cpu.v[0] = cpu.v[0].wrapping_sub(cpu.v[1]);
+    cpu.v[15] = no_borrow as u8;
+    // 2C0: 3F01  SE VF, 0x01
+    cpu.pc = if cpu.v[15] == 0x01 { 0x2C4 } else { 0x2C2 };
+    2
+}
+
+fn block_2c2(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2C2: 12EE  JP 0x2EE
+    cpu.pc = 0x2EE;
+    1
+}
+
+fn block_2c4(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2C4: 8015  SUB V0, V1
+    let no_borrow = cpu.v[0] >= cpu.v[1];
+    cpu.v[0] = cpu.v[0].wrapping_sub(cpu.v[1]);
+    cpu.v[15] = no_borrow as u8;
+    // 2C6: 3F01  SE VF, 0x01
+    cpu.pc = if cpu.v[15] == 0x01 { 0x2CA } else { 0x2C8 };
+    2
+}
+
+fn block_2c8(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2C8: 12E8  JP 0x2E8
+    cpu.pc = 0x2E8;
+    1
+}
+
+fn block_2ca(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2CA: 6020  LD V0, 0x20
+    cpu.v[0] = 0x20;
+    // 2CC: F018  LD ST, V0
+    cpu.sound_timer = cpu.v[0];
+    // 2CE: A30E  LD I, 0x30E
+    cpu.i = 0x30E;
+    // 2D0: 7EFF  ADD VE, 0xFF
+    cpu.v[14] = cpu.v[14].wrapping_add(0xFF);
+    // 2D2: 80E0  LD V0, VE
+    cpu.v[0] = cpu.v[14];
+    // 2D4: 8004  ADD V0, V0
+    let (sum, carry) = cpu.v[0].overflowing_add(cpu.v[0]);
+    cpu.v[0] = sum;
+    cpu.v[15] = carry as u8;
+    // 2D6: 6100  LD V1, 0x00
+    cpu.v[1] = 0x00;
+    // 2D8: D011  DRW V0, V1, 1
+    cpu.pc = 0x2D8;
+    interpret(chip8, cpu);
+    // 2DA: 3E00  SE VE, 0x00
+    cpu.pc = if cpu.v[14] == 0x00 { 0x2DE } else { 0x2DC };
+    9
+}
+
+fn block_2dc(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2DC: 1230  JP 0x230
+    cpu.pc = 0x230;
+    1
+}
+
+fn block_2de(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2DE: 12DE  JP 0x2DE
+    cpu.pc = 0x2DE;
+    1
+}
+
+fn block_2e0(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2E0: 78FF  ADD V8, 0xFF
+    cpu.v[8] = cpu.v[8].wrapping_add(0xFF);
+    // 2E2: 48FE  SNE V8, 0xFE
+    cpu.pc = if cpu.v[8] != 0xFE { 0x2E6 } else { 0x2E4 };
+    2
+}
+
+fn block_2e4(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2E4: 68FF  LD V8, 0xFF
+    cpu.v[8] = 0xFF;
+    cpu.pc = 0x2E6;
+    1
+}
+
+fn block_2e6(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2E6: 12EE  JP 0x2EE
+    cpu.pc = 0x2EE;
+    1
+}
+
+fn block_2e8(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2E8: 7801  ADD V8, 0x01
+    cpu.v[8] = cpu.v[8].wrapping_add(0x01);
+    // 2EA: 4802  SNE V8, 0x02
+    cpu.pc = if cpu.v[8] != 0x02 { 0x2EE } else { 0x2EC };
+    2
+}
+
+fn block_2ec(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2EC: 6801  LD V8, 0x01
+    cpu.v[8] = 0x01;
+    cpu.pc = 0x2EE;
+    1
+}
+
+fn block_2ee(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2EE: 6004  LD V0, 0x04
+    cpu.v[0] = 0x04;
+    // 2F0: F018  LD ST, V0
+    cpu.sound_timer = cpu.v[0];
+    // 2F2: 69FF  LD V9, 0xFF
+    cpu.v[9] = 0xFF;
+    // 2F4: 1270  JP 0x270
+    cpu.pc = 0x270;
+    4
+}
+
+fn block_2f6(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2F6: A314  LD I, 0x314
+    cpu.i = 0x314;
+    // 2F8: F533  LD B, V5
+    cpu.pc = 0x2F8;
+    interpret(chip8, cpu);
+    2
+}
+
+fn block_2fa(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2FA: F265  LD V2, [I]
+    cpu.pc = 0x2FA;
+    interpret(chip8, cpu);
+    // 2FC: F129  LD F, V1
+    cpu.i = 5 * cpu.v[1] as u16;
+    // 2FE: 6337  LD V3, 0x37
+    cpu.v[3] = 0x37;
+    // 300: 6400  LD V4, 0x00
+    cpu.v[4] = 0x00;
+    // 302: D345  DRW V3, V4, 5
+    cpu.pc = 0x302;
+    interpret(chip8, cpu);
+    // 304: 7305  ADD V3, 0x05
+    cpu.v[3] = cpu.v[3].wrapping_add(0x05);
+    // 306: F229  LD F, V2
+    cpu.i = 5 * cpu.v[2] as u16;
+    // 308: D345  DRW V3, V4, 5
+    cpu.pc = 0x308;
+    interpret(chip8, cpu);
+    // 30A: 00EE  RET
+    if cpu.sp == 0 {
+        // Underflows the stack in the interpreter
+        cpu.pc = 0x30A;
+        return 8;
+    }
+    cpu.sp -= 1;
+    cpu.pc = cpu.stack[cpu.sp as usize];
+    9
+}
@@ -0,0 +1,1330 @@
+//! 3-corax+.ch8 recompiled to Rust by `chippers recompile`.
+//!
+//! Runs on a `Chip8` that loaded [`ROM`]: call [`run`] in place of `Chip8::run`, or
+//! hand [`Recompiled`] to `Emulator::set_backend`. Code not found by following the jumps
+//! of the ROM, and code the ROM overwrites, run in the interpreter.
+
+use chippers::chip8::{Chip8, Cpu};
+use chippers::emulator::Backend;
+
+/// The ROM, loaded at 200.
+pub const ROM: [u8; 697] = [
+    0x12, 0x08, 0xA4, 0x65, 0xDA, 0xB4, 0x00, 0xEE, 0x00, 0xE0, 0x68, 0x32, 0x6B, 0x1A, 0xA4, 0xB1,
+    0xD8, 0xB4, 0x68, 0x3A, 0xA4, 0xB5, 0xD8, 0xB4, 0x68, 0x02, 0x69, 0x06, 0x6A, 0x0B, 0x6B, 0x01,
+    0x65, 0x2A, 0x66, 0x2B, 0xA4, 0x75, 0xD8, 0xB4, 0xA4, 0xAD, 0xD9, 0xB4, 0xA4, 0x65, 0x36, 0x2B,
+    0xA4, 0x61, 0xDA, 0xB4, 0x6B, 0x06, 0xA4, 0x79, 0xD8, 0xB4, 0xA4, 0xAD, 0xD9, 0xB4, 0xA4, 0x61,
+    0x45, 0x2A, 0xA4, 0x65, 0xDA, 0xB4, 0x6B, 0x0B, 0xA4, 0x7D, 0xD8, 0xB4, 0xA4, 0xAD, 0xD9, 0xB4,
+    0xA4, 0x61, 0x55, 0x60, 0xA4, 0x65, 0xDA, 0xB4, 0x6B, 0x10, 0xA4, 0x85, 0xD8, 0xB4, 0xA4, 0xAD,
+    0xD9, 0xB4, 0xA4, 0x61, 0x76, 0xFF, 0x46, 0x2A, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0x8D,
+    0xD8, 0xB4, 0xA4, 0xAD, 0xD9, 0xB4, 0xA4, 0x61, 0x95, 0x60, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05,
+    0xA4, 0x6D, 0xD8, 0xB4, 0xA4, 0xAD, 0xD9, 0xB4, 0xA4, 0x65, 0x12, 0x8E, 0xA4, 0x61, 0xDA, 0xB4,
+    0x68, 0x12, 0x69, 0x16, 0x6A, 0x1B, 0x6B, 0x01, 0xA4, 0x71, 0xD8, 0xB4, 0xA4, 0xAD, 0xD9, 0xB4,
+    0x22, 0x02, 0x7B, 0x05, 0xA4, 0x69, 0xD8, 0xB4, 0xA4, 0xA1, 0xD9, 0xB4, 0xA4, 0x65, 0xDA, 0xB4,
+    0x7B, 0x05, 0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x69, 0xD9, 0xB4, 0xA4, 0x61, 0x65, 0x2A, 0x67, 0x00,
+    0x87, 0x50, 0x47, 0x2A, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x6D,
+    0xD9, 0xB4, 0xA4, 0x61, 0x66, 0x0B, 0x67, 0x2A, 0x87, 0x61, 0x47, 0x2B, 0xA4, 0x65, 0xDA, 0xB4,
+    0x7B, 0x05, 0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x71, 0xD9, 0xB4, 0xA4, 0x61, 0x66, 0x78, 0x67, 0x1F,
+    0x87, 0x62, 0x47, 0x18, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x75,
+    0xD9, 0xB4, 0xA4, 0x61, 0x66, 0x78, 0x67, 0x1F, 0x87, 0x63, 0x47, 0x67, 0xA4, 0x65, 0xDA, 0xB4,
+    0x68, 0x22, 0x69, 0x26, 0x6A, 0x2B, 0x6B, 0x01, 0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x79, 0xD9, 0xB4,
+    0xA4, 0x61, 0x66, 0x8C, 0x67, 0x8C, 0x87, 0x64, 0x47, 0x18, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05,
+    0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x7D, 0xD9, 0xB4, 0xA4, 0x61, 0x66, 0x8C, 0x67, 0x78, 0x87, 0x65,
+    0x47, 0xEC, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x85, 0xD9, 0xB4,
+    0xA4, 0x61, 0x66, 0x78, 0x67, 0x8C, 0x87, 0x67, 0x47, 0xEC, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05,
+    0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0x81, 0xD9, 0xB4, 0xA4, 0x61, 0x66, 0x0F, 0x86, 0x66, 0x46, 0x07,
+    0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0x89, 0xD8, 0xB4, 0xA4, 0xA1, 0xD9, 0xB4, 0xA4, 0x61,
+    0x66, 0xE0, 0x86, 0x6E, 0x46, 0xC0, 0xA4, 0x65, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0xA5, 0xD8, 0xB4,
+    0xA4, 0x81, 0xD9, 0xB4, 0xA4, 0x5E, 0xF1, 0x65, 0xA4, 0x65, 0x30, 0xAA, 0xA4, 0x61, 0x31, 0x55,
+    0xA4, 0x61, 0xDA, 0xB4, 0x68, 0x32, 0x69, 0x36, 0x6A, 0x3B, 0x6B, 0x01, 0xA4, 0xA5, 0xD8, 0xB4,
+    0xA4, 0x7D, 0xD9, 0xB4, 0xA4, 0x5E, 0x60, 0x00, 0x61, 0x30, 0xF1, 0x55, 0xA4, 0x5E, 0xF0, 0x65,
+    0x81, 0x00, 0xA4, 0x5F, 0xF0, 0x65, 0xA4, 0x65, 0x30, 0x30, 0xA4, 0x61, 0x31, 0x00, 0xA4, 0x61,
+    0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0xA5, 0xD8, 0xB4, 0xA4, 0x75, 0xD9, 0xB4, 0xA4, 0x5E, 0x66, 0x89,
+    0xF6, 0x33, 0xF2, 0x65, 0xA4, 0x65, 0x30, 0x01, 0xA4, 0x61, 0x31, 0x03, 0xA4, 0x61, 0x32, 0x07,
+    0xA4, 0x61, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0xA5, 0xD8, 0xB4, 0xA4, 0xA1, 0xD9, 0xB4, 0xA4, 0x61,
+    0x66, 0x04, 0xF6, 0x1E, 0xDA, 0xB4, 0x7B, 0x05, 0xA4, 0xA9, 0xD8, 0xB4, 0xA4, 0xAD, 0xD9, 0xB4,
+    0xA4, 0x65, 0x66, 0xFF, 0x76, 0x0A, 0x36, 0x09, 0xA4, 0x61, 0x86, 0x66, 0x36, 0x04, 0xA4, 0x61,
+    0x66, 0xFF, 0x60, 0x0A, 0x86, 0x04, 0x36, 0x09, 0xA4, 0x61, 0x86, 0x66, 0x36, 0x04, 0xA4, 0x61,
+    0x66, 0xFF, 0x86, 0x6E, 0x86, 0x66, 0x36, 0x7F, 0xA4, 0x61, 0x86, 0x66, 0x86, 0x6E, 0x36, 0x7E,
+    0xA4, 0x61, 0x66, 0x05, 0x76, 0xF6, 0x36, 0xFB, 0xA4, 0x61, 0x66, 0x05, 0x86, 0x05, 0x36, 0xFB,
+    0xA4, 0x61, 0x66, 0x05, 0x80, 0x67, 0x30, 0xFB, 0xA4, 0x61, 0xDA, 0xB4, 0x14, 0x5C, 0xAA, 0x55,
+    0x00, 0x00, 0xA0, 0x40, 0xA0, 0x00, 0xA0, 0xC0, 0x80, 0xE0, 0xA0, 0xA0, 0xE0, 0xC0, 0x40, 0x40,
+    0xE0, 0xE0, 0x20, 0xC0, 0xE0, 0xE0, 0x60, 0x20, 0xE0, 0xA0, 0xE0, 0x20, 0x20, 0xE0, 0xC0, 0x20,
+    0xC0, 0x60, 0x80, 0xE0, 0xE0, 0xE0, 0x20, 0x40, 0x40, 0xE0, 0xE0, 0xA0, 0xE0, 0xE0, 0xE0, 0x20,
+    0xC0, 0x40, 0xA0, 0xE0, 0xA0, 0xC0, 0xE0, 0xA0, 0xE0, 0xE0, 0x80, 0x80, 0xE0, 0xC0, 0xA0, 0xA0,
+    0xC0, 0xE0, 0xC0, 0x80, 0xE0, 0xE0, 0x80, 0xC0, 0x80, 0x00, 0xA0, 0xA0, 0x40, 0xA0, 0x40, 0xA0,
+    0xA0, 0x0A, 0xAE, 0xA2, 0x42, 0x10, 0x30, 0x10, 0xB8,
+];
+
+/// Runs the recompiled code in place of the interpreter.
+pub struct Recompiled;
+
+impl Backend for Recompiled {
+    fn run(&mut self, chip8: &mut Chip8, cycles: u64) {
+        run(chip8, cycles);
+    }
+}
+
+/// Runs `cycles` instructions like `Chip8::run`.
+pub fn run(chip8: &mut Chip8, cycles: u64) {
+    let mut cpu = chip8.cpu();
+    let mut remaining = cycles;
+    while remaining > 0 {
+        let ran = match cpu.pc {
+            0x200 if remaining >= 1 && intact(chip8, 0x200, 0x202) => {
+                block_200(chip8, &mut cpu)
+            }
+            0x202 if remaining >= 3 && intact(chip8, 0x202, 0x208) => {
+                block_202(chip8, &mut cpu)
+            }
+            0x208 if remaining >= 20 && intact(chip8, 0x208, 0x230) => {
+                block_208(chip8, &mut cpu)
+            }
+            0x230 if remaining >= 1 && intact(chip8, 0x230, 0x232) => {
+                block_230(chip8, &mut cpu)
+            }
+            0x232 if remaining >= 8 && intact(chip8, 0x232, 0x242) => {
+                block_232(chip8, &mut cpu)
+            }
+            0x242 if remaining >= 1 && intact(chip8, 0x242, 0x244) => {
+                block_242(chip8, &mut cpu)
+            }
+            0x244 if remaining >= 8 && intact(chip8, 0x244, 0x254) => {
+                block_244(chip8, &mut cpu)
+            }
+            0x254 if remaining >= 1 && intact(chip8, 0x254, 0x256) => {
+                block_254(chip8, &mut cpu)
+            }
+            0x256 if remaining >= 9 && intact(chip8, 0x256, 0x268) => {
+                block_256(chip8, &mut cpu)
+            }
+            0x268 if remaining >= 1 && intact(chip8, 0x268, 0x26A) => {
+                block_268(chip8, &mut cpu)
+            }
+            0x26A if remaining >= 8 && intact(chip8, 0x26A, 0x27A) => {
+                block_26a(chip8, &mut cpu)
+            }
+            0x27A if remaining >= 1 && intact(chip8, 0x27A, 0x27C) => {
+                block_27a(chip8, &mut cpu)
+            }
+            0x27C if remaining >= 8 && intact(chip8, 0x27C, 0x28C) => {
+                block_27c(chip8, &mut cpu)
+            }
+            0x28E if remaining >= 10 && intact(chip8, 0x28E, 0x2A2) => {
+                block_28e(chip8, &mut cpu)
+            }
+            0x2A2 if remaining >= 17 && intact(chip8, 0x2A2, 0x2C4) => {
+                block_2a2(chip8, &mut cpu)
+            }
+            0x2C4 if remaining >= 1 && intact(chip8, 0x2C4, 0x2C6) => {
+                block_2c4(chip8, &mut cpu)
+            }
+            0x2C6 if remaining >= 11 && intact(chip8, 0x2C6, 0x2DC) => {
+                block_2c6(chip8, &mut cpu)
+            }
+            0x2DC if remaining >= 1 && intact(chip8, 0x2DC, 0x2DE) => {
+                block_2dc(chip8, &mut cpu)
+            }
+            0x2DE if remaining >= 11 && intact(chip8, 0x2DE, 0x2F4) => {
+                block_2de(chip8, &mut cpu)
+            }
+            0x2F4 if remaining >= 1 && intact(chip8, 0x2F4, 0x2F6) => {
+                block_2f4(chip8, &mut cpu)
+            }
+            0x2F6 if remaining >= 11 && intact(chip8, 0x2F6, 0x30C) => {
+                block_2f6(chip8, &mut cpu)
+            }
+            0x30C if remaining >= 1 && intact(chip8, 0x30C, 0x30E) => {
+                block_30c(chip8, &mut cpu)
+            }
+            0x30E if remaining >= 14 && intact(chip8, 0x30E, 0x32A) => {
+                block_30e(chip8, &mut cpu)
+            }
+            0x32A if remaining >= 1 && intact(chip8, 0x32A, 0x32C) => {
+                block_32a(chip8, &mut cpu)
+            }
+            0x32C if remaining >= 11 && intact(chip8, 0x32C, 0x342) => {
+                block_32c(chip8, &mut cpu)
+            }
+            0x342 if remaining >= 1 && intact(chip8, 0x342, 0x344) => {
+                block_342(chip8, &mut cpu)
+            }
+            0x344 if remaining >= 11 && intact(chip8, 0x344, 0x35A) => {
+                block_344(chip8, &mut cpu)
+            }
+            0x35A if remaining >= 1 && intact(chip8, 0x35A, 0x35C) => {
+                block_35a(chip8, &mut cpu)
+            }
+            0x35C if remaining >= 10 && intact(chip8, 0x35C, 0x370) => {
+                block_35c(chip8, &mut cpu)
+            }
+            0x370 if remaining >= 1 && intact(chip8, 0x370, 0x372) => {
+                block_370(chip8, &mut cpu)
+            }
+            0x372 if remaining >= 10 && intact(chip8, 0x372, 0x386) => {
+                block_372(chip8, &mut cpu)
+            }
+            0x386 if remaining >= 1 && intact(chip8, 0x386, 0x388) => {
+                block_386(chip8, &mut cpu)
+            }
+            0x388 if remaining >= 10 && intact(chip8, 0x388, 0x39C) => {
+                block_388(chip8, &mut cpu)
+            }
+            0x39C if remaining >= 1 && intact(chip8, 0x39C, 0x39E) => {
+                block_39c(chip8, &mut cpu)
+            }
+            0x39E if remaining >= 1 && intact(chip8, 0x39E, 0x3A0) => {
+                block_39e(chip8, &mut cpu)
+            }
+            0x3A0 if remaining >= 1 && intact(chip8, 0x3A0, 0x3A2) => {
+                block_3a0(chip8, &mut cpu)
+            }
+            0x3A2 if remaining >= 13 && intact(chip8, 0x3A2, 0x3BC) => {
+                block_3a2(chip8, &mut cpu)
+            }
+            0x3BC if remaining >= 7 && intact(chip8, 0x3BC, 0x3CA) => {
+                block_3bc(chip8, &mut cpu)
+            }
+            0x3CA if remaining >= 1 && intact(chip8, 0x3CA, 0x3CC) => {
+                block_3ca(chip8, &mut cpu)
+            }
+            0x3CC if remaining >= 1 && intact(chip8, 0x3CC, 0x3CE) => {
+                block_3cc(chip8, &mut cpu)
+            }
+            0x3CE if remaining >= 1 && intact(chip8, 0x3CE, 0x3D0) => {
+                block_3ce(chip8, &mut cpu)
+            }
+            0x3D0 if remaining >= 9 && intact(chip8, 0x3D0, 0x3E2) => {
+                block_3d0(chip8, &mut cpu)
+            }
+            0x3E2 if remaining >= 3 && intact(chip8, 0x3E2, 0x3E8) => {
+                block_3e2(chip8, &mut cpu)
+            }
+            0x3E8 if remaining >= 1 && intact(chip8, 0x3E8, 0x3EA) => {
+                block_3e8(chip8, &mut cpu)
+            }
+            0x3EA if remaining >= 1 && intact(chip8, 0x3EA, 0x3EC) => {
+                block_3ea(chip8, &mut cpu)
+            }
+            0x3EC if remaining >= 1 && intact(chip8, 0x3EC, 0x3EE) => {
+                block_3ec(chip8, &mut cpu)
+            }
+            0x3EE if remaining >= 1 && intact(chip8, 0x3EE, 0x3F0) => {
+                block_3ee(chip8, &mut cpu)
+            }
+            0x3F0 if remaining >= 1 && intact(chip8, 0x3F0, 0x3F2) => {
+                block_3f0(chip8, &mut cpu)
+            }
+            0x3F2 if remaining >= 19 && intact(chip8, 0x3F2, 0x418) => {
+                block_3f2(chip8, &mut cpu)
+            }
+            0x418 if remaining >= 1 && intact(chip8, 0x418, 0x41A) => {
+                block_418(chip8, &mut cpu)
+            }
+            0x41A if remaining >= 2 && intact(chip8, 0x41A, 0x41E) => {
+                block_41a(chip8, &mut cpu)
+            }
+            0x41E if remaining >= 1 && intact(chip8, 0x41E, 0x420) => {
+                block_41e(chip8, &mut cpu)
+            }
+            0x420 if remaining >= 4 && intact(chip8, 0x420, 0x428) => {
+                block_420(chip8, &mut cpu)
+            }
+            0x428 if remaining >= 1 && intact(chip8, 0x428, 0x42A) => {
+                block_428(chip8, &mut cpu)
+            }
+            0x42A if remaining >= 2 && intact(chip8, 0x42A, 0x42E) => {
+                block_42a(chip8, &mut cpu)
+            }
+            0x42E if remaining >= 1 && intact(chip8, 0x42E, 0x430) => {
+                block_42e(chip8, &mut cpu)
+            }
+            0x430 if remaining >= 4 && intact(chip8, 0x430, 0x438) => {
+                block_430(chip8, &mut cpu)
+            }
+            0x438 if remaining >= 1 && intact(chip8, 0x438, 0x43A) => {
+                block_438(chip8, &mut cpu)
+            }
+            0x43A if remaining >= 3 && intact(chip8, 0x43A, 0x440) => {
+                block_43a(chip8, &mut cpu)
+            }
+            0x440 if remaining >= 1 && intact(chip8, 0x440, 0x442) => {
+                block_440(chip8, &mut cpu)
+            }
+            0x442 if remaining >= 3 && intact(chip8, 0x442, 0x448) => {
+                block_442(chip8, &mut cpu)
+            }
+            0x448 if remaining >= 1 && intact(chip8, 0x448, 0x44A) => {
+                block_448(chip8, &mut cpu)
+            }
+            0x44A if remaining >= 3 && intact(chip8, 0x44A, 0x450) => {
+                block_44a(chip8, &mut cpu)
+            }
+            0x450 if remaining >= 1 && intact(chip8, 0x450, 0x452) => {
+                block_450(chip8, &mut cpu)
+            }
+            0x452 if remaining >= 3 && intact(chip8, 0x452, 0x458) => {
+                block_452(chip8, &mut cpu)
+            }
+            0x458 if remaining >= 1 && intact(chip8, 0x458, 0x45A) => {
+                block_458(chip8, &mut cpu)
+            }
+            0x45A if remaining >= 1 && intact(chip8, 0x45A, 0x45C) => {
+                block_45a(chip8, &mut cpu)
+            }
+            0x45C if remaining >= 1 && intact(chip8, 0x45C, 0x45E) => {
+                block_45c(chip8, &mut cpu)
+            }
+_ => 0,
+        };
+        if ran > 0 {
+            remaining -= ran;
+            continue;
+        }
+        let pc = cpu.pc as usize;
+        let memory = chip8.memory();
+        if pc >= 0x1000 || (pc < 0xFFF && memory[pc] == 0 && memory[pc + 1] == 0) {
+            // Every cycle from here on does nothing
+            break;
+        }
+        interpret(chip8, &mut cpu);
+        remaining -= 1;
+    }
+    chip8.set_cpu(&cpu);
+}
+
+/// Whether memory from `start` to `end` still holds the code of the ROM.
+fn intact(chip8: &Chip8, start: usize, end: usize) -> bool {
+    chip8.memory()[start..end] == ROM[start - 0x200..end - 0x200]
+}
+
+/// Runs the instruction at `cpu.pc` in the interpreter.
+fn interpret(chip8: &mut Chip8, cpu: &mut Cpu) {
+    chip8.set_cpu(cpu);
+    chip8.cycle();
+    *cpu = chip8.cpu();
+}
+
+fn block_200(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 200: 1208  JP 0x208
+    cpu.pc = 0x208;
+    1
+}
+
+fn block_202(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 202: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 204: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x204;
+    interpret(chip8, cpu);
+    // 206: 00EE  RET
+    if cpu.sp == 0 {
+        // Underflows the stack in the interpreter
+        cpu.pc = 0x206;
+        return 2;
+    }
+    cpu.sp -= 1;
+    cpu.pc = cpu.stack[cpu.sp as usize];
+    3
+}
+
+fn block_208(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 208: 00E0  CLS
+    cpu.pc = 0x208;
+    interpret(chip8, cpu);
+    // 20A: 6832  LD V8, 0x32
+    cpu.v[8] = 0x32;
+    // 20C: 6B1A  LD VB, 0x1A
+    cpu.v[11] = 0x1A;
+    // 20E: A4B1  LD I, 0x4B1
+    cpu.i = 0x4B1;
+    // 210: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x210;
+    interpret(chip8, cpu);
+    // 212: 683A  LD V8, 0x3A
+    cpu.v[8] = 0x3A;
+    // 214: A4B5  LD I, 0x4B5
+    cpu.i = 0x4B5;
+    // 216: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x216;
+    interpret(chip8, cpu);
+    // 218: 6802  LD V8, 0x02
+    cpu.v[8] = 0x02;
+    // 21A: 6906  LD V9, 0x06
+    cpu.v[9] = 0x06;
+    // 21C: 6A0B  LD VA, 0x0B
+    cpu.v[10] = 0x0B;
+    // 21E: 6B01  LD VB, 0x01
+    cpu.v[11] = 0x01;
+    // 220: 652A  LD V5, 0x2A
+    cpu.v[5] = 0x2A;
+    // 222: 662B  LD V6, 0x2B
+    cpu.v[6] = 0x2B;
+    // 224: A475  LD I, 0x475
+    cpu.i = 0x475;
+    // 226: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x226;
+    interpret(chip8, cpu);
+    // 228: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 22A: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x22A;
+    interpret(chip8, cpu);
+    // 22C: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 22E: 362B  SE V6, 0x2B
+    cpu.pc = if cpu.v[6] == 0x2B { 0x232 } else { 0x230 };
+    20
+}
+
+fn block_230(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 230: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x232;
+    1
+}
+
+fn block_232(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 232: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x232;
+    interpret(chip8, cpu);
+    // 234: 6B06  LD VB, 0x06
+    cpu.v[11] = 0x06;
+    // 236: A479  LD I, 0x479
+    cpu.i = 0x479;
+    // 238: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x238;
+    interpret(chip8, cpu);
+    // 23A: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 23C: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x23C;
+    interpret(chip8, cpu);
+    // 23E: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 240: 452A  SNE V5, 0x2A
+    cpu.pc = if cpu.v[5] != 0x2A { 0x244 } else { 0x242 };
+    8
+}
+
+fn block_242(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 242: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x244;
+    1
+}
+
+fn block_244(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 244: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x244;
+    interpret(chip8, cpu);
+    // 246: 6B0B  LD VB, 0x0B
+    cpu.v[11] = 0x0B;
+    // 248: A47D  LD I, 0x47D
+    cpu.i = 0x47D;
+    // 24A: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x24A;
+    interpret(chip8, cpu);
+    // 24C: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 24E: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x24E;
+    interpret(chip8, cpu);
+    // 250: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 252: 5560  SE V5, V6
+    cpu.pc = if cpu.v[5] == cpu.v[6] { 0x256 } else { 0x254 };
+    8
+}
+
+fn block_254(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 254: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x256;
+    1
+}
+
+fn block_256(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 256: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x256;
+    interpret(chip8, cpu);
+    // 258: 6B10  LD VB, 0x10
+    cpu.v[11] = 0x10;
+    // 25A: A485  LD I, 0x485
+    cpu.i = 0x485;
+    // 25C: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x25C;
+    interpret(chip8, cpu);
+    // 25E: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 260: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x260;
+    interpret(chip8, cpu);
+    // 262: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 264: 76FF  ADD V6, 0xFF
+    cpu.v[6] = cpu.v[6].wrapping_add(0xFF);
+    // 266: 462A  SNE V6, 0x2A
+    cpu.pc = if cpu.v[6] != 0x2A { 0x26A } else { 0x268 };
+    9
+}
+
+fn block_268(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 268: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x26A;
+    1
+}
+
+fn block_26a(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 26A: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x26A;
+    interpret(chip8, cpu);
+    // 26C: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 26E: A48D  LD I, 0x48D
+    cpu.i = 0x48D;
+    // 270: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x270;
+    interpret(chip8, cpu);
+    // 272: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 274: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x274;
+    interpret(chip8, cpu);
+    // 276: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 278: 9560  SNE V5, V6
+    cpu.pc = if cpu.v[5] != cpu.v[6] { 0x27C } else { 0x27A };
+    8
+}
+
+fn block_27a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 27A: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x27C;
+    1
+}
+
+fn block_27c(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 27C: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x27C;
+    interpret(chip8, cpu);
+    // 27E: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 280: A46D  LD I, 0x46D
+    cpu.i = 0x46D;
+    // 282: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x282;
+    interpret(chip8, cpu);
+    // 284: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 286: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x286;
+    interpret(chip8, cpu);
+    // 288: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 28A: 128E  JP 0x28E
+    cpu.pc = 0x28E;
+    8
+}
+
+fn block_28e(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 28E: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x28E;
+    interpret(chip8, cpu);
+    // 290: 6812  LD V8, 0x12
+    cpu.v[8] = 0x12;
+    // 292: 6916  LD V9, 0x16
+    cpu.v[9] = 0x16;
+    // 294: 6A1B  LD VA, 0x1B
+    cpu.v[10] = 0x1B;
+    // 296: 6B01  LD VB, 0x01
+    cpu.v[11] = 0x01;
+    // 298: A471  LD I, 0x471
+    cpu.i = 0x471;
+    // 29A: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x29A;
+    interpret(chip8, cpu);
+    // 29C: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 29E: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x29E;
+    interpret(chip8, cpu);
+    // 2A0: 2202  CALL 0x202
+    if cpu.sp >= 16 {
+        // Overflows the stack in the interpreter
+        cpu.pc = 0x2A0;
+        return 9;
+    }
+    cpu.stack[cpu.sp as usize] = 0x2A2;
+    cpu.sp += 1;
+    cpu.pc = 0x202;
+    10
+}
+
+fn block_2a2(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2A2: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 2A4: A469  LD I, 0x469
+    cpu.i = 0x469;
+    // 2A6: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x2A6;
+    interpret(chip8, cpu);
+    // 2A8: A4A1  LD I, 0x4A1
+    cpu.i = 0x4A1;
+    // 2AA: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x2AA;
+    interpret(chip8, cpu);
+    // 2AC: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 2AE: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x2AE;
+    interpret(chip8, cpu);
+    // 2B0: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 2B2: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 2B4: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x2B4;
+    interpret(chip8, cpu);
+    // 2B6: A469  LD I, 0x469
+    cpu.i = 0x469;
+    // 2B8: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x2B8;
+    interpret(chip8, cpu);
+    // 2BA: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 2BC: 652A  LD V5, 0x2A
+    cpu.v[5] = 0x2A;
+    // 2BE: 6700  LD V7, 0x00
+    cpu.v[7] = 0x00;
+    // 2C0: 8750  LD V7, V5
+    cpu.v[7] = cpu.v[5];
+    // 2C2: 472A  SNE V7, 0x2A
+    cpu.pc = if cpu.v[7] != 0x2A { 0x2C6 } else { 0x2C4 };
+    17
+}
+
+fn block_2c4(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2C4: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x2C6;
+    1
+}
+
+fn block_2c6(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2C6: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x2C6;
+    interpret(chip8, cpu);
+    // 2C8: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 2CA: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 2CC: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x2CC;
+    interpret(chip8, cpu);
+    // 2CE: A46D  LD I, 0x46D
+    cpu.i = 0x46D;
+    // 2D0: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x2D0;
+    interpret(chip8, cpu);
+    // 2D2: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 2D4: 660B  LD V6, 0x0B
+    cpu.v[6] = 0x0B;
+    // 2D6: 672A  LD V7, 0x2A
+    cpu.v[7] = 0x2A;
+    // 2D8: 8761  OR V7, V6
+    cpu.v[7] |= cpu.v[6];
+    cpu.v[15] = 0;
+    // 2DA: 472B  SNE V7, 0x2B
+    cpu.pc = if cpu.v[7] != 0x2B { 0x2DE } else { 0x2DC };
+    11
+}
+
+fn block_2dc(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2DC: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x2DE;
+    1
+}
+
+fn block_2de(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2DE: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x2DE;
+    interpret(chip8, cpu);
+    // 2E0: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 2E2: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 2E4: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x2E4;
+    interpret(chip8, cpu);
+    // 2E6: A471  LD I, 0x471
+    cpu.i = 0x471;
+    // 2E8: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x2E8;
+    interpret(chip8, cpu);
+    // 2EA: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 2EC: 6678  LD V6, 0x78
+    cpu.v[6] = 0x78;
+    // 2EE: 671F  LD V7, 0x1F
+    cpu.v[7] = 0x1F;
+    // 2F0: 8762  AND V7, V6
+    cpu.v[7] &= cpu.v[6];
+    cpu.v[15] = 0;
+    // 2F2: 4718  SNE V7, 0x18
+    cpu.pc = if cpu.v[7] != 0x18 { 0x2F6 } else { 0x2F4 };
+    11
+}
+
+fn block_2f4(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2F4: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x2F6;
+    1
+}
+
+fn block_2f6(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 2F6: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x2F6;
+    interpret(chip8, cpu);
+    // 2F8: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 2FA: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 2FC: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x2FC;
+    interpret(chip8, cpu);
+    // 2FE: A475  LD I, 0x475
+    cpu.i = 0x475;
+    // 300: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x300;
+    interpret(chip8, cpu);
+    // 302: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 304: 6678  LD V6, 0x78
+    cpu.v[6] = 0x78;
+    // 306: 671F  LD V7, 0x1F
+    cpu.v[7] = 0x1F;
+    // 308: 8763  XOR V7, V6
+    cpu.v[7] ^= cpu.v[6];
+    cpu.v[15] = 0;
+    // 30A: 4767  SNE V7, 0x67
+    cpu.pc = if cpu.v[7] != 0x67 { 0x30E } else { 0x30C };
+    11
+}
+
+fn block_30c(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 30C: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x30E;
+    1
+}
+
+fn block_30e(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 30E: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x30E;
+    interpret(chip8, cpu);
+    // 310: 6822  LD V8, 0x22
+    cpu.v[8] = 0x22;
+    // 312: 6926  LD V9, 0x26
+    cpu.v[9] = 0x26;
+    // 314: 6A2B  LD VA, 0x2B
+    cpu.v[10] = 0x2B;
+    // 316: 6B01  LD VB, 0x01
+    cpu.v[11] = 0x01;
+    // 318: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 31A: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x31A;
+    interpret(chip8, cpu);
+    // 31C: A479  LD I, 0x479
+    cpu.i = 0x479;
+    // 31E: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x31E;
+    interpret(chip8, cpu);
+    // 320: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 322: 668C  LD V6, 0x8C
+    cpu.v[6] = 0x8C;
+    // 324: 678C  LD V7, 0x8C
+    cpu.v[7] = 0x8C;
+    // 326: 8764  ADD V7, V6
+    let (sum, carry) = cpu.v[7].overflowing_add(cpu.v[6]);
+    cpu.v[7] = sum;
+    cpu.v[15] = carry as u8;
+    // 328: 4718  SNE V7, 0x18
+    cpu.pc = if cpu.v[7] != 0x18 { 0x32C } else { 0x32A };
+    14
+}
+
+fn block_32a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 32A: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x32C;
+    1
+}
+
+fn block_32c(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 32C: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x32C;
+    interpret(chip8, cpu);
+    // 32E: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 330: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 332: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x332;
+    interpret(chip8, cpu);
+    // 334: A47D  LD I, 0x47D
+    cpu.i = 0x47D;
+    // 336: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x336;
+    interpret(chip8, cpu);
+    // 338: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 33A: 668C  LD V6, 0x8C
+    cpu.v[6] = 0x8C;
+    // 33C: 6778  LD V7, 0x78
+    cpu.v[7] = 0x78;
+    // 33E: 8765  SUB V7, V6
+    let no_borrow = cpu.v[7] >= cpu.v[6];
+    cpu.v[7] = cpu.v[7].wrapping_sub(cpu.v[6]);
+    cpu.v[15] = no_borrow as u8;
+    // 340: 47EC  SNE V7, 0xEC
+    cpu.pc = if cpu.v[7] != 0xEC { 0x344 } else { 0x342 };
+    11
+}
+
+fn block_342(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 342: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x344;
+    1
+}
+
+fn block_344(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 344: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x344;
+    interpret(chip8, cpu);
+    // 346: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 348: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 34A: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x34A;
+    interpret(chip8, cpu);
+    // 34C: A485  LD I, 0x485
+    cpu.i = 0x485;
+    // 34E: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x34E;
+    interpret(chip8, cpu);
+    // 350: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 352: 6678  LD V6, 0x78
+    cpu.v[6] = 0x78;
+    // 354: 678C  LD V7, 0x8C
+    cpu.v[7] = 0x8C;
+    // 356: 8767  SUBN V7, V6
+    let no_borrow = cpu.v[6] >= cpu.v[7];
+    cpu.v[7] = cpu.v[6].wrapping_sub(cpu.v[7]);
+    cpu.v[15] = no_borrow as u8;
+    // 358: 47EC  SNE V7, 0xEC
+    cpu.pc = if cpu.v[7] != 0xEC { 0x35C } else { 0x35A };
+    11
+}
+
+fn block_35a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 35A: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x35C;
+    1
+}
+
+fn block_35c(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 35C: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x35C;
+    interpret(chip8, cpu);
+    // 35E: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 360: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 362: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x362;
+    interpret(chip8, cpu);
+    // 364: A481  LD I, 0x481
+    cpu.i = 0x481;
+    // 366: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x366;
+    interpret(chip8, cpu);
+    // 368: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 36A: 660F  LD V6, 0x0F
+    cpu.v[6] = 0x0F;
+    // 36C: 8666  SHR V6, V6
+    let lsb = cpu.v[6] & 1;
+    cpu.v[6] >>= 1;
+    cpu.v[15] = lsb;
+    // 36E: 4607  SNE V6, 0x07
+    cpu.pc = if cpu.v[6] != 0x07 { 0x372 } else { 0x370 };
+    10
+}
+
+fn block_370(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 370: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x372;
+    1
+}
+
+fn block_372(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 372: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x372;
+    interpret(chip8, cpu);
+    // 374: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 376: A489  LD I, 0x489
+    cpu.i = 0x489;
+    // 378: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x378;
+    interpret(chip8, cpu);
+    // 37A: A4A1  LD I, 0x4A1
+    cpu.i = 0x4A1;
+    // 37C: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x37C;
+    interpret(chip8, cpu);
+    // 37E: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 380: 66E0  LD V6, 0xE0
+    cpu.v[6] = 0xE0;
+    // 382: 866E  SHL V6, V6
+    let msb = cpu.v[6] >> 7;
+    cpu.v[6] <<= 1;
+    cpu.v[15] = msb;
+    // 384: 46C0  SNE V6, 0xC0
+    cpu.pc = if cpu.v[6] != 0xC0 { 0x388 } else { 0x386 };
+    10
+}
+
+fn block_386(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 386: A465  LD I, 0x465
+    cpu.i = 0x465;
+    cpu.pc = 0x388;
+    1
+}
+
+fn block_388(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 388: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x388;
+    interpret(chip8, cpu);
+    // 38A: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 38C: A4A5  LD I, 0x4A5
+    cpu.i = 0x4A5;
+    // 38E: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x38E;
+    interpret(chip8, cpu);
+    // 390: A481  LD I, 0x481
+    cpu.i = 0x481;
+    // 392: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x392;
+    interpret(chip8, cpu);
+    // 394: A45E  LD I, 0x45E
+    cpu.i = 0x45E;
+    // 396: F165  LD V1, [I]
+    cpu.pc = 0x396;
+    interpret(chip8, cpu);
+    // 398: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 39A: 30AA  SE V0, 0xAA
+    cpu.pc = if cpu.v[0] == 0xAA { 0x39E } else { 0x39C };
+    10
+}
+
+fn block_39c(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 39C: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x39E;
+    1
+}
+
+fn block_39e(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 39E: 3155  SE V1, 0x55
+    cpu.pc = if cpu.v[1] == 0x55 { 0x3A2 } else { 0x3A0 };
+    1
+}
+
+fn block_3a0(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3A0: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x3A2;
+    1
+}
+
+fn block_3a2(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3A2: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x3A2;
+    interpret(chip8, cpu);
+    // 3A4: 6832  LD V8, 0x32
+    cpu.v[8] = 0x32;
+    // 3A6: 6936  LD V9, 0x36
+    cpu.v[9] = 0x36;
+    // 3A8: 6A3B  LD VA, 0x3B
+    cpu.v[10] = 0x3B;
+    // 3AA: 6B01  LD VB, 0x01
+    cpu.v[11] = 0x01;
+    // 3AC: A4A5  LD I, 0x4A5
+    cpu.i = 0x4A5;
+    // 3AE: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x3AE;
+    interpret(chip8, cpu);
+    // 3B0: A47D  LD I, 0x47D
+    cpu.i = 0x47D;
+    // 3B2: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x3B2;
+    interpret(chip8, cpu);
+    // 3B4: A45E  LD I, 0x45E
+    cpu.i = 0x45E;
+    // 3B6: 6000  LD V0, 0x00
+    cpu.v[0] = 0x00;
+    // 3B8: 6130  LD V1, 0x30
+    cpu.v[1] = 0x30;
+    // 3BA: F155  LD [I], V1
+    cpu.pc = 0x3BA;
+    interpret(chip8, cpu);
+    13
+}
+
+fn block_3bc(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3BC: A45E  LD I, 0x45E
+    cpu.i = 0x45E;
+    // 3BE: F065  LD V0, [I]
+    cpu.pc = 0x3BE;
+    interpret(chip8, cpu);
+    // 3C0: 8100  LD V1, V0
+    cpu.v[1] = cpu.v[0];
+    // 3C2: A45F  LD I, 0x45F
+    cpu.i = 0x45F;
+    // 3C4: F065  LD V0, [I]
+    cpu.pc = 0x3C4;
+    interpret(chip8, cpu);
+    // 3C6: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 3C8: 3030  SE V0, 0x30
+    cpu.pc = if cpu.v[0] == 0x30 { 0x3CC } else { 0x3CA };
+    7
+}
+
+fn block_3ca(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3CA: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x3CC;
+    1
+}
+
+fn block_3cc(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3CC: 3100  SE V1, 0x00
+    cpu.pc = if cpu.v[1] == 0x00 { 0x3D0 } else { 0x3CE };
+    1
+}
+
+fn block_3ce(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3CE: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x3D0;
+    1
+}
+
+fn block_3d0(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3D0: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x3D0;
+    interpret(chip8, cpu);
+    // 3D2: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 3D4: A4A5  LD I, 0x4A5
+    cpu.i = 0x4A5;
+    // 3D6: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x3D6;
+    interpret(chip8, cpu);
+    // 3D8: A475  LD I, 0x475
+    cpu.i = 0x475;
+    // 3DA: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x3DA;
+    interpret(chip8, cpu);
+    // 3DC: A45E  LD I, 0x45E
+    cpu.i = 0x45E;
+    // 3DE: 6689  LD V6, 0x89
+    cpu.v[6] = 0x89;
+    // 3E0: F633  LD B, V6
+    cpu.pc = 0x3E0;
+    interpret(chip8, cpu);
+    9
+}
+
+fn block_3e2(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3E2: F265  LD V2, [I]
+    cpu.pc = 0x3E2;
+    interpret(chip8, cpu);
+    // 3E4: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 3E6: 3001  SE V0, 0x01
+    cpu.pc = if cpu.v[0] == 0x01 { 0x3EA } else { 0x3E8 };
+    3
+}
+
+fn block_3e8(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3E8: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x3EA;
+    1
+}
+
+fn block_3ea(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3EA: 3103  SE V1, 0x03
+    cpu.pc = if cpu.v[1] == 0x03 { 0x3EE } else { 0x3EC };
+    1
+}
+
+fn block_3ec(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3EC: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x3EE;
+    1
+}
+
+fn block_3ee(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3EE: 3207  SE V2, 0x07
+    cpu.pc = if cpu.v[2] == 0x07 { 0x3F2 } else { 0x3F0 };
+    1
+}
+
+fn block_3f0(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3F0: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x3F2;
+    1
+}
+
+fn block_3f2(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 3F2: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x3F2;
+    interpret(chip8, cpu);
+    // 3F4: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 3F6: A4A5  LD I, 0x4A5
+    cpu.i = 0x4A5;
+    // 3F8: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x3F8;
+    interpret(chip8, cpu);
+    // 3FA: A4A1  LD I, 0x4A1
+    cpu.i = 0x4A1;
+    // 3FC: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x3FC;
+    interpret(chip8, cpu);
+    // 3FE: A461  LD I, 0x461
+    cpu.i = 0x461;
+    // 400: 6604  LD V6, 0x04
+    cpu.v[6] = 0x04;
+    // 402: F61E  ADD I, V6
+    cpu.i = cpu.i.wrapping_add(cpu.v[6] as u16);
+    // 404: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x404;
+    interpret(chip8, cpu);
+    // 406: 7B05  ADD VB, 0x05
+    cpu.v[11] = cpu.v[11].wrapping_add(0x05);
+    // 408: A4A9  LD I, 0x4A9
+    cpu.i = 0x4A9;
+    // 40A: D8B4  DRW V8, VB, 4
+    cpu.pc = 0x40A;
+    interpret(chip8, cpu);
+    // 40C: A4AD  LD I, 0x4AD
+    cpu.i = 0x4AD;
+    // 40E: D9B4  DRW V9, VB, 4
+    cpu.pc = 0x40E;
+    interpret(chip8, cpu);
+    // 410: A465  LD I, 0x465
+    cpu.i = 0x465;
+    // 412: 66FF  LD V6, 0xFF
+    cpu.v[6] = 0xFF;
+    // 414: 760A  ADD V6, 0x0A
+    cpu.v[6] = cpu.v[6].wrapping_add(0x0A);
+    // 416: 3609  SE V6, 0x09
+    cpu.pc = if cpu.v[6] == 0x09 { 0x41A } else { 0x418 };
+    19
+}
+
+fn block_418(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 418: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x41A;
+    1
+}
+
+fn block_41a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 41A: 8666  SHR V6, V6
+    let lsb = cpu.v[6] & 1;
+    cpu.v[6] >>= 1;
+    cpu.v[15] = lsb;
+    // 41C: 3604  SE V6, 0x04
+    cpu.pc = if cpu.v[6] == 0x04 { 0x420 } else { 0x41E };
+    2
+}
+
+fn block_41e(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 41E: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x420;
+    1
+}
+
+fn block_420(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 420: 66FF  LD V6, 0xFF
+    cpu.v[6] = 0xFF;
+    // 422: 600A  LD V0, 0x0A
+    cpu.v[0] = 0x0A;
+    // 424: 8604  ADD V6, V0
+    let (sum, carry) = cpu.v[6].overflowing_add(cpu.v[0]);
+    cpu.v[6] = sum;
+    cpu.v[15] = carry as u8;
+    // 426: 3609  SE V6, 0x09
+    cpu.pc = if cpu.v[6] == 0x09 { 0x42A } else { 0x428 };
+    4
+}
+
+fn block_428(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 428: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x42A;
+    1
+}
+
+fn block_42a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 42A: 8666  SHR V6, V6
+    let lsb = cpu.v[6] & 1;
+    cpu.v[6] >>= 1;
+    cpu.v[15] = lsb;
+    // 42C: 3604  SE V6, 0x04
+    cpu.pc = if cpu.v[6] == 0x04 { 0x430 } else { 0x42E };
+    2
+}
+
+fn block_42e(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 42E: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x430;
+    1
+}
+
+fn block_430(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 430: 66FF  LD V6, 0xFF
+    cpu.v[6] = 0xFF;
+    // 432: 866E  SHL V6, V6
+    let msb = cpu.v[6] >> 7;
+    cpu.v[6] <<= 1;
+    cpu.v[15] = msb;
+    // 434: 8666  SHR V6, V6
+    let lsb = cpu.v[6] & 1;
+    cpu.v[6] >>= 1;
+    cpu.v[15] = lsb;
+    // 436: 367F  SE V6, 0x7F
+    cpu.pc = if cpu.v[6] == 0x7F { 0x43A } else { 0x438 };
+    4
+}
+
+fn block_438(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 438: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x43A;
+    1
+}
+
+fn block_43a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 43A: 8666  SHR V6, V6
+    let lsb = cpu.v[6] & 1;
+    cpu.v[6] >>= 1;
+    cpu.v[15] = lsb;
+    // 43C: 866E  SHL V6, V6
+    let msb = cpu.v[6] >> 7;
+    cpu.v[6] <<= 1;
+    cpu.v[15] = msb;
+    // 43E: 367E  SE V6, 0x7E
+    cpu.pc = if cpu.v[6] == 0x7E { 0x442 } else { 0x440 };
+    3
+}
+
+fn block_440(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 440: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x442;
+    1
+}
+
+fn block_442(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 442: 6605  LD V6, 0x05
+    cpu.v[6] = 0x05;
+    // 444: 76F6  ADD V6, 0xF6
+    cpu.v[6] = cpu.v[6].wrapping_add(0xF6);
+    // 446: 36FB  SE V6, 0xFB
+    cpu.pc = if cpu.v[6] == 0xFB { 0x44A } else { 0x448 };
+    3
+}
+
+fn block_448(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 448: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x44A;
+    1
+}
+
+fn block_44a(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 44A: 6605  LD V6, 0x05
+    cpu.v[6] = 0x05;
+    // 44C: 8605  SUB V6, V0
+    let no_borrow = cpu.v[6] >= cpu.v[0];
+    cpu.v[6] = cpu.v[6].wrapping_sub(cpu.v[0]);
+    cpu.v[15] = no_borrow as u8;
+    // 44E: 36FB  SE V6, 0xFB
+    cpu.pc = if cpu.v[6] == 0xFB { 0x452 } else { 0x450 };
+    3
+}
+
+fn block_450(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 450: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x452;
+    1
+}
+
+fn block_452(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 452: 6605  LD V6, 0x05
+    cpu.v[6] = 0x05;
+    // 454: 8067  SUBN V0, V6
+    let no_borrow = cpu.v[6] >= cpu.v[0];
+    cpu.v[0] = cpu.v[6].wrapping_sub(cpu.v[0]);
+    cpu.v[15] = no_borrow as u8;
+    // 456: 30FB  SE V0, 0xFB
+    cpu.pc = if cpu.v[0] == 0xFB { 0x45A } else { 0x458 };
+    3
+}
+
+fn block_458(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 458: A461  LD I, 0x461
+    cpu.i = 0x461;
+    cpu.pc = 0x45A;
+    1
+}
+
+fn block_45a(chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 45A: DAB4  DRW VA, VB, 4
+    cpu.pc = 0x45A;
+    interpret(chip8, cpu);
+    1
+}
+
+fn block_45c(_chip8: &mut Chip8, cpu: &mut Cpu) -> u64 {
+    // 45C: 145C  JP 0x45C
+    cpu.pc = 0x45C;
+    1
+}